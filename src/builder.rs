@@ -0,0 +1,580 @@
+use anyhow::Error;
+use libusb1_sys::constants::{LIBUSB_CLASS_VIDEO, LIBUSB_ENDPOINT_IN, LIBUSB_TRANSFER_TYPE_BULK, LIBUSB_TRANSFER_TYPE_INTERRUPT};
+use uuid::Uuid;
+
+use crate::TreeNode;
+use crate::usb_proto::{DescriptorConfig, DescriptorCsEndpoint, DescriptorEndpoint, DescriptorInterface, DescriptorTypes, ep_attr_to_u8, EndpointAttrSyncType, EndpointAttrTransferType, EndpointAttrUsageType, UsbInterfaceAssocDescriptor};
+use crate::uvc_proto::{DescriptorUvcFormatMjpeg, DescriptorUvcFormatUncompressed, DescriptorUvcFrameMjpeg, DescriptorUvcFrameUncompressed, DescriptorUvcInputHeader, UncompressedFormats, UvcExtensionUnitDescriptor, UvcHeaderDescriptor, UvcInputTerminalDescriptor, UvcInterfaceSubClass, UvcOutputTerminalDescriptor, UvcProcessingUnitDescriptor};
+
+const UVC_ITT_CAMERA: u16 = 0x0201;
+const UVC_TT_STREAMING: u16 = 0x0101;
+const UVC_EP_INTERRUPT: u8 = 0x03;
+// bEndpointAddress has four bits for the endpoint number
+const MAX_EP_NUM: u8 = 15;
+
+/// Builds a configuration descriptor tree from scratch, e.g. for a USB gadget personality.
+///
+/// Interface numbers, endpoint addresses, unit IDs and format/frame indices are assigned in the
+/// order things are added; `build` then runs `fix_tree` so lengths and counts are consistent.
+/// Misuse, e.g. streaming without a control interface or running out of endpoint numbers, makes
+/// `build` fail.
+///
+/// ```
+/// # use libusb1_sys::constants::LIBUSB_CLASS_VIDEO;
+/// # use usb_desc_parse::builder::ConfigBuilder;
+/// # use usb_desc_parse::uvc_proto::UvcInterfaceSubClass;
+/// let root = ConfigBuilder::new()
+///     .iad(LIBUSB_CLASS_VIDEO, UvcInterfaceSubClass::VideoInterfaceCollection as u8)
+///     .uvc_control(|c| c.camera(0).processing_unit(0))
+///     .uvc_streaming(|s| s.mjpeg().frame(1920, 1080, &[30, 15]))
+///     .build()
+///     .unwrap();
+/// ```
+pub struct ConfigBuilder {
+    config: DescriptorConfig,
+    children: Vec<TreeNode>,
+    open_iad: Option<TreeNode>,
+    next_iface: u8,
+    next_ep: u8,
+    // (control interface, output terminal id, streaming interfaces) for each uvc_control call
+    uvc_functions: Vec<(u8, u8, Vec<u8>)>,
+    // the first misuse, returned by build
+    error: Option<Error>,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> ConfigBuilder {
+        let config = DescriptorConfig {
+            w_total_length: 0,
+            b_num_interfaces: 0,
+            b_configuration_value: 1,
+            i_configuration: 0,
+            bm_attributes: 0x80,
+            b_max_power: 250,
+        };
+        ConfigBuilder { config, children: vec![], open_iad: None, next_iface: 0, next_ep: 1, uvc_functions: vec![], error: None }
+    }
+
+    /// Maximum bus power in mA (bMaxPower is in 2mA units).
+    pub fn max_power(mut self, milliamps: u16) -> ConfigBuilder {
+        self.config.b_max_power = (milliamps / 2).min(u8::MAX as u16) as u8;
+        self
+    }
+
+    pub fn self_powered(mut self) -> ConfigBuilder {
+        self.config.bm_attributes |= 1 << 6;
+        self
+    }
+
+    /// Starts an interface association; interfaces added until the next `iad` or `end_iad` belong to it.
+    pub fn iad(mut self, function_class: u8, function_sub_class: u8) -> ConfigBuilder {
+        self = self.end_iad();
+        let assoc = UsbInterfaceAssocDescriptor {
+            b_first_interface: self.next_iface,
+            b_interface_count: 0,
+            b_function_class: function_class,
+            b_function_sub_class: function_sub_class,
+            b_function_protocol: 0,
+            i_function: 0,
+        };
+        self.open_iad = Some(TreeNode { children: vec![], parsed: DescriptorTypes::InterfaceAssociation(assoc), extra: vec![] });
+        self
+    }
+
+    pub fn end_iad(mut self) -> ConfigBuilder {
+        if let Some(mut node) = self.open_iad.take() {
+            if let DescriptorTypes::InterfaceAssociation(ref mut assoc) = node.parsed {
+                assoc.b_interface_count = node.children.len() as u8;
+            }
+            self.children.push(node);
+        }
+        self
+    }
+
+    /// Adds a VideoControl interface. Without any entities a bare camera terminal is used.
+    pub fn uvc_control(mut self, f: impl FnOnce(UvcControlBuilder) -> UvcControlBuilder) -> ConfigBuilder {
+        let iface_num = self.alloc_iface();
+        let ctrl = f(UvcControlBuilder::new());
+        let interrupt_ep = ctrl.interrupt_ep.map(|_| self.alloc_ep());
+        let (node, output_terminal) = ctrl.build(iface_num, interrupt_ep);
+        self.uvc_functions.push((iface_num, output_terminal, vec![]));
+        self.push_iface(node);
+        self
+    }
+
+    /// Adds a VideoStreaming interface linked to the most recent `uvc_control`.
+    pub fn uvc_streaming(mut self, f: impl FnOnce(UvcStreamingBuilder) -> UvcStreamingBuilder) -> ConfigBuilder {
+        let iface_num = self.alloc_iface();
+        let ep_addr = self.alloc_ep() | LIBUSB_ENDPOINT_IN;
+        let terminal_link = match self.uvc_functions.last_mut() {
+            Some((_, output_terminal, streaming)) => {
+                streaming.push(iface_num);
+                *output_terminal
+            }
+            None => {
+                self.fail(anyhow!("uvc_streaming without a preceding uvc_control"));
+                0
+            }
+        };
+        let streaming = f(UvcStreamingBuilder::new());
+        match streaming.build(iface_num, ep_addr, terminal_link) {
+            Ok(node) => self.push_iface(node),
+            Err(e) => self.fail(e),
+        }
+        self
+    }
+
+    pub fn build(mut self) -> Result<TreeNode, Error> {
+        self = self.end_iad();
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let mut config = TreeNode { children: self.children, parsed: DescriptorTypes::Config(self.config), extra: vec![] };
+        for (vc_iface, _, streaming) in self.uvc_functions.iter() {
+            if let Some(vc) = find_iface_mut(&mut config, *vc_iface) {
+                link_uvc_header(vc, streaming);
+            }
+        }
+        let mut root = TreeNode::new();
+        root.children.push(config);
+        root.fix_tree();
+        Ok(root)
+    }
+
+    fn alloc_iface(&mut self) -> u8 {
+        let num = self.next_iface;
+        match num.checked_add(1) {
+            Some(next) => self.next_iface = next,
+            None => self.fail(anyhow!("More than {} interfaces", u8::MAX as u16 + 1)),
+        }
+        num
+    }
+
+    fn alloc_ep(&mut self) -> u8 {
+        let num = self.next_ep;
+        if num > MAX_EP_NUM {
+            self.fail(anyhow!("More than {} endpoints in each direction", MAX_EP_NUM));
+        }
+        self.next_ep = num.saturating_add(1);
+        num
+    }
+
+    fn fail(&mut self, error: Error) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+
+    fn push_iface(&mut self, node: TreeNode) {
+        match self.open_iad.as_mut() {
+            Some(iad) => iad.children.push(node),
+            None => self.children.push(node),
+        }
+    }
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        ConfigBuilder::new()
+    }
+}
+
+fn find_iface_mut(node: &mut TreeNode, num: u8) -> Option<&mut TreeNode> {
+    if let DescriptorTypes::Interface(iface) = &node.parsed {
+        if iface.b_interface_number == num && iface.b_alternate_setting == 0 {
+            return Some(node);
+        }
+    }
+    node.children.iter_mut().find_map(|child| find_iface_mut(child, num))
+}
+
+/// Lists the streaming interfaces in the VC header; `fix_tree` derives b_in_collection and the length.
+fn link_uvc_header(vc: &mut TreeNode, streaming: &[u8]) {
+    for child in vc.children.iter_mut() {
+        if let DescriptorTypes::UvcHeaderDescriptor(ref mut hdr) = child.parsed {
+            hdr.ba_interface_nr = streaming.to_vec();
+        }
+    }
+}
+
+enum UvcEntity {
+    Camera(u32),
+    ProcessingUnit(u16),
+    ExtensionUnit(Uuid, u8, Vec<u8>),
+}
+
+/// Describes the VideoControl interface. Entities are chained in the order they are added and
+/// terminated by a streaming output terminal.
+pub struct UvcControlBuilder {
+    bcd_uvc: u16,
+    dw_clock_frequency: u32,
+    entities: Vec<UvcEntity>,
+    interrupt_ep: Option<u16>,
+}
+
+impl UvcControlBuilder {
+    fn new() -> UvcControlBuilder {
+        UvcControlBuilder { bcd_uvc: 0x0110, dw_clock_frequency: 48_000_000, entities: vec![], interrupt_ep: None }
+    }
+
+    pub fn bcd_uvc(mut self, bcd_uvc: u16) -> UvcControlBuilder {
+        self.bcd_uvc = bcd_uvc;
+        self
+    }
+
+    /// Camera input terminal with the given 24-bit bmControls.
+    pub fn camera(mut self, bm_controls: u32) -> UvcControlBuilder {
+        self.entities.push(UvcEntity::Camera(bm_controls));
+        self
+    }
+
+    pub fn processing_unit(mut self, bm_controls: u16) -> UvcControlBuilder {
+        self.entities.push(UvcEntity::ProcessingUnit(bm_controls));
+        self
+    }
+
+    pub fn extension_unit(mut self, guid: Uuid, b_num_controls: u8, bm_controls: Vec<u8>) -> UvcControlBuilder {
+        self.entities.push(UvcEntity::ExtensionUnit(guid, b_num_controls, bm_controls));
+        self
+    }
+
+    /// Adds the optional status interrupt endpoint.
+    pub fn interrupt_ep(mut self, w_max_packet_size: u16) -> UvcControlBuilder {
+        self.interrupt_ep = Some(w_max_packet_size);
+        self
+    }
+
+    fn build(mut self, iface_num: u8, interrupt_ep: Option<u8>) -> (TreeNode, u8) {
+        if !self.entities.iter().any(|e| matches!(e, UvcEntity::Camera(_))) {
+            self.entities.insert(0, UvcEntity::Camera(0));
+        }
+        let mut children = vec![leaf(DescriptorTypes::UvcHeaderDescriptor(UvcHeaderDescriptor {
+            bcd_uvc: self.bcd_uvc,
+            w_total_length: 0,
+            dw_clock_frequency: self.dw_clock_frequency,
+            b_in_collection: 0,
+            ba_interface_nr: vec![],
+        }))];
+
+        let mut id = 0u8;
+        let mut source = 0u8;
+        for entity in self.entities {
+            id += 1;
+            let parsed = match entity {
+                UvcEntity::Camera(bm_controls) => {
+                    let mut xtra = vec![0u8; 6]; // focal lengths
+                    xtra.push(3);
+                    xtra.extend_from_slice(&bm_controls.to_le_bytes()[..3]);
+                    DescriptorTypes::UvcVcInputTerminal(UvcInputTerminalDescriptor {
+                        b_terminal_id: id, w_terminal_type: UVC_ITT_CAMERA, b_assoc_terminal: 0, i_terminal: 0, xtra,
+                    })
+                }
+                UvcEntity::ProcessingUnit(bm_controls) => DescriptorTypes::UvcVcProcessingUnit(UvcProcessingUnitDescriptor {
+                    b_unit_id: id, b_source_id: source, w_max_multiplier: 0, b_control_size: 2, bm_controls, i_processing: 0,
+                    xtra: if self.bcd_uvc >= 0x0110 { vec![0] } else { vec![] }, // bmVideoStandards
+                }),
+                UvcEntity::ExtensionUnit(guid, b_num_controls, bm_controls) => DescriptorTypes::UvcVcExtensionUnit(UvcExtensionUnitDescriptor {
+                    b_unit_id: id, guid_extension_code: guid, b_num_controls, b_nr_in_pins: 1, ba_source_id: vec![source],
+                    b_control_size: bm_controls.len() as u8, bm_controls, i_extension: 0,
+                }),
+            };
+            children.push(leaf(parsed));
+            source = id;
+        }
+        id += 1;
+        children.push(leaf(DescriptorTypes::UvcVcOutputTerminal(UvcOutputTerminalDescriptor {
+            b_terminal_id: id, w_terminal_type: UVC_TT_STREAMING, b_assoc_terminal: 0, b_source_id: source, i_terminal: 0,
+        })));
+
+        if let (Some(w_max_packet_size), Some(ep)) = (self.interrupt_ep, interrupt_ep) {
+            children.push(leaf(DescriptorTypes::Endpoint(DescriptorEndpoint {
+                b_endpoint_address: ep | LIBUSB_ENDPOINT_IN, bm_attributes: LIBUSB_TRANSFER_TYPE_INTERRUPT, w_max_packet_size, b_interval: 8,
+            })));
+            let [lo, hi] = w_max_packet_size.to_le_bytes();
+            children.push(leaf(DescriptorTypes::CsEndpoint(DescriptorCsEndpoint { bytes: vec![UVC_EP_INTERRUPT, lo, hi] })));
+        }
+
+        let iface = DescriptorInterface {
+            b_interface_number: iface_num,
+            b_alternate_setting: 0,
+            b_num_endpoints: self.interrupt_ep.is_some() as u8,
+            b_interface_class: LIBUSB_CLASS_VIDEO,
+            b_interface_sub_class: UvcInterfaceSubClass::VideoControl as u8,
+            b_interface_protocol: 0,
+            i_interface: 0,
+        };
+        (TreeNode { children, parsed: DescriptorTypes::Interface(iface), extra: vec![] }, id)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum UvcFormatKind {
+    Mjpeg,
+    Uncompressed(Uuid, u8),
+}
+
+struct UvcFrameSpec {
+    width: u16,
+    height: u16,
+    fps: Vec<u32>,
+}
+
+/// Describes a VideoStreaming interface. Each format call starts a new format; `frame` adds a
+/// frame to the most recent format.
+pub struct UvcStreamingBuilder {
+    formats: Vec<(UvcFormatKind, Vec<UvcFrameSpec>)>,
+    iso_packet_sizes: Vec<u16>,
+    bulk_packet_size: u16,
+    error: Option<Error>,
+}
+
+impl UvcStreamingBuilder {
+    fn new() -> UvcStreamingBuilder {
+        UvcStreamingBuilder { formats: vec![], iso_packet_sizes: vec![], bulk_packet_size: 512, error: None }
+    }
+
+    pub fn mjpeg(mut self) -> UvcStreamingBuilder {
+        self.formats.push((UvcFormatKind::Mjpeg, vec![]));
+        self
+    }
+
+    pub fn yuy2(mut self) -> UvcStreamingBuilder {
+        self.formats.push((UvcFormatKind::Uncompressed(UncompressedFormats::YUY2, 16), vec![]));
+        self
+    }
+
+    pub fn nv12(mut self) -> UvcStreamingBuilder {
+        self.formats.push((UvcFormatKind::Uncompressed(UncompressedFormats::NV12, 12), vec![]));
+        self
+    }
+
+    pub fn uncompressed(mut self, guid_format: Uuid, b_bits_per_pixel: u8) -> UvcStreamingBuilder {
+        self.formats.push((UvcFormatKind::Uncompressed(guid_format, b_bits_per_pixel), vec![]));
+        self
+    }
+
+    /// Adds a frame to the current format. The first rate becomes the default interval.
+    pub fn frame(mut self, width: u16, height: u16, fps: &[u32]) -> UvcStreamingBuilder {
+        match self.formats.last_mut() {
+            Some((_, frames)) => frames.push(UvcFrameSpec { width, height, fps: fps.to_vec() }),
+            None if self.error.is_none() => self.error = Some(anyhow!("UVC frame {}x{} added before any format", width, height)),
+            None => {}
+        }
+        self
+    }
+
+    /// Bulk streaming endpoint on alternate setting 0 (the default).
+    pub fn bulk(mut self, w_max_packet_size: u16) -> UvcStreamingBuilder {
+        self.bulk_packet_size = w_max_packet_size;
+        self.iso_packet_sizes.clear();
+        self
+    }
+
+    /// Isochronous streaming, one alternate setting per packet size.
+    pub fn isochronous(mut self, w_max_packet_sizes: &[u16]) -> UvcStreamingBuilder {
+        self.iso_packet_sizes = w_max_packet_sizes.to_vec();
+        self
+    }
+
+    fn build(self, iface_num: u8, ep_addr: u8, terminal_link: u8) -> Result<TreeNode, Error> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        // format and frame indices as well as alternate settings are single bytes, 0 is not used
+        if self.formats.len() > u8::MAX as usize || self.formats.iter().any(|(_, frames)| frames.len() > u8::MAX as usize) ||
+            self.iso_packet_sizes.len() > u8::MAX as usize {
+            return Err(anyhow!("More than {} formats, frames of a format or isochronous alternate settings", u8::MAX));
+        }
+        let mut formats = vec![];
+        for (fmt_idx, (kind, frames)) in self.formats.iter().enumerate() {
+            let b_format_index = fmt_idx as u8 + 1;
+            let frame_nodes = frames.iter().enumerate().map(|(frame_idx, spec)| {
+                leaf(uvc_frame(*kind, frame_idx as u8 + 1, spec))
+            }).collect();
+            let parsed = match kind {
+                UvcFormatKind::Mjpeg => DescriptorTypes::DescriptorUvcFormatMjpeg(DescriptorUvcFormatMjpeg {
+                    b_format_index, b_num_frame_descriptors: 0, bm_flags: 1, b_default_frame_index: 1,
+                    b_aspect_ratio_x: 0, b_aspect_ratio_y: 0, bm_interface_flags: 0, b_copy_protect: 0,
+                }),
+                UvcFormatKind::Uncompressed(guid_format, b_bits_per_pixel) => DescriptorTypes::DescriptorUvcFormatUncompressed(DescriptorUvcFormatUncompressed {
+                    b_format_index, b_num_frame_descriptors: 0, guid_format: *guid_format, b_bits_per_pixel: *b_bits_per_pixel,
+                    b_default_frame_index: 1, b_aspect_ratio_x: 0, b_aspect_ratio_y: 0, bm_interface_flags: 0, b_copy_protect: 0,
+                }),
+            };
+            formats.push(TreeNode { children: frame_nodes, parsed, extra: vec![] });
+        }
+
+        let hdr = DescriptorUvcInputHeader {
+            w_total_length: 0,
+            b_endpoint_address: ep_addr,
+            bm_info: 0,
+            b_terminal_link: terminal_link,
+            b_still_capture_method: 0,
+            b_trigger_support: 0,
+            b_trigger_usage: 0,
+            b_control_size: 1,
+            bma_controls: vec![0; self.formats.len()],
+        };
+        let mut children = vec![TreeNode { children: formats, parsed: DescriptorTypes::UvcInputHeader(hdr), extra: vec![] }];
+
+        let iface = |alt: u8, b_num_endpoints: u8| DescriptorInterface {
+            b_interface_number: iface_num,
+            b_alternate_setting: alt,
+            b_num_endpoints,
+            b_interface_class: LIBUSB_CLASS_VIDEO,
+            b_interface_sub_class: UvcInterfaceSubClass::VideoStreaming as u8,
+            b_interface_protocol: 0,
+            i_interface: 0,
+        };
+
+        if self.iso_packet_sizes.is_empty() {
+            children.push(leaf(DescriptorTypes::Endpoint(DescriptorEndpoint {
+                b_endpoint_address: ep_addr, bm_attributes: LIBUSB_TRANSFER_TYPE_BULK, w_max_packet_size: self.bulk_packet_size, b_interval: 0,
+            })));
+            return Ok(TreeNode { children, parsed: DescriptorTypes::Interface(iface(0, 1)), extra: vec![] });
+        }
+
+        let bm_attributes = ep_attr_to_u8(EndpointAttrTransferType::Isochronous, EndpointAttrSyncType::Async, EndpointAttrUsageType::Data);
+        for (idx, w_max_packet_size) in self.iso_packet_sizes.iter().enumerate() {
+            let ep = leaf(DescriptorTypes::Endpoint(DescriptorEndpoint {
+                b_endpoint_address: ep_addr, bm_attributes, w_max_packet_size: *w_max_packet_size, b_interval: 1,
+            }));
+            children.push(TreeNode { children: vec![ep], parsed: DescriptorTypes::Interface(iface(idx as u8 + 1, 1)), extra: vec![] });
+        }
+        Ok(TreeNode { children, parsed: DescriptorTypes::Interface(iface(0, 0)), extra: vec![] })
+    }
+}
+
+fn uvc_frame(kind: UvcFormatKind, b_frame_index: u8, spec: &UvcFrameSpec) -> DescriptorTypes {
+    let bits_per_pixel = match kind {
+        UvcFormatKind::Mjpeg => 16,
+        UvcFormatKind::Uncompressed(_, bpp) => bpp as u64,
+    };
+    let pixels = spec.width as u64 * spec.height as u64;
+    let dw_frame_interval: Vec<u32> = spec.fps.iter().map(|fps| 10_000_000 / (*fps).max(1)).collect();
+    let dw_default_frame_interval = dw_frame_interval.first().copied().unwrap_or(333333);
+    let min_fps = spec.fps.iter().copied().min().unwrap_or(30);
+    let max_fps = spec.fps.iter().copied().max().unwrap_or(30);
+    // 4K at 60fps is already past u32::MAX bits per second
+    let saturate = |value: u64| value.min(u32::MAX as u64) as u32;
+    let dw_min_bit_rate = saturate(pixels * bits_per_pixel * min_fps as u64);
+    let dw_max_bit_rate = saturate(pixels * bits_per_pixel * max_fps as u64);
+    let dw_max_video_frame_buffer_size = saturate(pixels * bits_per_pixel / 8);
+    match kind {
+        UvcFormatKind::Mjpeg => DescriptorTypes::DescriptorUvcFrameMjpeg(DescriptorUvcFrameMjpeg {
+            b_frame_index, bm_capabilities: 0, w_width: spec.width, w_height: spec.height, dw_min_bit_rate, dw_max_bit_rate,
+            dw_max_video_frame_buffer_size, dw_default_frame_interval, dw_frame_interval,
+        }),
+        UvcFormatKind::Uncompressed(_, _) => DescriptorTypes::DescriptorUvcFrameUncompressed(DescriptorUvcFrameUncompressed {
+            b_frame_index, bm_capabilities: 0, w_width: spec.width, w_height: spec.height, dw_min_bit_rate, dw_max_bit_rate,
+            dw_max_video_frame_buffer_size, dw_default_frame_interval, dw_frame_interval,
+        }),
+    }
+}
+
+fn leaf(parsed: DescriptorTypes) -> TreeNode {
+    TreeNode { children: vec![], parsed, extra: vec![] }
+}
+
+#[cfg(test)]
+mod test {
+    use libusb1_sys::constants::LIBUSB_CLASS_VIDEO;
+
+    use crate::builder::ConfigBuilder;
+    use crate::TreeNode;
+    use crate::usb_proto::DescriptorTypes;
+    use crate::uvc_proto::UvcInterfaceSubClass;
+
+    fn webcam() -> TreeNode {
+        ConfigBuilder::new()
+            .iad(LIBUSB_CLASS_VIDEO, UvcInterfaceSubClass::VideoInterfaceCollection as u8)
+            .uvc_control(|c| c.camera(0x0a).processing_unit(0x175b).interrupt_ep(64))
+            .uvc_streaming(|s| s
+                .mjpeg().frame(1920, 1080, &[30, 15]).frame(1280, 720, &[60, 30])
+                .yuy2().frame(640, 480, &[30])
+                .isochronous(&[192, 1024, 3072]))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn builder_round_trip() {
+        let built = webcam();
+        let mut bin = vec![];
+        built.serialize(&mut bin).unwrap();
+
+        let mut slice = &bin[..];
+        let parsed = TreeNode::deserialize(&mut slice).unwrap();
+        assert_eq!(format!("{}", parsed), format!("{}", built));
+    }
+
+    #[test]
+    fn builder_assigns_numbers() {
+        let root = webcam();
+        let config = &root.children[0];
+        match &config.parsed {
+            DescriptorTypes::Config(conf) => {
+                let mut bin = vec![];
+                root.serialize(&mut bin).unwrap();
+                assert_eq!(conf.w_total_length as usize, bin.len());
+                assert_eq!(conf.b_num_interfaces, 2);
+            }
+            _ => panic!("Expected config, got {:?}", config.parsed),
+        }
+        match &config.children[0].parsed {
+            DescriptorTypes::InterfaceAssociation(assoc) => {
+                assert_eq!(assoc.b_first_interface, 0);
+                assert_eq!(assoc.b_interface_count, 2);
+            }
+            other => panic!("Expected IAD, got {:?}", other),
+        }
+        let hdr = root.get_node(|parsed| matches!(parsed, DescriptorTypes::UvcHeaderDescriptor(_))).unwrap();
+        match &hdr.parsed {
+            DescriptorTypes::UvcHeaderDescriptor(hdr) => assert_eq!(hdr.ba_interface_nr, vec![1]),
+            _ => unreachable!(),
+        }
+        let input_hdr = root.get_node(|parsed| matches!(parsed, DescriptorTypes::UvcInputHeader(_))).unwrap();
+        match &input_hdr.parsed {
+            DescriptorTypes::UvcInputHeader(hdr) => {
+                assert_eq!(hdr.b_num_formats(), 2);
+                assert_eq!(hdr.b_terminal_link, 3);
+                assert_eq!(hdr.b_endpoint_address, 0x82);
+            }
+            _ => unreachable!(),
+        }
+        let fmt = root.get_format_by_idx(2).unwrap();
+        match &fmt.parsed {
+            DescriptorTypes::DescriptorUvcFormatUncompressed(fmt) => assert_eq!(fmt.b_num_frame_descriptors, 1),
+            other => panic!("Expected YUY2 format, got {:?}", other),
+        }
+        let frame = root.get_format_by_idx(1).unwrap().get_frame_by_idx(2).unwrap();
+        match &frame.parsed {
+            DescriptorTypes::DescriptorUvcFrameMjpeg(frame) => {
+                assert_eq!((frame.w_width, frame.w_height), (1280, 720));
+                assert_eq!(frame.dw_frame_interval, vec![166666, 333333]);
+            }
+            other => panic!("Expected MJPEG frame, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn builder_misuse() {
+        let no_control = ConfigBuilder::new().uvc_streaming(|s| s.mjpeg().frame(640, 480, &[30])).build();
+        assert!(no_control.is_err());
+        let no_format = ConfigBuilder::new().uvc_control(|c| c).uvc_streaming(|s| s.frame(640, 480, &[30]).mjpeg()).build();
+        assert!(no_format.is_err());
+
+        // endpoint 15 is the last one, the 16th streaming interface runs out
+        let mut builder = ConfigBuilder::new().uvc_control(|c| c);
+        for _ in 0..15 {
+            builder = builder.uvc_streaming(|s| s.mjpeg().frame(640, 480, &[30]));
+        }
+        let root = builder.build().unwrap();
+        assert!(root.get_node(|parsed| matches!(parsed, DescriptorTypes::Endpoint(ep) if ep.b_endpoint_address == 0x8f)).is_some());
+        let mut builder = ConfigBuilder::new().uvc_control(|c| c);
+        for _ in 0..16 {
+            builder = builder.uvc_streaming(|s| s.mjpeg().frame(640, 480, &[30]));
+        }
+        assert!(builder.build().is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::TreeNode;
+
+/// A class-specific descriptor decoded by a caller-supplied `DescriptorDecoder`.
+pub trait CustomDescriptor: Debug + Send + Sync {
+    /// Writes the complete descriptor, including the bLength/bDescriptorType header.
+    fn serialize(&self, buffer: &mut dyn Write) -> Result<(), Error>;
+
+    /// Called from `TreeNode::fix_tree` with the node's children so counts and lengths can be updated.
+    fn fix(&mut self, _children: &[TreeNode]) {}
+
+    fn clone_box(&self) -> Box<dyn CustomDescriptor>;
+}
+
+impl Clone for Box<dyn CustomDescriptor> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Custom descriptors are equal if they serialize to the same bytes.
+impl PartialEq for dyn CustomDescriptor {
+    fn eq(&self, other: &Self) -> bool {
+        custom_bytes(self) == custom_bytes(other)
+    }
+}
+
+impl Eq for dyn CustomDescriptor {}
+
+impl Hash for dyn CustomDescriptor {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        custom_bytes(self).hash(state);
+    }
+}
+
+/// A custom descriptor kept as its raw bytes (including the header), e.g. after deserializing a
+/// tree with serde, where the decoder that produced the original is not known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawDescriptor {
+    pub bytes: Vec<u8>,
+}
+
+impl CustomDescriptor for RawDescriptor {
+    fn serialize(&self, buffer: &mut dyn Write) -> Result<(), Error> {
+        buffer.write_all(&self.bytes)?;
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn CustomDescriptor> {
+        Box::new(self.clone())
+    }
+}
+
+/// Custom descriptors are written as their bytes and read back as `RawDescriptor`.
+#[cfg(feature = "serde")]
+pub(crate) mod custom_serde {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{CustomDescriptor, RawDescriptor};
+
+    #[allow(clippy::borrowed_box)] // the signature serde's `with` expects
+    pub fn serialize<S: Serializer>(desc: &Box<dyn CustomDescriptor>, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut bytes = vec![];
+        desc.serialize(&mut bytes).map_err(serde::ser::Error::custom)?;
+        bytes.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Box<dyn CustomDescriptor>, D::Error> {
+        Ok(Box::new(RawDescriptor { bytes: Vec::deserialize(deserializer)? }))
+    }
+}
+
+/// The serialized descriptor, or its Debug output if it can not be serialized.
+pub(crate) fn custom_bytes(desc: &dyn CustomDescriptor) -> Vec<u8> {
+    let mut bytes = vec![];
+    match desc.serialize(&mut bytes) {
+        Ok(()) => bytes,
+        Err(_) => format!("{:?}", desc).into_bytes(),
+    }
+}
+
+/// Decodes the body of a descriptor (everything after bLength and bDescriptorType).
+/// `len` is the descriptor's bLength.
+pub trait DescriptorDecoder: Send + Sync {
+    fn decode(&self, buffer: &mut &[u8], len: u8) -> Result<Box<dyn CustomDescriptor>, Error>;
+}
+
+impl<F> DescriptorDecoder for F
+    where F: Fn(&mut &[u8], u8) -> Result<Box<dyn CustomDescriptor>, Error> + Send + Sync
+{
+    fn decode(&self, buffer: &mut &[u8], len: u8) -> Result<Box<dyn CustomDescriptor>, Error> {
+        self(buffer, len)
+    }
+}
+
+/// Identifies which descriptors a decoder handles. The class triple comes from the most recent
+/// interface descriptor; `subtype` is the byte following bDescriptorType, or None to match any.
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub struct DecoderKey {
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub desc_type: u8,
+    pub subtype: Option<u8>,
+}
+
+impl DecoderKey {
+    pub fn new(class: u8, subclass: u8, protocol: u8, desc_type: u8, subtype: Option<u8>) -> DecoderKey {
+        DecoderKey { class, subclass, protocol, desc_type, subtype }
+    }
+}
+
+#[derive(Default)]
+pub struct DecoderRegistry {
+    decoders: HashMap<DecoderKey, Box<dyn DescriptorDecoder>>,
+}
+
+impl DecoderRegistry {
+    pub fn new() -> DecoderRegistry {
+        DecoderRegistry { decoders: HashMap::new() }
+    }
+
+    /// Registers a decoder, replacing any previous one for the same key. Registered decoders take
+    /// precedence over the built-in UVC/UAC/DFU parsers.
+    pub fn register(&mut self, key: DecoderKey, decoder: Box<dyn DescriptorDecoder>) {
+        self.decoders.insert(key, decoder);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decoders.is_empty()
+    }
+
+    /// Looks up a decoder for `key`, falling back to one registered with a wildcard subtype.
+    pub fn get(&self, key: &DecoderKey) -> Option<&dyn DescriptorDecoder> {
+        self.decoders.get(key)
+            .or_else(|| self.decoders.get(&DecoderKey { subtype: None, ..*key }))
+            .map(|decoder| decoder.as_ref())
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::Write;
+
+use structure::byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::usb_proto::UsbDescriptorTypes;
+
+// DFU
+// https://www.usb.org/sites/default/files/DFU_1.1.pdf
+// https://github.com/torvalds/linux/blob/master/include/linux/usb/dfu.h (gadget side)
+
+pub const DFU_IFACE_SUBCLASS: u8 = 0x01;
+
+#[derive(FromPrimitive, Debug)]
+#[repr(u8)]
+pub enum DfuInterfaceProtocol {
+    Runtime = 0x01,
+    Dfu = 0x02,
+}
+
+#[derive(FromPrimitive, Debug)]
+#[repr(u8)]
+pub enum DfuRequestCodes {
+    Detach = 0x00,
+    Dnload = 0x01,
+    Upload = 0x02,
+    GetStatus = 0x03,
+    ClrStatus = 0x04,
+    GetState = 0x05,
+    Abort = 0x06,
+}
+
+// 4.1.3 bmAttributes
+pub const DFU_ATTR_CAN_DNLOAD: u8 = 1 << 0;
+pub const DFU_ATTR_CAN_UPLOAD: u8 = 1 << 1;
+pub const DFU_ATTR_MANIFESTATION_TOLERANT: u8 = 1 << 2;
+pub const DFU_ATTR_WILL_DETACH: u8 = 1 << 3;
+
+// 4.1.3 DFU 1.0 functional descriptors end before bcdDFUVersion
+pub const DFU_FUNCTIONAL_MIN_LEN: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DescriptorDfuFunctional {
+    pub bm_attributes: u8,
+    pub w_detach_time_out: u16,
+    pub w_transfer_size: u16,
+    /// Absent in the 7 byte descriptors of DFU 1.0 devices
+    pub bcd_dfu_version: Option<u16>,
+}
+
+impl DescriptorDfuFunctional {
+    pub fn serialize(&self, mut buffer: impl Write) {
+        buffer.write_u8(self.size() as u8).unwrap();
+        buffer.write_u8(UsbDescriptorTypes::CsDevice as u8).unwrap();
+        buffer.write_u8(self.bm_attributes).unwrap();
+        buffer.write_u16::<LittleEndian>(self.w_detach_time_out).unwrap();
+        buffer.write_u16::<LittleEndian>(self.w_transfer_size).unwrap();
+        if let Some(version) = self.bcd_dfu_version {
+            buffer.write_u16::<LittleEndian>(version).unwrap();
+        }
+    }
+    pub fn deserialize(buffer: &mut &[u8]) -> DescriptorDfuFunctional {
+        let bm_attributes = buffer.read_u8().unwrap();
+        let w_detach_time_out = buffer.read_u16::<LittleEndian>().unwrap();
+        let w_transfer_size = buffer.read_u16::<LittleEndian>().unwrap();
+        // a single byte after wTransferSize is not a version, it stays in TreeNode::extra
+        let bcd_dfu_version = if buffer.len() >= 2 { Some(buffer.read_u16::<LittleEndian>().unwrap()) } else { None };
+        DescriptorDfuFunctional { bm_attributes, w_detach_time_out, w_transfer_size, bcd_dfu_version }
+    }
+    pub fn size(&self) -> usize {
+        DFU_FUNCTIONAL_MIN_LEN + if self.bcd_dfu_version.is_some() { 2 } else { 0 }
+    }
+    pub fn capabilities(&self) -> DfuCapabilities {
+        DfuCapabilities {
+            can_download: self.bm_attributes & DFU_ATTR_CAN_DNLOAD != 0,
+            can_upload: self.bm_attributes & DFU_ATTR_CAN_UPLOAD != 0,
+            manifestation_tolerant: self.bm_attributes & DFU_ATTR_MANIFESTATION_TOLERANT != 0,
+            will_detach: self.bm_attributes & DFU_ATTR_WILL_DETACH != 0,
+            detach_timeout_ms: self.w_detach_time_out,
+            transfer_size: self.w_transfer_size,
+            bcd_dfu_version: self.bcd_dfu_version,
+        }
+    }
+}
+
+/// Decoded view of a DFU functional descriptor, answering "can this device be firmware-updated".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DfuCapabilities {
+    pub can_download: bool,
+    pub can_upload: bool,
+    /// Device stays responsive on the bus after the manifestation phase
+    pub manifestation_tolerant: bool,
+    /// Device detaches itself on DFU_DETACH, no bus reset required from the host
+    pub will_detach: bool,
+    pub detach_timeout_ms: u16,
+    pub transfer_size: u16,
+    /// Absent for DFU 1.0 devices
+    pub bcd_dfu_version: Option<u16>,
+}
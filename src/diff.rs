@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Debug;
+
+use crate::TreeNode;
+use crate::decoder::custom_bytes;
+use crate::usb_proto::DescriptorTypes;
+use crate::validate::node_label;
+
+/// A difference found by `TreeNode::diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Location of the descriptor, e.g. `Root / Config 1 / Interface 1.0 / UvcInputHeader / Format 2`
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Only in the other tree; `descriptor` is its Debug output. Its children are not listed.
+    Added { descriptor: String },
+    /// Only in this tree; `descriptor` is its Debug output. Its children are not listed.
+    Removed { descriptor: String },
+    Modified { fields: Vec<FieldChange> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ChangeKind::Added { descriptor } => write!(f, "+ {}: {}", self.path, descriptor),
+            ChangeKind::Removed { descriptor } => write!(f, "- {}: {}", self.path, descriptor),
+            ChangeKind::Modified { fields } => {
+                write!(f, "~ {}", self.path)?;
+                for field in fields.iter() {
+                    write!(f, "\n\t{}: {} -> {}", field.field, field.old, field.new)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Renders changes one per line (modified fields are indented below their descriptor).
+pub fn render_changes(changes: &[Change]) -> String {
+    changes.iter().map(|change| format!("{}\n", change)).collect()
+}
+
+impl TreeNode {
+    /// Compares this tree with `other`, which is usually a later firmware or a rewritten copy.
+    ///
+    /// Children are aligned by what they describe rather than by position: interface number and
+    /// alternate setting, endpoint address, format and frame index, unit and terminal ID. Other
+    /// descriptors are aligned by type and order of appearance among their siblings.
+    pub fn diff(&self, other: &TreeNode) -> Vec<Change> {
+        let mut changes = vec![];
+        diff_node(self, other, &mut vec![diff_key(&self.parsed)], &mut changes);
+        changes
+    }
+}
+
+fn diff_node(old: &TreeNode, new: &TreeNode, path: &mut Vec<String>, changes: &mut Vec<Change>) {
+    let mut fields = diff_fields(&old.parsed, &new.parsed);
+    if old.extra != new.extra {
+        fields.push(FieldChange { field: "extra".to_string(), old: hex_bytes(&old.extra), new: hex_bytes(&new.extra) });
+    }
+    if !fields.is_empty() {
+        changes.push(Change { path: path.join(" / "), kind: ChangeKind::Modified { fields } });
+    }
+
+    let old_keys = sibling_keys(&old.children);
+    let new_keys = sibling_keys(&new.children);
+    let new_by_key: HashMap<&String, usize> = new_keys.iter().enumerate().map(|(idx, key)| (key, idx)).collect();
+    let mut matched = vec![false; new.children.len()];
+    for (old_child, key) in old.children.iter().zip(old_keys.iter()) {
+        path.push(key.clone());
+        match new_by_key.get(key) {
+            Some(&idx) => {
+                matched[idx] = true;
+                diff_node(old_child, &new.children[idx], path, changes);
+            }
+            None => changes.push(Change { path: path.join(" / "), kind: ChangeKind::Removed { descriptor: format!("{:?}", old_child.parsed) } }),
+        }
+        path.pop();
+    }
+    for ((new_child, key), matched) in new.children.iter().zip(new_keys.iter()).zip(matched) {
+        if !matched {
+            path.push(key.clone());
+            changes.push(Change { path: path.join(" / "), kind: ChangeKind::Added { descriptor: format!("{:?}", new_child.parsed) } });
+            path.pop();
+        }
+    }
+}
+
+/// Identity keys of `children`, with a `#n` suffix on the n-th repetition of a key.
+fn sibling_keys(children: &[TreeNode]) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    children.iter().map(|child| {
+        let key = diff_key(&child.parsed);
+        let count = seen.entry(key.clone()).or_default();
+        *count += 1;
+        match count {
+            1 => key,
+            n => format!("{} #{}", key, n),
+        }
+    }).collect()
+}
+
+fn diff_key(parsed: &DescriptorTypes) -> String {
+    match parsed {
+        DescriptorTypes::InterfaceAssociation(assoc) => format!("IAD {}", assoc.b_first_interface),
+        DescriptorTypes::UvcVcInputTerminal(desc) => format!("UvcVcInputTerminal {}", desc.b_terminal_id),
+        DescriptorTypes::UvcVcOutputTerminal(desc) => format!("UvcVcOutputTerminal {}", desc.b_terminal_id),
+        DescriptorTypes::UvcVcProcessingUnit(desc) => format!("UvcVcProcessingUnit {}", desc.b_unit_id),
+        DescriptorTypes::UvcVcExtensionUnit(desc) => format!("UvcVcExtensionUnit {}", desc.b_unit_id),
+        DescriptorTypes::UacInputTerminal(desc) => format!("UacInputTerminal {}", desc.b_terminal_id),
+        DescriptorTypes::UacOutputTerminal(desc) => format!("UacOutputTerminal {}", desc.b_terminal_id),
+        DescriptorTypes::UacFeatureUnit(desc) => format!("UacFeatureUnit {}", desc.b_unit_id),
+        other => node_label(other),
+    }
+}
+
+/// Compares two descriptors field by field. Descriptors of different types are reported as a
+/// whole, custom ones by the bytes they serialize to.
+fn diff_fields(old: &DescriptorTypes, new: &DescriptorTypes) -> Vec<FieldChange> {
+    if old == new {
+        return vec![];
+    }
+    macro_rules! fields {
+        ($old:expr, $new:expr, $($field:ident),+) => {
+            [$(field_change(stringify!($field), &$old.$field, &$new.$field)),+].into_iter().flatten().collect()
+        };
+    }
+    use DescriptorTypes::*;
+    match (old, new) {
+        (Unknown(o), Unknown(n)) => fields!(o, n, desc_type, bytes),
+        (Device(o), Device(n)) => fields!(o, n, bcd_usb, b_device_class, b_device_sub_class, b_device_protocol, b_max_packet_size0,
+            id_vendor, id_product, bcd_device, i_manufacturer, i_product, i_serial_number, b_num_configurations),
+        (CsDevice(o), CsDevice(n)) => fields!(o, n, bytes),
+        (DfuFunctional(o), DfuFunctional(n)) => fields!(o, n, bm_attributes, w_detach_time_out, w_transfer_size, bcd_dfu_version),
+        (Config(o), Config(n)) => fields!(o, n, w_total_length, b_num_interfaces, b_configuration_value, i_configuration, bm_attributes, b_max_power),
+        (Interface(o), Interface(n)) => fields!(o, n, b_interface_number, b_alternate_setting, b_num_endpoints, b_interface_class,
+            b_interface_sub_class, b_interface_protocol, i_interface),
+        (CsInterface(o), CsInterface(n)) => fields!(o, n, bytes),
+        (InterfaceAssociation(o), InterfaceAssociation(n)) => fields!(o, n, b_first_interface, b_interface_count, b_function_class,
+            b_function_sub_class, b_function_protocol, i_function),
+        (Endpoint(o), Endpoint(n)) | (HidEndpoint(o), HidEndpoint(n)) => fields!(o, n, b_endpoint_address, bm_attributes, w_max_packet_size, b_interval),
+        (UacEndpoint(o), UacEndpoint(n)) => fields!(o, n, b_endpoint_address, bm_attributes, w_max_packet_size, b_interval, b_refresh, b_synch_address),
+        (SsEpComp(o), SsEpComp(n)) => fields!(o, n, b_max_burst, bm_attributes, w_bytes_per_interval),
+        (SspIsochEpComp(o), SspIsochEpComp(n)) => fields!(o, n, w_reserved, dw_bytes_per_interval),
+        (CsEndpoint(o), CsEndpoint(n)) => fields!(o, n, bytes),
+        (UvcInputHeader(o), UvcInputHeader(n)) => fields!(o, n, w_total_length, b_endpoint_address, bm_info, b_terminal_link,
+            b_still_capture_method, b_trigger_support, b_trigger_usage, b_control_size, bma_controls),
+        (UacAcHeader(o), UacAcHeader(n)) => fields!(o, n, bcd_adc, w_total_length, b_in_collection, ba_interface_nr),
+        (UacInputTerminal(o), UacInputTerminal(n)) => fields!(o, n, b_terminal_id, w_terminal_type, b_assoc_terminal, b_nr_channels,
+            w_channel_config, i_channel_names, i_terminal),
+        (UacOutputTerminal(o), UacOutputTerminal(n)) => fields!(o, n, b_terminal_id, w_terminal_type, b_assoc_terminal, b_source_id, i_terminal),
+        (UacFeatureUnit(o), UacFeatureUnit(n)) => fields!(o, n, b_unit_id, b_source_id, b_control_size, bma_controls),
+        (UacAsGeneral(o), UacAsGeneral(n)) => fields!(o, n, b_terminal_link, b_delay, w_format_tag),
+        (UacFormatTypeI(o), UacFormatTypeI(n)) => fields!(o, n, b_nr_channels, b_subframe_size, b_bit_resolution, b_sam_freq_type, t_sam_freq),
+        (UacFormatTypeUnknown(o), UacFormatTypeUnknown(n)) => fields!(o, n, format_type, bytes),
+        (UacIsoEndpointDescriptor(o), UacIsoEndpointDescriptor(n)) => fields!(o, n, b_descriptor_subtype, bm_attributes, b_lock_delay_units, w_lock_delay),
+        (DescriptorUvcFormatUncompressed(o), DescriptorUvcFormatUncompressed(n)) => fields!(o, n, b_format_index, b_num_frame_descriptors,
+            guid_format, b_bits_per_pixel, b_default_frame_index, b_aspect_ratio_x, b_aspect_ratio_y, bm_interface_flags, b_copy_protect),
+        (DescriptorUvcFormatMjpeg(o), DescriptorUvcFormatMjpeg(n)) => fields!(o, n, b_format_index, b_num_frame_descriptors, bm_flags,
+            b_default_frame_index, b_aspect_ratio_x, b_aspect_ratio_y, bm_interface_flags, b_copy_protect),
+        (DescriptorUvcFrameUncompressed(o), DescriptorUvcFrameUncompressed(n)) => fields!(o, n, b_frame_index, bm_capabilities, w_width, w_height,
+            dw_min_bit_rate, dw_max_bit_rate, dw_max_video_frame_buffer_size, dw_default_frame_interval, dw_frame_interval),
+        (DescriptorUvcFrameMjpeg(o), DescriptorUvcFrameMjpeg(n)) => fields!(o, n, b_frame_index, bm_capabilities, w_width, w_height,
+            dw_min_bit_rate, dw_max_bit_rate, dw_max_video_frame_buffer_size, dw_default_frame_interval, dw_frame_interval),
+        (UvcFormatFrameBased(o), UvcFormatFrameBased(n)) => fields!(o, n, b_format_index, b_num_frame_descriptors, guid_format, b_bits_per_pixel,
+            b_default_frame_index, b_aspect_ratio_x, b_aspect_ratio_y, bm_interface_flags, b_copy_protect, b_variable_size),
+        (UvcFrameFrameBased(o), UvcFrameFrameBased(n)) => fields!(o, n, b_frame_index, bm_capabilities, w_width, w_height,
+            dw_min_bit_rate, dw_max_bit_rate, dw_default_frame_interval, dw_bytes_per_line, dw_frame_interval),
+        (DescriptorUvcVsInterfaceUnknown(o), DescriptorUvcVsInterfaceUnknown(n)) => fields!(o, n, iface_subclass, bytes),
+        (DescriptorUvcVcInterfaceUnknown(o), DescriptorUvcVcInterfaceUnknown(n)) => fields!(o, n, iface_subclass, bytes),
+        (DescriptorUacInterfaceUnknown(o), DescriptorUacInterfaceUnknown(n)) => fields!(o, n, iface_subclass, bytes),
+        (UvcHeaderDescriptor(o), UvcHeaderDescriptor(n)) => fields!(o, n, bcd_uvc, w_total_length, dw_clock_frequency, b_in_collection, ba_interface_nr),
+        (UvcVcInputTerminal(o), UvcVcInputTerminal(n)) => fields!(o, n, b_terminal_id, w_terminal_type, b_assoc_terminal, i_terminal, xtra),
+        (UvcVcProcessingUnit(o), UvcVcProcessingUnit(n)) => fields!(o, n, b_unit_id, b_source_id, w_max_multiplier, b_control_size,
+            bm_controls, i_processing, xtra),
+        (UvcVcExtensionUnit(o), UvcVcExtensionUnit(n)) => fields!(o, n, b_unit_id, guid_extension_code, b_num_controls, b_nr_in_pins,
+            ba_source_id, b_control_size, bm_controls, i_extension),
+        (UvcVcOutputTerminal(o), UvcVcOutputTerminal(n)) => fields!(o, n, b_terminal_id, w_terminal_type, b_assoc_terminal, b_source_id, i_terminal),
+        (Custom(o), Custom(n)) => vec![FieldChange {
+            field: "bytes".to_string(), old: hex_bytes(&custom_bytes(o.as_ref())), new: hex_bytes(&custom_bytes(n.as_ref())),
+        }],
+        _ => vec![FieldChange { field: "descriptor".to_string(), old: format!("{:?}", old), new: format!("{:?}", new) }],
+    }
+}
+
+fn field_change<T: PartialEq + Debug>(field: &str, old: &T, new: &T) -> Option<FieldChange> {
+    (old != new).then(|| FieldChange { field: field.to_string(), old: format!("{:?}", old), new: format!("{:?}", new) })
+}
+
+/// Raw bytes as `[de, ad]`.
+fn hex_bytes(bytes: &[u8]) -> String {
+    format!("{:02x?}", bytes)
+}
+
+/// Splits `Variant(Struct { a: 1, b: [2, 3] })` into `Variant(Struct` and its top-level fields.
+pub(crate) fn debug_fields(debug: &str) -> Option<(&str, Vec<(&str, &str)>)> {
+    let open = debug.find('{')?;
+    let close = debug.rfind('}')?;
+    let body = debug.get(open + 1..close)?.trim();
+    let mut fields = vec![];
+    let mut depth = 0;
+    let mut in_str = false;
+    let mut start = 0;
+    let mut prev = ' ';
+    for (idx, c) in body.char_indices() {
+        match c {
+            '"' if prev != '\\' => in_str = !in_str,
+            '(' | '[' | '{' if !in_str => depth += 1,
+            ')' | ']' | '}' if !in_str => depth -= 1,
+            ',' if !in_str && depth == 0 => {
+                fields.push(debug_field(&body[start..idx])?);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    if !body[start..].trim().is_empty() {
+        fields.push(debug_field(&body[start..])?);
+    }
+    Some((debug[..open].trim(), fields))
+}
+
+fn debug_field(field: &str) -> Option<(&str, &str)> {
+    let (name, value) = field.split_once(':')?;
+    Some((name.trim(), value.trim()))
+}
@@ -0,0 +1,252 @@
+use libusb1_sys::constants::LIBUSB_CLASS_AUDIO;
+use uuid::Uuid;
+
+use crate::TreeNode;
+use crate::repair::{Repair, UvcIndexMap};
+use crate::uac_proto::UacInterfaceSubclass;
+use crate::usb_proto::DescriptorTypes;
+
+const INTERVALS_PER_SECOND: u32 = 10_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum UvcFormatKind {
+    Mjpeg,
+    Uncompressed,
+    FrameBased,
+}
+
+/// Which video formats, resolutions and frame rates to offer, applied with
+/// `TreeNode::apply_video_filter`. Every limit is optional and empty lists allow everything.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default))]
+pub struct VideoFilter {
+    pub max_fps: Option<u32>,
+    pub min_fps: Option<u32>,
+    pub max_width: Option<u16>,
+    pub max_height: Option<u16>,
+    pub max_pixels: Option<u64>,
+    pub allowed_formats: Vec<UvcFormatKind>,
+    /// e.g. "MJPG", "YUY2", "NV12", "H264"; uncompressed and frame based formats use the first
+    /// four bytes of their GUID
+    pub allowed_fourccs: Vec<String>,
+    /// Keep the smallest frames of a format instead of removing the format when none pass
+    pub keep_at_least_one_frame: bool,
+}
+
+impl VideoFilter {
+    fn allows_format(&self, kind: UvcFormatKind, fourcc: &str) -> bool {
+        (self.allowed_formats.is_empty() || self.allowed_formats.contains(&kind)) &&
+            (self.allowed_fourccs.is_empty() || self.allowed_fourccs.iter().any(|allowed| allowed.eq_ignore_ascii_case(fourcc)))
+    }
+
+    fn allows_size(&self, width: u16, height: u16) -> bool {
+        !matches!(self.max_width, Some(max) if width > max) &&
+            !matches!(self.max_height, Some(max) if height > max) &&
+            !matches!(self.max_pixels, Some(max) if width as u64 * height as u64 > max)
+    }
+
+    #[allow(clippy::manual_div_ceil)] // u64::div_ceil needs Rust 1.73
+    fn allows_interval(&self, interval: u32) -> bool {
+        // dwFrameInterval is in 100ns units, so the fastest allowed rate is the smallest interval
+        !matches!(self.max_fps, Some(fps) if fps != 0 && interval < INTERVALS_PER_SECOND / fps) &&
+            !matches!(self.min_fps, Some(fps) if fps != 0 && interval as u64 > (INTERVALS_PER_SECOND as u64 + fps as u64 - 1) / fps as u64)
+    }
+}
+
+/// Which audio streaming alternate settings to offer, applied with `TreeNode::apply_audio_filter`.
+/// Empty lists allow everything.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default))]
+pub struct AudioFilter {
+    pub sample_rates: Vec<u32>,
+    pub channels: Vec<u8>,
+    pub bit_depths: Vec<u8>,
+    /// wFormatTag values from the AS general descriptor, e.g. 1 for PCM
+    pub formats: Vec<u16>,
+}
+
+impl AudioFilter {
+    fn allows(list: &[u8], value: u8) -> bool {
+        list.is_empty() || list.contains(&value)
+    }
+}
+
+/// What `TreeNode::apply_video_filter` changed.
+#[derive(Debug, Clone, Default)]
+pub struct VideoFilterReport {
+    pub repairs: Vec<Repair>,
+    /// Indices of every remaining frame before and after filtering, e.g. for `StreamingTranslator`
+    pub index_map: UvcIndexMap,
+}
+
+impl TreeNode {
+    /// Removes the frame intervals, frames and formats `filter` does not allow from every UVC input
+    /// header below this node, prunes bmaControls to match, closes the gaps in format and frame
+    /// indices with `renumber_uvc_indices` and then runs `fix_tree`.
+    pub fn apply_video_filter(&mut self, filter: &VideoFilter) -> VideoFilterReport {
+        filter_video_node(self, filter);
+        let index_map = self.renumber_uvc_indices();
+        VideoFilterReport { repairs: self.fix_tree(), index_map }
+    }
+
+    /// Trims the sample rates of every Type I audio streaming alternate setting below this node,
+    /// removes alternate settings `filter` does not allow and renumbers the rest. An AS interface
+    /// left with only its zero bandwidth setting is removed; `fix_tree` then drops it from the AC
+    /// header and interface association.
+    pub fn apply_audio_filter(&mut self, filter: &AudioFilter) -> Vec<Repair> {
+        filter_audio_node(self, filter);
+        self.fix_tree()
+    }
+}
+
+pub(crate) fn filter_video_node(node: &mut TreeNode, filter: &VideoFilter) {
+    if let DescriptorTypes::UvcInputHeader(_) = node.parsed {
+        let keep: Vec<bool> = node.children.iter_mut()
+            .filter(|child| child.parsed.is_uvc_format())
+            .map(|fmt| filter_format(fmt, filter))
+            .collect();
+        if let DescriptorTypes::UvcInputHeader(ref mut hdr) = node.parsed {
+            let control_size = hdr.b_control_size as usize;
+            if control_size > 0 && hdr.bma_controls.len() == keep.len() * control_size {
+                hdr.bma_controls = hdr.bma_controls.chunks(control_size).zip(keep.iter())
+                    .filter(|(_, keep)| **keep)
+                    .flat_map(|(controls, _)| controls.to_vec())
+                    .collect();
+            }
+        }
+        let mut keep = keep.into_iter();
+        node.children.retain(|child| !child.parsed.is_uvc_format() || keep.next().unwrap_or(true));
+        return;
+    }
+    node.children.iter_mut().for_each(|child| filter_video_node(child, filter));
+}
+
+fn filter_audio_node(node: &mut TreeNode, filter: &AudioFilter) {
+    node.children.retain_mut(|child| {
+        if !is_audio_streaming(child) {
+            return true;
+        }
+        let had_alts = child.children.iter().any(|alt| matches!(alt.parsed, DescriptorTypes::Interface(_)));
+        child.children.retain_mut(|alt| match alt.parsed {
+            DescriptorTypes::Interface(_) => filter_audio_alt(alt, filter),
+            _ => true,
+        });
+        let mut alt_setting = 0;
+        child.children.iter_mut().for_each(|alt| if let DescriptorTypes::Interface(ref mut iface) = alt.parsed {
+            alt_setting += 1;
+            iface.b_alternate_setting = alt_setting;
+        });
+        !had_alts || alt_setting > 0
+    });
+    node.children.iter_mut().for_each(|child| filter_audio_node(child, filter));
+}
+
+fn is_audio_streaming(node: &TreeNode) -> bool {
+    match &node.parsed {
+        DescriptorTypes::Interface(iface) => iface.b_interface_class == LIBUSB_CLASS_AUDIO &&
+            iface.b_interface_sub_class == UacInterfaceSubclass::AudioStreaming as u8 && iface.b_alternate_setting == 0,
+        _ => false,
+    }
+}
+
+/// Filters one AS alternate setting, returning whether it should be kept.
+#[allow(clippy::manual_div_ceil)] // u32::div_ceil needs Rust 1.73
+fn filter_audio_alt(alt: &mut TreeNode, filter: &AudioFilter) -> bool {
+    let format_tag = alt.children.iter().find_map(|child| match &child.parsed {
+        DescriptorTypes::UacAsGeneral(hdr) => Some(hdr.w_format_tag),
+        _ => None,
+    });
+    if let Some(format_tag) = format_tag {
+        if !filter.formats.is_empty() && !filter.formats.contains(&format_tag) {
+            return false;
+        }
+    }
+
+    let mut max_packet_size = None;
+    for child in alt.children.iter_mut() {
+        if let DescriptorTypes::UacFormatTypeI(ref mut fmt) = child.parsed {
+            if !AudioFilter::allows(&filter.channels, fmt.b_nr_channels) || !AudioFilter::allows(&filter.bit_depths, fmt.b_bit_resolution) {
+                return false;
+            }
+            // b_sam_freq_type 0 is a continuous range, which is left alone
+            if fmt.b_sam_freq_type == 0 || filter.sample_rates.is_empty() {
+                continue;
+            }
+            let before = fmt.t_sam_freq.len();
+            fmt.t_sam_freq.retain(|freq| filter.sample_rates.contains(freq));
+            if fmt.t_sam_freq.is_empty() {
+                return false;
+            }
+            fmt.b_sam_freq_type = fmt.t_sam_freq.len() as u8;
+            if fmt.t_sam_freq.len() != before {
+                // one millisecond of the fastest remaining rate plus one sample of slack
+                let max_freq = fmt.t_sam_freq.iter().max().copied().unwrap_or_default();
+                let samples = (max_freq + 999) / 1000 + 1;
+                max_packet_size = Some((samples * fmt.b_nr_channels as u32 * fmt.b_subframe_size as u32) as u16);
+            }
+        }
+    }
+    if let Some(max_packet_size) = max_packet_size {
+        alt.children.iter_mut().for_each(|child| match child.parsed {
+            DescriptorTypes::UacEndpoint(ref mut ep) => ep.w_max_packet_size = ep.w_max_packet_size.min(max_packet_size),
+            DescriptorTypes::Endpoint(ref mut ep) => ep.w_max_packet_size = ep.w_max_packet_size.min(max_packet_size),
+            _ => {}
+        });
+    }
+    true
+}
+
+/// Filters the frames of a format node, returning whether the format should be kept.
+fn filter_format(fmt: &mut TreeNode, filter: &VideoFilter) -> bool {
+    let (kind, fourcc) = match &fmt.parsed {
+        DescriptorTypes::DescriptorUvcFormatMjpeg(_) => (UvcFormatKind::Mjpeg, "MJPG".to_string()),
+        DescriptorTypes::DescriptorUvcFormatUncompressed(f) => (UvcFormatKind::Uncompressed, fourcc(&f.guid_format)),
+        DescriptorTypes::UvcFormatFrameBased(f) => (UvcFormatKind::FrameBased, fourcc(&f.guid_format)),
+        _ => return true,
+    };
+    if !filter.allows_format(kind, &fourcc) {
+        return false;
+    }
+
+    let original = fmt.children.clone();
+    let frame_kind = fmt.parsed.clone();
+    fmt.children.retain_mut(|child| {
+        let (width, height, intervals) = match (&frame_kind, &mut child.parsed) {
+            (DescriptorTypes::DescriptorUvcFormatMjpeg(_), DescriptorTypes::DescriptorUvcFrameMjpeg(frame)) => (frame.w_width, frame.w_height, &mut frame.dw_frame_interval),
+            (DescriptorTypes::DescriptorUvcFormatUncompressed(_), DescriptorTypes::DescriptorUvcFrameUncompressed(frame)) => (frame.w_width, frame.w_height, &mut frame.dw_frame_interval),
+            (DescriptorTypes::UvcFormatFrameBased(_), DescriptorTypes::UvcFrameFrameBased(frame)) => (frame.w_width, frame.w_height, &mut frame.dw_frame_interval),
+            _ => return true, // color matching, still image and unknown descriptors stay
+        };
+        intervals.retain(|interval| filter.allows_interval(*interval));
+        filter.allows_size(width, height) && !intervals.is_empty()
+    });
+    if !fmt.uvc_frame_indices().is_empty() {
+        return true;
+    }
+    if !filter.keep_at_least_one_frame {
+        return false;
+    }
+
+    // nothing passed: fall back to the smallest frames, with whichever intervals pass
+    let pixels = |node: &TreeNode| node.frame_sz().ok().map(|sz| sz.width as u64 * sz.height as u64);
+    let min_pixels = original.iter().filter_map(pixels).min();
+    fmt.children = original.into_iter().filter(|child| !matches!(pixels(child), Some(p) if Some(p) != min_pixels)).collect();
+    fmt.children.iter_mut().for_each(|child| {
+        let intervals = match &mut child.parsed {
+            DescriptorTypes::DescriptorUvcFrameMjpeg(frame) => &mut frame.dw_frame_interval,
+            DescriptorTypes::DescriptorUvcFrameUncompressed(frame) => &mut frame.dw_frame_interval,
+            DescriptorTypes::UvcFrameFrameBased(frame) => &mut frame.dw_frame_interval,
+            _ => return,
+        };
+        if intervals.iter().any(|interval| filter.allows_interval(*interval)) {
+            intervals.retain(|interval| filter.allows_interval(*interval));
+        }
+    });
+    true
+}
+
+/// The FourCC leading a UVC format GUID, which is read as a little endian u32 (Data1).
+fn fourcc(guid: &Uuid) -> String {
+    guid.as_fields().0.to_le_bytes().iter().map(|b| *b as char).collect()
+}
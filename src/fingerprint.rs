@@ -0,0 +1,27 @@
+use std::fmt;
+
+use anyhow::Error;
+use sha2::{Digest, Sha256};
+
+use crate::TreeNode;
+
+/// SHA-256 of a tree's serialized descriptors, usable as a cache key for per-model quirks.
+/// Displays as lowercase hex.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fingerprint(pub [u8; 32]);
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl TreeNode {
+    /// Hashes the wire format of the tree, which unlike `Hash` does not depend on the Rust
+    /// version or on how the tree was parsed, so fingerprints can be persisted and shared.
+    pub fn fingerprint(&self) -> Result<Fingerprint, Error> {
+        let mut bytes = vec![];
+        self.serialize(&mut bytes)?;
+        Ok(Fingerprint(Sha256::digest(&bytes).into()))
+    }
+}
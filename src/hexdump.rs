@@ -0,0 +1,126 @@
+use std::fmt;
+
+use crate::decoder::DecoderRegistry;
+use crate::diff::debug_fields;
+use crate::{node_factory, typed_len};
+use crate::usb_proto::DescriptorTypes;
+
+const BYTES_PER_ROW: usize = 16;
+/// Field name parts the specifications spell in capitals, as in bcdUVC or bTerminalID
+const ACRONYMS: [&str; 5] = ["adc", "dfu", "id", "usb", "uvc"];
+
+/// One descriptor of a blob, as `parse_list` reads it.
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    /// Offset of the descriptor in the blob
+    pub offset: usize,
+    /// The descriptor's bytes, bLength and bDescriptorType included
+    pub bytes: Vec<u8>,
+    /// Absent when bLength is invalid, runs past the end of the blob or is too short for the
+    /// descriptor's type
+    pub parsed: Option<DescriptorTypes>,
+    /// Bytes at the end of `bytes` the parser did not read; `parse_list` keeps them in `TreeNode::extra`
+    pub extra: usize,
+}
+
+/// Splits a configuration descriptor blob into descriptors the way `parse_list` does.
+pub fn annotate(buffer: &[u8]) -> Vec<Annotation> {
+    annotate_with(buffer, &DecoderRegistry::new())
+}
+
+/// Same as `annotate`, but consults `registry` for class-specific descriptors first.
+pub fn annotate_with(buffer: &[u8], registry: &DecoderRegistry) -> Vec<Annotation> {
+    let mut annotations = vec![];
+    let mut class = 0u8;
+    let mut subclass = 0u8;
+    let mut protocol = 0u8;
+    let mut offset = 0;
+    while offset < buffer.len() {
+        let remaining = &buffer[offset..];
+        let length = remaining[0] as usize;
+        if remaining.len() < 2 || length < 2 || length > remaining.len() {
+            // nothing after a broken bLength can be located
+            annotations.push(Annotation { offset, bytes: remaining.to_vec(), parsed: None, extra: 0 });
+            break;
+        }
+        let bytes = remaining[..length].to_vec();
+        if length < typed_len(&bytes, class, subclass) {
+            annotations.push(Annotation { offset, bytes, parsed: None, extra: 0 });
+            offset += length;
+            continue;
+        }
+        let mut slice = &bytes[2..];
+        let parsed = node_factory(bytes[1], &mut slice, &mut class, &mut subclass, &mut protocol, bytes[0], registry);
+        let extra = slice.len();
+        annotations.push(Annotation { offset, bytes, parsed: Some(parsed), extra });
+        offset += length;
+    }
+    annotations
+}
+
+/// Renders annotations one descriptor after the other, see `Annotation`'s Display.
+pub fn render_annotations(annotations: &[Annotation]) -> String {
+    annotations.iter().map(|annotation| annotation.to_string()).collect()
+}
+
+impl fmt::Display for Annotation {
+    /// `0x0012  0d 24 01 00 01 d6 00 ...  UvcHeaderDescriptor bcdUVC=0x0100 wTotalLength=214 ...`,
+    /// wrapped every 16 bytes. Extra bytes follow on their own rows, marked with `!!`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (read, extra) = self.bytes.split_at(self.bytes.len() - self.extra);
+        let label = match &self.parsed {
+            Some(parsed) => describe(parsed),
+            None if self.bytes.len() < 2 || self.bytes[0] < 2 => format!("!! invalid bLength {}", self.bytes[0]),
+            None if self.bytes[0] as usize == self.bytes.len() => format!("!! bLength {} too short for descriptor type {:#04x}", self.bytes[0], self.bytes[1]),
+            None => format!("!! truncated: bLength {} but only {} bytes left", self.bytes[0], self.bytes.len()),
+        };
+        write_rows(f, self.offset, read, &label)?;
+        if !extra.is_empty() {
+            write_rows(f, self.offset + read.len(), extra, &format!("!! {} extra bytes not parsed", extra.len()))?;
+        }
+        Ok(())
+    }
+}
+
+fn write_rows(f: &mut fmt::Formatter<'_>, offset: usize, bytes: &[u8], label: &str) -> fmt::Result {
+    for (row, chunk) in bytes.chunks(BYTES_PER_ROW).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let label = if row == 0 { label } else { "" };
+        let line = format!("{:#06x}  {:<w$}  {}", offset + row * BYTES_PER_ROW, hex.join(" "), label, w = BYTES_PER_ROW * 3 - 1);
+        writeln!(f, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// `UvcHeaderDescriptor bcdUVC=0x0100 wTotalLength=214`, with field names spelled the way the
+/// USB specifications do.
+fn describe(parsed: &DescriptorTypes) -> String {
+    let debug = format!("{:?}", parsed);
+    let Some((name, fields)) = debug_fields(&debug) else { return debug };
+    let mut label = name.split('(').next().unwrap_or(name).to_string();
+    for (field, value) in fields {
+        let value = match (field.starts_with("bcd_"), value.parse::<u16>()) {
+            (true, Ok(bcd)) => format!("{:#06x}", bcd),
+            _ => value.replace(' ', ""),
+        };
+        label.push_str(&format!(" {}={}", camel_case(field), value));
+    }
+    label
+}
+
+fn camel_case(field: &str) -> String {
+    let mut parts = field.split('_');
+    let mut name = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        if ACRONYMS.contains(&part) {
+            name.push_str(&part.to_uppercase());
+            continue;
+        }
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.extend(first.to_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    name
+}
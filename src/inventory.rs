@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use anyhow::Error;
+
+use crate::TreeNode;
+use crate::linux::{Sysfs, SysfsDevice};
+use crate::usb_proto::{DescriptorInterface, DescriptorTypes, DeviceAddress, EndpointAddress, InterfaceAddress};
+
+/// A device arriving or leaving, as `DeviceInventory::handle` takes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotplugEvent {
+    /// Carries the device's sysfs name, e.g. `1-2.3`
+    Added(String),
+    /// The sysfs directory is gone by the time this arrives, so the address comes with the event
+    Removed(DeviceAddress),
+}
+
+impl HotplugEvent {
+    /// Parses a kernel uevent as read from a `NETLINK_KOBJECT_UEVENT` socket: an `action@devpath`
+    /// header followed by NUL separated `KEY=value` pairs. Anything but the addition or removal of
+    /// a USB device (interfaces, other subsystems, bind/change actions) gives `None`.
+    pub fn from_uevent(message: &[u8]) -> Option<HotplugEvent> {
+        let message = String::from_utf8_lossy(message);
+        let vars: HashMap<&str, &str> = message.split('\0').filter_map(|var| var.split_once('=')).collect();
+        if vars.get("SUBSYSTEM") != Some(&"usb") || vars.get("DEVTYPE") != Some(&"usb_device") {
+            return None;
+        }
+        match *vars.get("ACTION")? {
+            "add" => Some(HotplugEvent::Added(vars.get("DEVPATH")?.rsplit('/').next()?.to_string())),
+            "remove" => Some(HotplugEvent::Removed(DeviceAddress::new(vars.get("BUSNUM")?.parse().ok()?, vars.get("DEVNUM")?.parse().ok()?))),
+            _ => None,
+        }
+    }
+}
+
+/// The UAC capture and playback endpoints of a device that has both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Speakerphone {
+    pub mic_iface: InterfaceAddress,
+    pub mic_ep: EndpointAddress,
+    pub speaker_iface: InterfaceAddress,
+    pub speaker_ep: EndpointAddress,
+}
+
+/// The parsed descriptors of every attached device, kept current with hotplug events.
+///
+/// Queries only look at a device's active configuration, so unconfigured devices do not show up
+/// in them, and return addresses sorted by bus, device and interface or endpoint.
+#[derive(Debug, Clone)]
+pub struct DeviceInventory {
+    sysfs: Sysfs,
+    devices: HashMap<DeviceAddress, SysfsDevice>,
+}
+
+impl DeviceInventory {
+    /// An empty inventory reading added devices from `sysfs`; see `scan` for the devices already
+    /// attached.
+    pub fn new(sysfs: Sysfs) -> DeviceInventory {
+        DeviceInventory { sysfs, devices: HashMap::new() }
+    }
+
+    /// Replaces the inventory with every device sysfs currently lists.
+    pub fn scan(&mut self) -> Result<(), Error> {
+        self.devices = self.sysfs.devices()?;
+        Ok(())
+    }
+
+    /// Applies one event. Adding a device that can not be read is an error and leaves the
+    /// inventory as it was; removing an unknown device is not.
+    pub fn handle(&mut self, event: &HotplugEvent) -> Result<(), Error> {
+        match event {
+            HotplugEvent::Added(name) => {
+                let device = self.sysfs.device(name)?;
+                info!("USB device {} added at {}", name, device.address);
+                self.insert(device);
+            }
+            HotplugEvent::Removed(address) => {
+                if self.remove(*address).is_some() {
+                    info!("USB device {} removed", address);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies events until the source runs out, e.g. a channel fed from a uevent socket. Events
+    /// that fail are logged and skipped.
+    pub fn ingest(&mut self, events: impl IntoIterator<Item = HotplugEvent>) {
+        for event in events {
+            if let Err(e) = self.handle(&event) {
+                warn!("Ignoring {:?}: {}", event, e);
+            }
+        }
+    }
+
+    /// Adds or replaces a device; device numbers are reused once a device is gone.
+    pub fn insert(&mut self, device: SysfsDevice) {
+        self.devices.insert(device.address, device);
+    }
+
+    pub fn remove(&mut self, address: DeviceAddress) -> Option<SysfsDevice> {
+        self.devices.remove(&address)
+    }
+
+    pub fn get(&self, address: DeviceAddress) -> Option<&SysfsDevice> {
+        self.devices.get(&address)
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = &SysfsDevice> {
+        self.devices.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// The interfaces whose first alternate setting passes `filter`.
+    pub fn interfaces(&self, filter: impl Fn(&DescriptorInterface) -> bool) -> Vec<InterfaceAddress> {
+        let mut ifaces: Vec<InterfaceAddress> = self.active_configs()
+            .flat_map(|(address, config)| {
+                let mut found = vec![];
+                collect_ifaces(config, &filter, &mut found);
+                found.into_iter().map(move |iface| InterfaceAddress::new(address.bus, address.num, iface))
+            })
+            .collect();
+        ifaces.sort_by_key(|iface| (iface.bus, iface.num, iface.iface));
+        ifaces
+    }
+
+    pub fn uvc_streaming_interfaces(&self) -> Vec<InterfaceAddress> {
+        self.interfaces(DescriptorInterface::is_video_streaming)
+    }
+
+    pub fn uac_interfaces(&self) -> Vec<InterfaceAddress> {
+        self.interfaces(DescriptorInterface::is_audio)
+    }
+
+    /// Devices with both a UAC capture and a UAC playback endpoint.
+    pub fn speakerphones(&self) -> Vec<Speakerphone> {
+        let mut found: Vec<Speakerphone> = self.active_configs().filter_map(|(address, config)| {
+            let (bus, num) = (address.bus, address.num);
+            Some(Speakerphone {
+                mic_iface: InterfaceAddress::new(bus, num, config.find_mic_iface(None)?),
+                mic_ep: EndpointAddress::new(bus, num, config.find_mic_ep()?),
+                speaker_iface: InterfaceAddress::new(bus, num, config.find_spkr_iface(None)?),
+                speaker_ep: EndpointAddress::new(bus, num, config.find_spkr_ep()?),
+            })
+        }).collect();
+        found.sort_by_key(|phone| (phone.mic_ep.bus, phone.mic_ep.num));
+        found
+    }
+
+    fn active_configs(&self) -> impl Iterator<Item = (DeviceAddress, &TreeNode)> {
+        self.devices.values().filter_map(|device| Some((device.address, device.active_config()?)))
+    }
+}
+
+fn collect_ifaces(node: &TreeNode, filter: &impl Fn(&DescriptorInterface) -> bool, found: &mut Vec<u8>) {
+    for child in node.children.iter() {
+        match &child.parsed {
+            // alternate settings are children of the first one and not visited
+            DescriptorTypes::Interface(iface) if filter(iface) && !found.contains(&iface.b_interface_number) => {
+                found.push(iface.b_interface_number);
+            }
+            DescriptorTypes::InterfaceAssociation(_) => collect_ifaces(child, filter, found),
+            _ => {}
+        }
+    }
+}
@@ -9,28 +9,58 @@ extern crate num_derive;
 #[macro_use]
 extern crate structure;
 
-mod uac_proto;
-mod usb_proto;
-mod uvc_proto;
+pub mod builder;
+pub mod decoder;
+pub mod diff;
+pub mod dfu_proto;
+pub mod filter;
+pub mod fingerprint;
+pub mod hexdump;
+pub mod inventory;
+pub mod linux;
+pub mod lsusb;
+#[cfg(feature = "policy")]
+pub mod policy;
+pub mod repair;
+pub mod request;
+pub mod streaming;
+pub mod text;
+pub mod uac_control;
+pub mod uac_proto;
+pub mod usb_proto;
+pub mod usbmon;
+pub mod uvc_control;
+pub mod uvc_proto;
+pub mod validate;
 mod logger;
 
 use std::fmt;
 use std::io::{Read, Write};
 
 use anyhow::Error;
-use libusb1_sys::constants::{LIBUSB_CLASS_AUDIO, LIBUSB_CLASS_HID, LIBUSB_CLASS_VIDEO};
+use libusb1_sys::constants::{LIBUSB_CLASS_APPLICATION, LIBUSB_CLASS_AUDIO, LIBUSB_CLASS_HID, LIBUSB_CLASS_VIDEO};
 use num_traits::FromPrimitive;
 use structure::byteorder::{ReadBytesExt, WriteBytesExt};
-use uuid::Uuid;
 
+use crate::decoder::{DecoderKey, DecoderRegistry};
+use crate::filter::{filter_video_node, VideoFilter};
+use crate::dfu_proto::{DescriptorDfuFunctional, DfuCapabilities, DFU_FUNCTIONAL_MIN_LEN, DFU_IFACE_SUBCLASS};
 use crate::uac_proto::{DescriptorUacFormatTypeUnknown, DescriptorUacInterfaceUnknown, Uac1AcHeaderDescriptor, Uac1AsHeaderDescriptor, Uac1OutputTerminalDescriptor, UacDescriptorSubtypes, UacFeatureUnitDescriptor, UacFormatTypeI, UacFormatTypeIContinuousDescriptor, UacInputTerminalDescriptor, UacInterfaceSubclass, UacInterfaceSubtypes, UacIsoEndpointDescriptor};
 use crate::usb_proto::{DescriptorConfig, DescriptorCsDevice, DescriptorCsEndpoint, DescriptorCsInterface, DescriptorEndpoint, DescriptorInterface, DescriptorTypes, DescriptorUnknown, IfaceAltSetting, UacDescriptorEndpoint, UsbDescriptorHeader, UsbDescriptorTypes, UsbInterfaceAssocDescriptor, UsbSsEpCompDescriptor, UsbSspIsochEpCompDescriptor};
 use crate::uvc_proto::{DescriptorUvcFormatFrameBased, DescriptorUvcFormatMjpeg, DescriptorUvcFormatUncompressed, DescriptorUvcFrameFrameBased, DescriptorUvcFrameMjpeg, DescriptorUvcFrameUncompressed, DescriptorUvcInputHeader, DescriptorUvcVcInterfaceUnknown, DescriptorUvcVsInterfaceUnknown, MockVideoConfig, MockVideoFormat, UncompressedFormats, UvcExtensionUnitDescriptor, UvcHeaderDescriptor, UvcInputTerminalDescriptor, UvcInterfaceSubClass, UvcOutputTerminalDescriptor, UvcProcessingUnitDescriptor, UvcVcDescriptorSubtypes, UvcVsDescriptorSubtypes};
 
-#[derive(Debug, Clone)]
+/// Trees compare and hash by content, so identical descriptor sets from different devices can be
+/// deduplicated; see `fingerprint` for a key that is stable across builds.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TreeNode {
     pub children: Vec<TreeNode>,
     pub parsed: DescriptorTypes,
+    /// Bytes the descriptor's bLength covers beyond what `parsed` reads, e.g. vendor additions to
+    /// a standard descriptor. They are written back after it and counted in its bLength, so an
+    /// untouched descriptor serializes to its original bytes.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub extra: Vec<u8>,
 }
 
 pub struct Size2d {
@@ -46,7 +76,7 @@ impl Size2d {
 
 impl TreeNode {
     pub fn new() -> TreeNode {
-        return TreeNode { children: vec![], parsed: DescriptorTypes::Root() };
+        return TreeNode { children: vec![], parsed: DescriptorTypes::Root(), extra: vec![] };
     }
 
     pub fn shallow_clone(&self) -> TreeNode {
@@ -87,12 +117,16 @@ impl TreeNode {
         }
     }
 
-    pub fn get_node(&self, cb: fn(parsed: &DescriptorTypes) -> bool) -> Option<&TreeNode> {
+    pub fn get_node(&self, cb: impl Fn(&DescriptorTypes) -> bool) -> Option<&TreeNode> {
+        self.get_node_dyn(&cb)
+    }
+
+    fn get_node_dyn(&self, cb: &dyn Fn(&DescriptorTypes) -> bool) -> Option<&TreeNode> {
         if cb(&self.parsed) {
             return Some(self);
         }
         for child in &self.children {
-            let res = child.get_node(cb);
+            let res = child.get_node_dyn(cb);
             if res.is_some() { return res; }
         }
         return None;
@@ -128,6 +162,21 @@ impl TreeNode {
         })
     }
 
+    pub fn get_dfu_functional(&self) -> Option<&TreeNode> {
+        self.get_node(|parsed| match parsed {
+            DescriptorTypes::DfuFunctional(_) => true,
+            _ => false
+        })
+    }
+
+    /// Returns what the device supports over standard DFU, or None if it has no DFU interface.
+    pub fn dfu_capabilities(&self) -> Option<DfuCapabilities> {
+        match &self.get_dfu_functional()?.parsed {
+            DescriptorTypes::DfuFunctional(desc) => Some(desc.capabilities()),
+            _ => None
+        }
+    }
+
     pub fn get_iface_by_num(&self, iface_setting: IfaceAltSetting) -> Option<&TreeNode> {
         match &self.parsed {
             DescriptorTypes::Interface(me) => {
@@ -229,102 +278,47 @@ impl TreeNode {
         let sz = match &self.parsed {
             DescriptorTypes::DescriptorUvcFrameMjpeg(frame) => Size2d::new(frame.w_width as u32, frame.w_height as u32),
             DescriptorTypes::DescriptorUvcFrameUncompressed(frame) => Size2d::new(frame.w_width as u32, frame.w_height as u32),
+            DescriptorTypes::UvcFrameFrameBased(frame) => Size2d::new(frame.w_width as u32, frame.w_height as u32),
             _ => Err(anyhow!("Attempt to get frame size from unsupported type: {:?}", self.parsed))?
         };
         Ok(sz)
     }
 
-    pub fn fix_tree(&mut self) {
+    /// Number of bytes this node and all of its children serialize to.
+    pub fn serialized_len(&self) -> usize {
         let mut tmp_buf = vec![];
         self.serialize(&mut tmp_buf).unwrap();
-        let iface_cnt = self.find_ifaces().len();
+        tmp_buf.len()
+    }
 
-        match &mut self.parsed {
-            DescriptorTypes::Config(conf) => {
-                conf.w_total_length = tmp_buf.len() as u16;
-                conf.b_num_interfaces = iface_cnt as u8;
-            }
-            DescriptorTypes::UvcInputHeader(ref mut hdr) => {
-                hdr.w_total_length = tmp_buf.len() as u16;
-            }
-            DescriptorTypes::DescriptorUvcFormatMjpeg(ref mut fmt) => {
-                fmt.b_num_frame_descriptors = self.children.iter().filter(|child| match child.parsed {
-                    DescriptorTypes::DescriptorUvcFrameMjpeg(_) => true,
-                    _ => false
-                }).count() as u8;
-                // fix b_default_frame_index < first_frame_idx
-                for child in self.children.iter() {
-                    if let DescriptorTypes::DescriptorUvcFrameMjpeg(ref frame) = child.parsed {
-                        if fmt.b_default_frame_index < frame.b_frame_index {
-                            fmt.b_default_frame_index = frame.b_frame_index;
-                        }
-                        break;
-                    }
-                }
-                // fix b_default_frame_index > last_frame_idx
-                for child in self.children.iter().rev() {
-                    if let DescriptorTypes::DescriptorUvcFrameMjpeg(ref frame) = child.parsed {
-                        if fmt.b_default_frame_index > frame.b_frame_index {
-                            fmt.b_default_frame_index = frame.b_frame_index;
-                        }
-                        break;
-                    }
-                }
-            }
-            DescriptorTypes::DescriptorUvcFormatUncompressed(ref mut fmt) => {
-                fmt.b_num_frame_descriptors = self.children.iter().filter(|child| match child.parsed {
-                    DescriptorTypes::DescriptorUvcFrameUncompressed(_) => true,
-                    _ => false
-                }).count() as u8;
-                // fix b_default_frame_index < first_frame_idx
-                for child in self.children.iter() {
-                    if let DescriptorTypes::DescriptorUvcFrameUncompressed(ref frame) = child.parsed {
-                        if fmt.b_default_frame_index < frame.b_frame_index {
-                            fmt.b_default_frame_index = frame.b_frame_index;
-                        }
-                        break;
-                    }
-                }
-                // fix b_default_frame_index > last_frame_idx
-                for child in self.children.iter().rev() {
-                    if let DescriptorTypes::DescriptorUvcFrameUncompressed(ref frame) = child.parsed {
-                        if fmt.b_default_frame_index > frame.b_frame_index {
-                            fmt.b_default_frame_index = frame.b_frame_index;
-                        }
-                        break;
-                    }
-                }
-            }
-            DescriptorTypes::UvcFormatFrameBased(ref mut fmt) => {
-                fmt.b_num_frame_descriptors = self.children.iter().filter(|child| match child.parsed {
-                    DescriptorTypes::UvcFrameFrameBased(_) => true,
-                    _ => false
-                }).count() as u8;
-                // fix b_default_frame_index < first_frame_idx
-                for child in self.children.iter() {
-                    if let DescriptorTypes::UvcFrameFrameBased(ref frame) = child.parsed {
-                        if fmt.b_default_frame_index < frame.b_frame_index {
-                            fmt.b_default_frame_index = frame.b_frame_index;
-                        }
-                        break;
-                    }
-                }
-                // fix b_default_frame_index > last_frame_idx
-                for child in self.children.iter().rev() {
-                    if let DescriptorTypes::UvcFrameFrameBased(ref frame) = child.parsed {
-                        if fmt.b_default_frame_index > frame.b_frame_index {
-                            fmt.b_default_frame_index = frame.b_frame_index;
-                        }
-                        break;
-                    }
-                }
-            }
-            _ => (),
-        }
+    /// Length of the class-specific interface descriptors of an interface node, i.e. what a UVC
+    /// VideoControl or UAC AudioControl header's wTotalLength covers. These precede the endpoints.
+    pub fn class_specific_len(&self) -> usize {
+        self.children.iter().take_while(|child| match child.parsed {
+            DescriptorTypes::Interface(_) |
+            DescriptorTypes::Endpoint(_) |
+            DescriptorTypes::UacEndpoint(_) |
+            DescriptorTypes::HidEndpoint(_) => false,
+            _ => true,
+        }).map(|child| child.serialized_len()).sum()
+    }
 
-        self.children.iter_mut().for_each(|child| {
-            child.fix_tree();
-        });
+    /// Frame indices of the frames belonging to this UVC format node, in descriptor order.
+    pub fn uvc_frame_indices(&self) -> Vec<u8> {
+        self.children.iter().filter_map(|child| match (&self.parsed, &child.parsed) {
+            (DescriptorTypes::DescriptorUvcFormatMjpeg(_), DescriptorTypes::DescriptorUvcFrameMjpeg(frame)) => Some(frame.b_frame_index),
+            (DescriptorTypes::DescriptorUvcFormatUncompressed(_), DescriptorTypes::DescriptorUvcFrameUncompressed(frame)) => Some(frame.b_frame_index),
+            (DescriptorTypes::UvcFormatFrameBased(_), DescriptorTypes::UvcFrameFrameBased(frame)) => Some(frame.b_frame_index),
+            _ => None
+        }).collect()
+    }
+
+    /// Number of endpoint descriptors directly below an interface node (alternate settings excluded).
+    pub fn endpoint_count(&self) -> usize {
+        self.children.iter().filter(|child| match child.parsed {
+            DescriptorTypes::Endpoint(_) | DescriptorTypes::UacEndpoint(_) | DescriptorTypes::HidEndpoint(_) => true,
+            _ => false,
+        }).count()
     }
 
     pub fn find_mic_iface(&self, mut iface: Option<u8>) -> Option<u8> {
@@ -545,20 +539,10 @@ impl TreeNode {
         })
     }
 
+    /// Drops frame intervals faster than 30fps; see `VideoFilter` for other limits. Indices are
+    /// left as they are, run `fix_tree` afterwards.
     pub fn remove_high_fps(&mut self) {
-        match &mut self.parsed {
-            DescriptorTypes::UvcFrameFrameBased(frame) => {
-                frame.dw_frame_interval.retain(|interval| *interval >= 333333);
-            }
-            DescriptorTypes::DescriptorUvcFrameMjpeg(frame) => {
-                frame.dw_frame_interval.retain(|interval| *interval >= 333333);
-            }
-            DescriptorTypes::DescriptorUvcFrameUncompressed(frame) => {
-                frame.dw_frame_interval.retain(|interval| *interval >= 333333);
-            }
-            _ => {} // ignore non frame things
-        };
-        self.children.iter_mut().for_each(|child| child.remove_high_fps());
+        filter_video_node(self, &VideoFilter { max_fps: Some(30), keep_at_least_one_frame: true, ..Default::default() });
     }
 
     pub fn remove_ifaces(&mut self, ids: &Vec<u8>) {
@@ -585,92 +569,12 @@ impl TreeNode {
         self.children.iter_mut().for_each(|child| child.remove_iface_assoc(ids))
     }
 
-    pub fn remove_high_resolution(&mut self) /* -> Result<(), Error> */ {
+    /// Drops frames above 720p, keeping the smallest frames of formats that have nothing at or
+    /// below 720p; see `VideoFilter` for other limits. Indices are left as they are, run
+    /// `fix_tree` afterwards.
+    pub fn remove_high_resolution(&mut self) {
         const MAX_PIXELS: u64 = 1280 * 720;
-        // TODO: remove the entire format when all frames for a format are > 720p instead of
-        // leaving them in
-
-        // We track the min resolution of each format and check if none are <= MAX_PIXELS so that
-        // each format always has at least one entry, if these higher resolutions are selected it
-        // may cause an increased CPU usage it would be better to fully remove a format from
-        // the descriptors but this has proved to do correctly
-        let frame_min_pixel_count = self.children.iter().filter_map(|i| match &i.parsed {
-            DescriptorTypes::UvcFrameFrameBased(frame) => Some(frame.w_width as u64 * frame.w_height as u64),
-            _ => None
-        }).min();
-        let mjpeg_min_pixel_count = self.children.iter().filter_map(|i| match &i.parsed {
-            DescriptorTypes::DescriptorUvcFrameMjpeg(frame) => Some(frame.w_width as u64 * frame.w_height as u64),
-            _ => None
-        }).min();
-
-        let filter_uncompressed_by_pix_fmt = |i: &TreeNode, pix_fmt: Uuid| match &i.parsed {
-            DescriptorTypes::DescriptorUvcFrameUncompressed(frame) => {
-                match &self.parsed {
-                    DescriptorTypes::DescriptorUvcFormatUncompressed(format) => {
-                        if format.guid_format == pix_fmt {
-                            Some(frame.w_width as u64 * frame.w_height as u64)
-                        } else {
-                            None
-                        }
-                    }
-                    _ => {
-                        warn!("FrameUncompressed with a parent that is not FormatUncompressed should not be possible");
-                        None
-                    }
-                }
-            }
-            _ => None
-        };
-        let uncompressed_yuy2_min_pixel_count = self.children.iter().filter_map(|node| filter_uncompressed_by_pix_fmt(node, UncompressedFormats::YUY2)).min();
-        let uncompressed_nv12_min_pixel_count = self.children.iter().filter_map(|node| filter_uncompressed_by_pix_fmt(node, UncompressedFormats::NV12)).min();
-
-        if [frame_min_pixel_count, mjpeg_min_pixel_count, uncompressed_yuy2_min_pixel_count, uncompressed_nv12_min_pixel_count].iter().any(|min| min.map_or(false, |min| min > MAX_PIXELS))
-        {
-            warn!("A format contains only resolutions greater than 720p if this is used it may increase CPU usage");
-        }
-
-        let uncompressed_pix_fmt = match &self.parsed {
-            DescriptorTypes::DescriptorUvcFormatUncompressed(format) => Some(format.guid_format),
-            _ => None
-        };
-
-        self.children.retain(|child| match &child.parsed {
-            DescriptorTypes::UvcFrameFrameBased(frame) => {
-                let pixels = frame.w_width as u64 * frame.w_height as u64;
-                let min_pixels = frame_min_pixel_count.unwrap_or(0);
-                warn!("frame pixels {} min_pixels {}", pixels, min_pixels);
-                pixels <= MAX_PIXELS || (min_pixels > MAX_PIXELS && pixels == min_pixels || min_pixels == 0)
-            }
-            DescriptorTypes::DescriptorUvcFrameMjpeg(frame) => {
-                let pixels = frame.w_width as u64 * frame.w_height as u64;
-                let min_pixels = mjpeg_min_pixel_count.unwrap_or(0);
-                warn!("mjpeg pixels {} min_pixels {}", pixels, min_pixels);
-                pixels <= MAX_PIXELS || (min_pixels > MAX_PIXELS && pixels == min_pixels || min_pixels == 0)
-            }
-            DescriptorTypes::DescriptorUvcFrameUncompressed(frame) => {
-                let pixels = frame.w_width as u64 * frame.w_height as u64;
-                let min_pixels = match uncompressed_pix_fmt {
-                    Some(UncompressedFormats::YUY2) => {
-                        warn!("uncompressed pixels {} min_pixels {} guid_format {:?}", pixels, uncompressed_yuy2_min_pixel_count.unwrap_or(0), UncompressedFormats::YUY2);
-                        uncompressed_yuy2_min_pixel_count.unwrap_or(0)
-                    }
-                    Some(UncompressedFormats::NV12) => {
-                        warn!("uncompressed pixels {} min_pixels {} guid_format {:?}", pixels, uncompressed_nv12_min_pixel_count.unwrap_or(0), UncompressedFormats::NV12);
-                        uncompressed_nv12_min_pixel_count.unwrap_or(0)
-                    }
-                    Some(_) | None => {
-                        // to be safe we keep any unknown children
-                        warn!("Unknown uncompressed format {:?} frame {:?}", uncompressed_pix_fmt, frame);
-                        0
-                    }
-                };
-                pixels <= MAX_PIXELS || min_pixels > MAX_PIXELS || min_pixels == 0
-            }
-            _ => {
-                true
-            }
-        });
-        self.children.iter_mut().for_each(|child| child.remove_high_resolution())
+        filter_video_node(self, &VideoFilter { max_pixels: Some(MAX_PIXELS), keep_at_least_one_frame: true, ..Default::default() });
     }
 
     pub fn is_audio_control(&self) -> Result<bool, Error> {
@@ -726,6 +630,8 @@ impl TreeNode {
         }
     }
 
+    /// Removes the frame based formats of a UVC input header node together with their bmaControls
+    /// entries. Indices are left as they are, run `fix_tree` afterwards.
     pub fn remove_h264(&mut self) -> Result<(), Error> {
         // Only operate on UvcInputHeader
         if let DescriptorTypes::UvcInputHeader(ref mut hdr) = &mut self.parsed {
@@ -737,8 +643,9 @@ impl TreeNode {
                     DescriptorTypes::DescriptorUvcFormatMjpeg(_) | DescriptorTypes::DescriptorUvcFormatUncompressed(_) => {
                         let start = idx * hdr.b_control_size as usize;
                         let end = start + hdr.b_control_size as usize;
-                        let bma_ctrl = &hdr.bma_controls[start..end];
-                        bma_ctrls.extend_from_slice(&bma_ctrl);
+                        let bma_ctrl = hdr.bma_controls.get(start..end)
+                            .ok_or(anyhow!("bmaControls has no entry for format {} of {}", idx + 1, self.children.len()))?;
+                        bma_ctrls.extend_from_slice(bma_ctrl);
                     }
                     _ => Err(anyhow!("Unknown child type for UvcInputHeader: {:?}", node.parsed))?
                 }
@@ -753,10 +660,24 @@ impl TreeNode {
         }
     }
 
-    pub fn serialize(&self, mut buffer: &mut Vec<u8>) -> Result<(), Error> {
+    pub fn serialize(&self, buffer: &mut Vec<u8>) -> Result<(), Error> {
+        let start = buffer.len();
+        self.serialize_parsed(buffer)?;
+        if !self.extra.is_empty() && buffer.len() > start {
+            buffer.extend_from_slice(&self.extra);
+            buffer[start] = (buffer.len() - start) as u8;
+        }
+        for child in self.children.iter() {
+            child.serialize(buffer)?;
+        }
+        Ok(())
+    }
+
+    fn serialize_parsed(&self, mut buffer: &mut Vec<u8>) -> Result<(), Error> {
         match &self.parsed {
             DescriptorTypes::Root() => (),
             DescriptorTypes::CsDevice(desc) => desc.serialize(&mut buffer),
+            DescriptorTypes::DfuFunctional(desc) => desc.serialize(&mut buffer),
             DescriptorTypes::Config(desc) => desc.serialize(&mut buffer),
             DescriptorTypes::Interface(desc) => desc.serialize(&mut buffer),
             DescriptorTypes::CsInterface(desc) => desc.serialize(&mut buffer),
@@ -790,6 +711,7 @@ impl TreeNode {
             DescriptorTypes::UvcVcOutputTerminal(desc) => desc.serialize(&mut buffer),
             DescriptorTypes::SsEpComp(desc) => desc.serialize(&mut buffer),
             DescriptorTypes::SspIsochEpComp(desc) => desc.serialize(&mut buffer),
+            DescriptorTypes::Custom(desc) => desc.serialize(&mut buffer)?,
             DescriptorTypes::Unknown(desc) => {
                 buffer.write_u8(desc.bytes.len() as u8 + 2u8).unwrap();
                 buffer.write_u8(desc.desc_type).unwrap();
@@ -797,14 +719,19 @@ impl TreeNode {
             }
             _ => panic!("Cannot serialize unknown type: {:?}", self.parsed)
         }
-        for child in self.children.iter() {
-            child.serialize(&mut buffer).unwrap();
-        }
         Ok(())
     }
 
     pub fn deserialize(slice: &mut &[u8]) -> Result<TreeNode, Error> {
-        let root = parse_list(slice);
+        TreeNode::deserialize_with(slice, &DecoderRegistry::new())
+    }
+
+    /// Same as `deserialize`, but consults `registry` for class-specific descriptors first.
+    pub fn deserialize_with(slice: &mut &[u8], registry: &DecoderRegistry) -> Result<TreeNode, Error> {
+        let root = parse_list_with(slice, registry);
+        if !slice.is_empty() {
+            return Err(anyhow!("Could not locate a descriptor in the last {} bytes, bLength is {}", slice.len(), slice[0]));
+        }
         let root = pivot_cfg_desc(&root);
 
         // https://www.beyondlogic.org/usbnutshell/usb5.shtml#InterfaceDescriptors
@@ -838,7 +765,11 @@ impl RecursiveDisplay for TreeNode {
         // serialize_tree(&mut tmp_buf, &self).unwrap();
         // write!(f, "{} bytes {}{:?}\n", tmp_buf.len(), (0..depth).map(|_| "\t").collect::<String>(), self.parsed)?;
 
-        write!(f, "{}{:?}\n", (0..depth).map(|_| "\t").collect::<String>(), self.parsed)?;
+        write!(f, "{}{:?}", (0..depth).map(|_| "\t").collect::<String>(), self.parsed)?;
+        if !self.extra.is_empty() {
+            write!(f, " extra: {:?}", self.extra)?;
+        }
+        writeln!(f)?;
         for child in self.children.iter() {
             child.recursive_fmt(f, depth + 1)?;
         }
@@ -958,9 +889,88 @@ fn uac_iface_factory(buffer: &mut &[u8], subclass: &mut u8) -> DescriptorTypes {
     }
 }
 
-fn node_factory(desc_type: u8, mut buffer: &mut &[u8], class: &mut u8, subclass: &mut u8, len: u8) -> DescriptorTypes {
+fn custom_factory(desc_type: u8, buffer: &mut &[u8], class: u8, subclass: u8, protocol: u8, len: u8, registry: &DecoderRegistry) -> Option<DescriptorTypes> {
+    let key = DecoderKey::new(class, subclass, protocol, desc_type, buffer.first().copied());
+    let decoder = registry.get(&key)?;
+    let mut attempt = *buffer;
+    match decoder.decode(&mut attempt, len) {
+        Ok(desc) => {
+            *buffer = attempt;
+            Some(DescriptorTypes::Custom(desc))
+        }
+        Err(e) => {
+            warn!("Custom decoder failed for {:?}, using built-in parser: {}", key, e);
+            None
+        }
+    }
+}
+
+/// Bytes `node_factory` reads from `bytes`, a whole descriptor, given the class and subclass of
+/// the interface it follows: the fixed part of its typed layout plus the arrays its count fields
+/// announce. Descriptors shorter than this would make it panic; raw ones only need their header.
+pub(crate) fn typed_len(bytes: &[u8], class: u8, subclass: u8) -> usize {
+    let at = |i: usize| bytes.get(i).copied().unwrap_or(0) as usize;
+    let subtype = bytes.get(2).copied().unwrap_or(0);
+    match FromPrimitive::from_u8(at(1) as u8) {
+        Some(UsbDescriptorTypes::Config) | Some(UsbDescriptorTypes::Interface) => 9,
+        Some(UsbDescriptorTypes::InterfaceAssociation) | Some(UsbDescriptorTypes::SuperSpeedPlusIsochEpComp) => 8,
+        Some(UsbDescriptorTypes::Endpoint) if class == LIBUSB_CLASS_AUDIO && at(0) == 9 => 9,
+        Some(UsbDescriptorTypes::Endpoint) => 7,
+        Some(UsbDescriptorTypes::SuperSpeedEpComp) => 6,
+        Some(UsbDescriptorTypes::CsInterface) if class == LIBUSB_CLASS_VIDEO => match FromPrimitive::from_u8(subclass) {
+            Some(UvcInterfaceSubClass::VideoStreaming) => match FromPrimitive::from_u8(subtype) {
+                Some(UvcVsDescriptorSubtypes::InputHeader) => 13 + at(3) * at(12),
+                Some(UvcVsDescriptorSubtypes::FormatUncompressed) => 27,
+                Some(UvcVsDescriptorSubtypes::FormatMjpeg) => 11,
+                Some(UvcVsDescriptorSubtypes::FormatFrameBased) => 28,
+                Some(UvcVsDescriptorSubtypes::FrameUncompressed) | Some(UvcVsDescriptorSubtypes::FrameMjpeg) => 26 + 4 * at(25),
+                Some(UvcVsDescriptorSubtypes::FrameFrameBased) => 26 + 4 * at(21),
+                _ => 3,
+            },
+            Some(UvcInterfaceSubClass::VideoControl) => match FromPrimitive::from_u8(subtype) {
+                Some(UvcVcDescriptorSubtypes::UvcVcHeader) => 12 + at(11),
+                Some(UvcVcDescriptorSubtypes::UvcVcInputTerminal) => 8,
+                Some(UvcVcDescriptorSubtypes::UvcVcOutputTerminal) => 9,
+                Some(UvcVcDescriptorSubtypes::UvcVcProcessingUnit) => 11,
+                Some(UvcVcDescriptorSubtypes::UvcVcExtensionUnit) => 24 + at(21) + at(22 + at(21)),
+                _ => 3,
+            },
+            _ => 2,
+        },
+        Some(UsbDescriptorTypes::CsInterface) if class == LIBUSB_CLASS_AUDIO => match FromPrimitive::from_u8(subclass) {
+            Some(UacInterfaceSubclass::AudioControl) => match FromPrimitive::from_u8(subtype) {
+                Some(UacDescriptorSubtypes::Header) => 8 + at(7),
+                Some(UacDescriptorSubtypes::InputTerminal) => 12,
+                Some(UacDescriptorSubtypes::OutputTerminal) => 9,
+                Some(UacDescriptorSubtypes::FeatureUnit) => 6,
+                _ => 3,
+            },
+            Some(UacInterfaceSubclass::AudioStreaming) => match FromPrimitive::from_u8(subtype) {
+                Some(UacInterfaceSubtypes::General) => 7,
+                Some(UacInterfaceSubtypes::FormatType) => match FromPrimitive::from_u8(at(3) as u8) {
+                    Some(UacFormatTypeI::Pcm) => 8 + 3 * at(7),
+                    _ => 4,
+                },
+                _ => 3,
+            },
+            _ => 2,
+        },
+        Some(UsbDescriptorTypes::CsEndpoint) if class == LIBUSB_CLASS_AUDIO && subclass == UacInterfaceSubclass::AudioStreaming as u8 => 7,
+        _ => 2,
+    }
+}
+
+fn node_factory(desc_type: u8, mut buffer: &mut &[u8], class: &mut u8, subclass: &mut u8, protocol: &mut u8, len: u8, registry: &DecoderRegistry) -> DescriptorTypes {
+    if desc_type != UsbDescriptorTypes::Interface as u8 && !registry.is_empty() {
+        if let Some(node) = custom_factory(desc_type, buffer, *class, *subclass, *protocol, len, registry) {
+            return node;
+        }
+    }
     match FromPrimitive::from_u8(desc_type) {
         Some(UsbDescriptorTypes::CsDevice) => {
+            if *class == LIBUSB_CLASS_APPLICATION && *subclass == DFU_IFACE_SUBCLASS && len as usize >= DFU_FUNCTIONAL_MIN_LEN {
+                return DescriptorTypes::DfuFunctional(DescriptorDfuFunctional::deserialize(&mut buffer));
+            }
             warn!("Unknown class specific device: class={:#04x} subclass={:#04x}", class, subclass);
             let mut desc = vec![0u8; buffer.len()];
             buffer.read_exact(&mut desc).unwrap();
@@ -975,6 +985,7 @@ fn node_factory(desc_type: u8, mut buffer: &mut &[u8], class: &mut u8, subclass:
             let iface = DescriptorInterface::deserialize(&mut buffer);
             *class = iface.b_interface_class;
             *subclass = iface.b_interface_sub_class;
+            *protocol = iface.b_interface_protocol;
             return DescriptorTypes::Interface(iface);
         }
         Some(UsbDescriptorTypes::CsInterface) => {
@@ -1019,29 +1030,49 @@ fn node_factory(desc_type: u8, mut buffer: &mut &[u8], class: &mut u8, subclass:
     }
 }
 
-pub fn parse_list(mut buffer: &mut &[u8]) -> TreeNode {
+pub fn parse_list(buffer: &mut &[u8]) -> TreeNode {
+    parse_list_with(buffer, &DecoderRegistry::new())
+}
+
+pub fn parse_list_with(mut buffer: &mut &[u8], registry: &DecoderRegistry) -> TreeNode {
     let mut root = TreeNode {
         children: vec![],
         parsed: DescriptorTypes::Root(),
+        extra: vec![],
     };
     let mut class = 0u8;
     let mut subclass = 0u8;
+    let mut protocol = 0u8;
     while buffer.len() > 0 {
-        let hdr = UsbDescriptorHeader::deserialize(&mut buffer);
-        let mut desc = vec![0u8; hdr.b_length as usize - 2];
-        if buffer.len() < desc.len() {
-            warn!("Could not read entire descriptor!");
+        // the rest of the buffer is left unread when no descriptor can be located in it
+        let length = buffer[0] as usize;
+        if buffer.len() < 2 || length < 2 || length > buffer.len() {
+            warn!("Could not read entire descriptor! bLength {} with {} bytes left", length, buffer.len());
             return root;
         }
+        let too_short = length < typed_len(&buffer[..length], class, subclass);
+        let hdr = UsbDescriptorHeader::deserialize(&mut buffer);
+        let mut desc = vec![0u8; length - 2];
         buffer.read_exact(&mut desc).unwrap();
+        if too_short {
+            // kept as raw bytes so the tree still serializes to what was read
+            warn!("bLength {} too short for descriptor type {:#04x}, keeping it unparsed", length, hdr.b_descriptor_type);
+            root.children.push(TreeNode {
+                children: vec![],
+                parsed: DescriptorTypes::Unknown(DescriptorUnknown { desc_type: hdr.b_descriptor_type, bytes: desc }),
+                extra: vec![],
+            });
+            continue;
+        }
         let mut slice = &desc[..];
-        let node = node_factory(hdr.b_descriptor_type, &mut slice, &mut class, &mut subclass, hdr.b_length);
+        let node = node_factory(hdr.b_descriptor_type, &mut slice, &mut class, &mut subclass, &mut protocol, hdr.b_length, registry);
         if slice.len() > 0 {
-            warn!("{} extra bytes after parsing node of type {}", slice.len(), hdr.b_descriptor_type);
+            info!("Keeping {} extra bytes after parsing node of type {}", slice.len(), hdr.b_descriptor_type);
         }
         root.children.push(TreeNode {
             children: vec![],
             parsed: node,
+            extra: slice.to_vec(),
         });
     }
     info!("Read a root node with {} children", root.children.len());
@@ -1049,7 +1080,7 @@ pub fn parse_list(mut buffer: &mut &[u8]) -> TreeNode {
 }
 
 pub fn pivot_cfg_desc(root: &TreeNode) -> TreeNode {
-    let mut new_root = TreeNode { children: vec![], parsed: DescriptorTypes::Root() };
+    let mut new_root = TreeNode { children: vec![], parsed: DescriptorTypes::Root(), extra: vec![] };
     let mut cur_node: Option<usize> = None;
     let mut bytes_remaining = 0usize;
     for child in &root.children {
@@ -1082,7 +1113,10 @@ pub fn pivot_uvc_input_hdr(node: &TreeNode, mut new_node: Option<TreeNode>, idx:
         // if we hit a UVC input header, recurse
         if let DescriptorTypes::UvcInputHeader(hdr) = &child.parsed {
             *idx += 1;
-            let sz = hdr.w_total_length as usize - hdr.size();
+            let sz = (hdr.w_total_length as usize).checked_sub(hdr.size() + child.extra.len()).unwrap_or_else(|| {
+                warn!("UVC input header wTotalLength {} is shorter than the header itself, keeping its formats as siblings", hdr.w_total_length);
+                0
+            });
             new_child = pivot_uvc_input_hdr(&node, Some(new_child), idx, sz);
             *idx -= 1;
         }
@@ -1092,7 +1126,7 @@ pub fn pivot_uvc_input_hdr(node: &TreeNode, mut new_node: Option<TreeNode>, idx:
 
         let mut tmp_buf = vec![];
         new_child.serialize(&mut tmp_buf).unwrap();
-        bytes_remaining -= tmp_buf.len();
+        bytes_remaining = bytes_remaining.saturating_sub(tmp_buf.len());
 
         new_node.children.push(new_child);
         *idx += 1;
@@ -1238,11 +1272,32 @@ pub fn pivot_alt_settings(node: &TreeNode, mut new_node: Option<TreeNode>, idx:
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
     use std::fs;
     use std::fs::File;
     use std::io::Read;
 
+    use sha2::{Digest, Sha256};
+
+    use crate::builder::ConfigBuilder;
+    use crate::decoder::CustomDescriptor;
     use crate::logger::setup_logger;
+    use crate::linux::{parse_descriptors, Sysfs, UsbSpeed};
+    use crate::lsusb::parse_lsusb;
+    use crate::diff::{render_changes, ChangeKind, FieldChange};
+    use crate::filter::AudioFilter;
+    use crate::hexdump::{annotate, render_annotations};
+    use crate::inventory::{DeviceInventory, HotplugEvent, Speakerphone};
+    #[cfg(feature = "policy")]
+    use crate::policy::{apply_policy, PolicySet};
+    use crate::streaming::StreamingTranslator;
+    use crate::uac_control::{FeatureUnitControl, FeatureUnitValue, SamplingFreqControl, Uac2Range};
+    use crate::uvc_control::{UvcControl, UvcControlInfo, UvcControlSelector, UvcControlSpec, UVC_CONTROLS};
+    use crate::uac_proto::{FeatureUnitControlSelectors, Uac2RequestCodes, UacRequestCodes, UacVolume};
+    use crate::usb_proto::{DescriptorDevice, DeviceAddress, EndpointAddress, InterfaceAddress, Recip, SetupPacket, StandardRequest, XferDir, XferType};
+    use crate::usbmon::{captured_descriptors, control_transfers, read_capture, ControlTransfer};
+    use crate::uvc_proto::{UvcCtControlSelectors, UvcPuControlSelectors, UvcRequestCodes, UvcStreamingControl, UvcStreamingControlVersion};
+    use crate::validate::Severity;
 
     use super::*;
 
@@ -1484,6 +1539,32 @@ mod tests {
         assert_txt(&txt_expected, &actual);
     }
 
+    #[test]
+    fn test_panacast_p50_high_resolution_index_map() {
+        // setup
+        setup();
+        let bin_original = read_bin_file("0x0b0e_0x3013_Jabra_PanaCast_50_config_desc_0.bin");
+        let mut slice = &bin_original[..];
+        let mut actual = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+
+        // exercise
+        actual.remove_high_resolution();
+        let map = actual.renumber_uvc_indices();
+        actual.fix_tree();
+
+        // assert: the 4800x1200 YUY2 frame 1 is gone, 3840x1080 moves from frame 2 to 1
+        assert!(!map.is_identity());
+        assert_eq!(map.to_new(2, 3, 1), None);
+        assert_eq!(map.to_new(2, 3, 2), Some((3, 1)));
+        assert_eq!(map.to_old(2, 3, 1), Some((3, 2)));
+        assert_eq!(map.to_new(2, 2, 3), Some((2, 3)));
+        match &actual.get_format_by_idx(3).expect("format 3 should exist").parsed {
+            DescriptorTypes::DescriptorUvcFormatUncompressed(fmt) => assert_eq!(fmt.b_default_frame_index, 1),
+            _ => panic!("format 3 should be the YUY2 format"),
+        }
+        assert!(actual.validate().is_empty());
+    }
+
     #[test]
     fn test_bose_vb1_camera_remove_h264() {
         // setup
@@ -1509,6 +1590,218 @@ mod tests {
         let _ = TreeNode::deserialize(&mut slice);
     }
 
+    #[test]
+    fn test_remove_h264_short_bma_controls() {
+        // setup: a VB1 input header with bmaControls for its first format only
+        setup();
+        let bin_input = read_bin_file("0xa213_0x05a7_114_Bose_Videobar_VB1_config_desc_0.bin");
+        let mut root = TreeNode::deserialize(&mut &bin_input[..]).expect("Could not deserialize");
+        let hdr = root.get_uvc_input_hdr().expect("Input header not found!");
+        if let DescriptorTypes::UvcInputHeader(ref mut desc) = hdr.parsed {
+            desc.bma_controls.truncate(desc.b_control_size as usize);
+        }
+        let formats = hdr.children.len();
+
+        // exercise & assert: an error instead of a panic, with the formats left alone
+        assert!(hdr.remove_h264().is_err());
+        assert_eq!(hdr.children.len(), formats);
+    }
+
+    #[test]
+    #[cfg(feature = "policy")]
+    fn test_policy_fixtures() {
+        // setup
+        setup();
+        let policies = PolicySet::load("test/fixtures/policies.json").expect("Could not load policies");
+        let cases = [
+            (0x046d, 0x0866, "046d_0866_meetup_config_desc_0.bin", "046d_0866_meetup_config_desc_0_no_60hz.txt"),
+            (0x046d, 0x0867, "0x0867_0x046d_MeetUp_Speakerphone_config_desc_0.bin", "0x0867_0x046d_MeetUp_Speakerphone_config_desc_0_no_uac.txt"),
+            (0x25c1, 0x0018, "0x0018_0x25c1_HuddleSHOT_config_desc_0.bin", "0x0018_0x25c1_HuddleSHOT_config_desc_0_no_uac.txt"),
+        ];
+
+        for (vid, pid, bin_file, txt_file) in cases {
+            // exercise
+            let bin_input = read_bin_file(bin_file);
+            let mut slice = &bin_input[..];
+            let mut actual = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+            let policy = policies.find(vid, pid, 0x0100).expect("No policy found");
+            let report = apply_policy(&mut actual, policy).expect("Could not apply policy");
+            info!("{}", report);
+
+            // assert
+            assert_txt(&read_txt_file(txt_file), &actual);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "policy")]
+    fn test_bose_vb1_policy() {
+        // setup
+        setup();
+        let policies = PolicySet::load("test/fixtures/policies.json").expect("Could not load policies");
+        let txt_expected = read_txt_file("0xa213_0x05a7_114_Bose_Videobar_VB1_config_desc_0_no_h264.txt")
+            .replace("b_max_power: 1 ", "b_max_power: 250 ");
+        let bin_input = read_bin_file("0xa213_0x05a7_114_Bose_Videobar_VB1_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let mut actual = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+
+        // exercise
+        let policy = policies.find(0x05a7, 0xa213, 0x0114).expect("No policy found");
+        let report = apply_policy(&mut actual, policy).expect("Could not apply policy");
+
+        // assert
+        assert_eq!(policy.name, "Bose VB1 1.14: no H.264");
+        assert_eq!(policies.find(0x05a7, 0xa213, 0x0116).unwrap().name, "Bose VB1");
+        assert!(report.steps[1].repairs.iter().any(|repair| repair.field == "b_max_power" && repair.new == "250"));
+        assert_txt(&txt_expected, &actual);
+    }
+
+    #[test]
+    #[cfg(feature = "policy")]
+    fn test_policy_errors_leave_tree_untouched() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0xa213_0x05a7_114_Bose_Videobar_VB1_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let mut actual = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let expected = format!("{}", actual);
+        let policies = PolicySet::from_json(r#"{ "policies": [{ "vendor_id": 1447, "product_id": 41491, "steps": [
+            { "op": "remove_h264" },
+            { "op": "set_field", "target": { "descriptor": "interface", "interface": 9, "alt": 0 }, "field": "i_interface", "value": 1 }
+        ] }] }"#).expect("Could not parse policies");
+
+        // exercise
+        let result = apply_policy(&mut actual, &policies.policies[0]);
+
+        // assert
+        assert!(result.is_err());
+        assert_eq!(format!("{}", actual), expected);
+        assert!(PolicySet::from_json(r#"{ "policies": [{ "vendor_id": "0xzz", "product_id": 1, "steps": [] }] }"#).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "policy")]
+    fn test_policy_ids() {
+        // exercise
+        let policies = PolicySet::from_json(r#"{ "policies": [
+            { "vendor_id": "0x046D", "product_id": "0X0866", "bcd_device": "0x0012", "steps": [] },
+            { "vendor_id": "1133", "product_id": 2150, "bcd_device": "18", "steps": [] }
+        ] }"#).expect("Could not parse policies");
+
+        // assert: strings without a 0x prefix are decimal
+        let ids: Vec<(u16, u16, Option<u16>)> = policies.policies.iter().map(|policy| (policy.vendor_id, policy.product_id, policy.bcd_device)).collect();
+        assert_eq!(ids, vec![(0x046d, 0x0866, Some(0x12)), (0x046d, 0x0866, Some(18))]);
+        assert!(PolicySet::from_json(r#"{ "policies": [{ "vendor_id": "046d", "product_id": 1, "steps": [] }] }"#).is_err());
+    }
+
+    #[test]
+    fn test_vadio_diff_remove_uac() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0x0018_0x25c1_HuddleSHOT_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let original = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let mut rewritten = original.clone();
+        let ids = rewritten.find_uac_ifaces();
+        rewritten.remove_iface_assoc(&ids);
+        rewritten.remove_ifaces(&ids);
+        rewritten.fix_tree();
+
+        // exercise
+        let changes = original.diff(&rewritten);
+        info!("changes:\n{}", render_changes(&changes));
+
+        // assert
+        assert!(original.diff(&original).is_empty());
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].path, "Root / Config 1");
+        assert!(matches!(&changes[0].kind, ChangeKind::Modified { fields } if fields.contains(&FieldChange {
+            field: "b_num_interfaces".to_string(), old: "6".to_string(), new: "3".to_string() })));
+        assert_eq!(changes[1].path, "Root / Config 1 / IAD 2");
+        assert!(matches!(changes[1].kind, ChangeKind::Removed { .. }));
+    }
+
+    #[test]
+    fn test_meetup_diff_remove_60hz() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("046d_0866_meetup_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let original = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let mut rewritten = original.clone();
+        rewritten.remove_high_fps();
+        rewritten.fix_tree();
+
+        // exercise
+        let rendered = render_changes(&original.diff(&rewritten));
+
+        // assert
+        assert_eq!(rendered, "~ Root / Config 1\n\
+            \tw_total_length: 2380 -> 2376\n\
+            ~ Root / Config 1 / IAD 0 / Interface 1.0 / UvcInputHeader\n\
+            \tw_total_length: 1901 -> 1897\n\
+            ~ Root / Config 1 / IAD 0 / Interface 1.0 / UvcInputHeader / Format 2 / Frame 15\n\
+            \tdw_frame_interval: [166666, 333333, 416666, 500000, 666666, 1000000, 1333333, 2000000] -> [333333, 416666, 500000, 666666, 1000000, 1333333, 2000000]\n");
+    }
+
+    #[test]
+    fn test_c925e_diff_extra_bytes() {
+        // setup: vendor bytes after the first MJPEG frame and a different width
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let original = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let mut rewritten = original.clone();
+        let hdr = rewritten.get_uvc_input_hdr().unwrap();
+        let frame = hdr.children[1].children.iter_mut()
+            .find(|child| matches!(&child.parsed, DescriptorTypes::DescriptorUvcFrameMjpeg(frame) if frame.b_frame_index == 1))
+            .unwrap();
+        frame.extra = vec![0xde, 0xad];
+        if let DescriptorTypes::DescriptorUvcFrameMjpeg(ref mut frame) = frame.parsed {
+            frame.w_width = 1024;
+        }
+
+        // exercise
+        let changes = original.diff(&rewritten);
+
+        // assert
+        assert_eq!(changes.len(), 1);
+        assert_eq!(render_changes(&changes), "~ Root / Config 1 / IAD 0 / Interface 1.0 / UvcInputHeader / Format 2 / Frame 1\n\
+            \tw_width: 640 -> 1024\n\
+            \textra: [] -> [de, ad]\n");
+    }
+
+    #[test]
+    fn test_tree_equality_and_fingerprint() {
+        // setup
+        setup();
+        let files = [
+            "0xa213_0x05a7_114_Bose_Videobar_VB1_config_desc_0.bin",
+            "0xa213_0x05a7_116_Bose_Videobar_VB1_config_desc_0.bin",
+            "0xa213_0x05a7_114_Bose_Videobar_VB1_config_desc_0.bin",
+        ];
+        let bins: Vec<Vec<u8>> = files.iter().map(|file| read_bin_file(file)).collect();
+        let trees: Vec<TreeNode> = bins.iter().map(|bin| {
+            let mut slice = &bin[..];
+            TreeNode::deserialize(&mut slice).expect("Could not deserialize")
+        }).collect();
+        let mut modified = trees[0].clone();
+        modified.get_uvc_input_hdr().unwrap().remove_h264().unwrap();
+
+        // exercise
+        let unique: HashSet<&TreeNode> = trees.iter().collect();
+        let fingerprint = trees[0].fingerprint().unwrap();
+
+        // assert
+        assert_eq!(unique.len(), 2);
+        assert_eq!(trees[0], trees[2]);
+        assert_ne!(trees[0], trees[1]);
+        assert_ne!(trees[0], modified);
+        assert_eq!(fingerprint.0[..], Sha256::digest(&bins[0])[..]);
+        assert_eq!(format!("{}", fingerprint).len(), 64);
+        assert_ne!(modified.fingerprint().unwrap(), fingerprint);
+    }
+
     #[test]
     fn test_c920_remove_all() {
         // setup
@@ -1565,10 +1858,1386 @@ mod tests {
     }
 
     #[test]
-    fn test_huddly_iq() {
-        let txt_filename = "0x2bd9_0x0021_7_Huddly_IQ_config_desc_0.txt";
-        let bin_filename = "0x2bd9_0x0021_7_Huddly_IQ_config_desc_0.bin";
+    fn test_poly_studio_x30_dfu_capabilities() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0x9275_0x095d_7_Poly_Studio_X30_config_desc_0.bin");
 
-        descriptor_test(txt_filename, &bin_filename);
+        // exercise
+        let mut slice = &bin_input[..];
+        let root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let caps = root.dfu_capabilities().expect("No DFU functional descriptor");
+
+        // assert
+        assert!(caps.can_download);
+        assert!(!caps.can_upload);
+        assert!(caps.manifestation_tolerant);
+        assert!(caps.will_detach);
+        assert_eq!(caps.detach_timeout_ms, 255);
+        assert_eq!(caps.transfer_size, 4096);
+        assert_eq!(caps.bcd_dfu_version, Some(0x0110));
+
+        // DFU 1.0 functional descriptors have no bcdDFUVersion
+        let bin_input = read_bin_file("dfu_1_0_runtime_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let caps = root.dfu_capabilities().expect("No DFU functional descriptor");
+        assert!(caps.can_download && caps.can_upload && caps.will_detach && !caps.manifestation_tolerant);
+        assert_eq!((caps.detach_timeout_ms, caps.transfer_size, caps.bcd_dfu_version), (255, 1024, None));
+        let mut buffer = vec![];
+        root.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer, bin_input);
+
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        assert!(root.dfu_capabilities().is_none());
+    }
+
+    #[test]
+    fn test_dfu_functional_lengths() {
+        setup();
+        let config = [9, 2, 0, 0, 1, 1, 0, 0x80, 50];
+        let iface = [9, 4, 0, 0, 0, 0xfe, DFU_IFACE_SUBCLASS, 1, 0];
+        let functional = [9, 0x21, 0x0d, 0xff, 0x00, 0x00, 0x10, 0x10, 0x01];
+        for (len, version, extra) in [(7, None, vec![]), (8, None, vec![0x10]), (9, Some(0x0110), vec![])] {
+            // setup: a DFU runtime interface with the functional descriptor cut to len bytes
+            let mut blob = [&config[..], &iface[..], &functional[..len]].concat();
+            blob[2] = blob.len() as u8;
+            blob[18] = len as u8;
+
+            // exercise
+            let root = TreeNode::deserialize(&mut &blob[..]).expect("Could not deserialize");
+            let mut buffer = vec![];
+            root.serialize(&mut buffer).unwrap();
+
+            // assert
+            let node = root.get_dfu_functional().expect("No DFU functional descriptor");
+            match &node.parsed {
+                DescriptorTypes::DfuFunctional(desc) => {
+                    assert_eq!(desc.bcd_dfu_version, version, "{} bytes", len);
+                    assert_eq!(desc.size() + node.extra.len(), len);
+                    assert_eq!((desc.w_detach_time_out, desc.w_transfer_size), (255, 4096));
+                }
+                _ => panic!("expected a DFU functional descriptor"),
+            }
+            assert_eq!(node.extra, extra, "{} bytes", len);
+            assert_eq!(buffer, blob, "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn test_dfu_capabilities_attributes() {
+        let desc = DescriptorDfuFunctional { bm_attributes: 0x0a, w_detach_time_out: 1000, w_transfer_size: 64, bcd_dfu_version: None };
+        let caps = desc.capabilities();
+        assert_eq!((caps.can_download, caps.can_upload, caps.manifestation_tolerant, caps.will_detach), (false, true, false, true));
+        assert_eq!((caps.detach_timeout_ms, caps.transfer_size, caps.bcd_dfu_version), (1000, 64, None));
+        let mut buffer = vec![];
+        desc.serialize(&mut buffer);
+        assert_eq!(buffer, vec![7, 0x21, 0x0a, 0xe8, 0x03, 0x40, 0x00]);
+        assert_eq!(DescriptorDfuFunctional::deserialize(&mut &buffer[2..]), desc);
+    }
+
+    #[derive(Debug, Clone)]
+    struct UvcInterruptEndpoint {
+        w_max_transfer_size: u16,
+    }
+
+    impl CustomDescriptor for UvcInterruptEndpoint {
+        fn serialize(&self, mut buffer: &mut dyn Write) -> Result<(), Error> {
+            let format = structure!("<BBBH");
+            format.pack_into(&mut buffer, format.size() as u8, UsbDescriptorTypes::CsEndpoint as u8, 3, self.w_max_transfer_size)?;
+            Ok(())
+        }
+        fn clone_box(&self) -> Box<dyn CustomDescriptor> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn test_c925e_custom_decoder() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut registry = DecoderRegistry::new();
+        let key = DecoderKey::new(LIBUSB_CLASS_VIDEO, UvcInterfaceSubClass::VideoControl as u8, 0, UsbDescriptorTypes::CsEndpoint as u8, Some(3));
+        registry.register(key, Box::new(|buffer: &mut &[u8], _len: u8| -> Result<Box<dyn CustomDescriptor>, Error> {
+            let (_subtype, w_max_transfer_size) = structure!("<BH").unpack_from(buffer)?;
+            Ok(Box::new(UvcInterruptEndpoint { w_max_transfer_size }))
+        }));
+
+        // exercise
+        let mut slice = &bin_input[..];
+        let mut root = TreeNode::deserialize_with(&mut slice, &registry).expect("Could not deserialize");
+        root.fix_tree();
+
+        // assert
+        let txt = format!("{}", root);
+        assert!(txt.contains("Custom(UvcInterruptEndpoint { w_max_transfer_size: 64 })"));
+        assert!(!txt.contains("CsEndpoint("));
+        assert_bin(&bin_input, &root);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_json_round_trip() {
+        setup();
+        let mut filenames: Vec<String> = fs::read_dir("test/fixtures").unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with("config_desc_0.bin"))
+            .collect();
+        filenames.sort();
+        for filename in filenames {
+            // setup
+            let bin_input = read_bin_file(&filename);
+            let mut slice = &bin_input[..];
+            let root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+            let mut bin_expected = vec![];
+            root.serialize(&mut bin_expected).unwrap();
+
+            // exercise
+            let json = serde_json::to_string(&root).expect("Could not serialize to json");
+            let restored: TreeNode = serde_json::from_str(&json).expect("Could not deserialize from json");
+
+            // assert
+            assert_eq!(restored, root, "{}", filename);
+            assert_bin(&bin_expected, &restored);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_c925e_serde_json_custom_descriptor() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut registry = DecoderRegistry::new();
+        let key = DecoderKey::new(LIBUSB_CLASS_VIDEO, UvcInterfaceSubClass::VideoControl as u8, 0, UsbDescriptorTypes::CsEndpoint as u8, Some(3));
+        registry.register(key, Box::new(|buffer: &mut &[u8], _len: u8| -> Result<Box<dyn CustomDescriptor>, Error> {
+            let (_subtype, w_max_transfer_size) = structure!("<BH").unpack_from(buffer)?;
+            Ok(Box::new(UvcInterruptEndpoint { w_max_transfer_size }))
+        }));
+        let mut slice = &bin_input[..];
+        let root = TreeNode::deserialize_with(&mut slice, &registry).expect("Could not deserialize");
+
+        // exercise
+        let json = serde_json::to_string_pretty(&root).expect("Could not serialize to json");
+        let restored: TreeNode = serde_json::from_str(&json).expect("Could not deserialize from json");
+
+        // assert
+        assert!(json.contains("\"type\": \"Custom\""));
+        assert!(json.contains("\"b_interface_number\": 1"));
+        assert!(format!("{}", restored).contains("Custom(RawDescriptor { bytes: [5, 37, 3, 64, 0] })"));
+        assert_eq!(restored, root);
+        assert_bin(&bin_input, &restored);
+    }
+
+    #[test]
+    fn test_validate_fixtures() {
+        setup();
+        let mut filenames: Vec<String> = fs::read_dir("test/fixtures").unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with("config_desc_0.bin"))
+            .collect();
+        filenames.sort();
+        for filename in filenames {
+            let bin_input = read_bin_file(&filename);
+            let mut slice = &bin_input[..];
+            let root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+            let diags = root.validate();
+            diags.iter().for_each(|diag| info!("{}: {}", filename, diag));
+
+            // both devices report a class-specific header length that disagrees with their descriptors
+            let expected = match filename.as_str() {
+                "0x2bd9_0x0021_7_Huddly_IQ_config_desc_0.bin" => 2,
+                "0x0018_0x25c1_HuddleSHOT_config_desc_0.bin" => 1,
+                _ => 0,
+            };
+            assert_eq!(diags.len(), expected, "{}", filename);
+        }
+    }
+
+    #[test]
+    fn test_validate_broken_tree() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let mut root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+
+        // exercise: drop the first MJPEG frame and point the IAD past the last interface
+        let hdr = root.get_uvc_input_hdr().unwrap();
+        hdr.children[1].children.retain(|child| !matches!(&child.parsed, DescriptorTypes::DescriptorUvcFrameMjpeg(frame) if frame.b_frame_index == 1));
+        let config = &mut root.children[0];
+        if let DescriptorTypes::InterfaceAssociation(ref mut assoc) = config.children[0].parsed {
+            assoc.b_interface_count = 5;
+        }
+        let diags = root.validate();
+        diags.iter().for_each(|diag| info!("{}", diag));
+
+        // assert
+        let find = |needle: &str| diags.iter().find(|diag| diag.message.contains(needle)).unwrap_or_else(|| panic!("missing '{}'", needle));
+        assert!(find("w_total_length is 2503").path.ends_with("Config 1"));
+        assert!(find("b_num_frame_descriptors is 17 but 16").path.ends_with("UvcInputHeader / Format 2"));
+        assert_eq!(find("b_default_frame_index 1").fix, "set b_default_frame_index to 2");
+        assert!(find("IAD covers interface 4").path.ends_with("Config 1"));
+        assert!(diags.iter().all(|diag| diag.severity == Severity::Error));
+
+        let repairs = root.fix_tree();
+        repairs.iter().for_each(|repair| info!("{}", repair));
+        assert!(repairs.iter().any(|repair| repair.field == "b_interface_count" && repair.old == "5" && repair.new == "2"));
+        assert!(root.validate().is_empty());
+    }
+
+    #[test]
+    fn test_c925e_fix_tree_keeps_indices() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let mut root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+
+        // exercise: drop the YUY2 format, the first MJPEG frame and every interval above 10fps
+        let hdr = root.get_uvc_input_hdr().unwrap();
+        if let DescriptorTypes::UvcInputHeader(ref mut hdr) = hdr.parsed {
+            hdr.bma_controls = vec![0x11, 0x22];
+        }
+        hdr.children.retain(|child| !matches!(child.parsed, DescriptorTypes::DescriptorUvcFormatUncompressed(_)));
+        hdr.children[0].children.retain(|child| !matches!(&child.parsed, DescriptorTypes::DescriptorUvcFrameMjpeg(frame) if frame.b_frame_index == 1));
+        hdr.children[0].children.iter_mut().for_each(|child| if let DescriptorTypes::DescriptorUvcFrameMjpeg(ref mut frame) = child.parsed {
+            frame.dw_frame_interval.retain(|interval| *interval >= 1000000);
+        });
+        let repairs = root.fix_tree();
+        repairs.iter().for_each(|repair| info!("{}", repair));
+
+        // assert
+        assert!(root.validate().is_empty());
+        let hdr = root.get_uvc_input_hdr().unwrap();
+        match &hdr.parsed {
+            // the controls of the removed first format go with it
+            DescriptorTypes::UvcInputHeader(hdr) => assert_eq!(hdr.bma_controls, vec![0x22]),
+            _ => panic!("expected an input header"),
+        }
+        match &hdr.children[0].parsed {
+            DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) => {
+                assert_eq!(fmt.b_format_index, 2);
+                assert_eq!(fmt.b_num_frame_descriptors, 16);
+                assert_eq!(fmt.b_default_frame_index, 2);
+            }
+            _ => panic!("expected an MJPEG format"),
+        }
+        let frames: Vec<(u8, u32)> = hdr.children[0].children.iter().filter_map(|child| match &child.parsed {
+            DescriptorTypes::DescriptorUvcFrameMjpeg(frame) => Some((frame.b_frame_index, frame.dw_default_frame_interval)),
+            _ => None,
+        }).collect();
+        assert_eq!(frames.iter().map(|(idx, _)| *idx).collect::<Vec<u8>>(), (2..=17).collect::<Vec<u8>>());
+        assert!(frames.iter().all(|(_, interval)| *interval == 1000000));
+        assert!(!repairs.iter().any(|repair| repair.field == "b_format_index" || repair.field == "b_frame_index"));
+        assert!(!root.renumber_uvc_indices().is_identity());
+    }
+
+    #[test]
+    fn test_c925e_renumber_uvc_indices() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let mut root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let hdr = root.get_uvc_input_hdr().unwrap();
+        hdr.children.retain(|child| !matches!(child.parsed, DescriptorTypes::DescriptorUvcFormatUncompressed(_)));
+        hdr.children[0].children.retain(|child| !matches!(&child.parsed, DescriptorTypes::DescriptorUvcFrameMjpeg(frame) if frame.b_frame_index == 1));
+
+        // exercise
+        let map = root.renumber_uvc_indices();
+
+        // assert
+        assert!(!map.is_identity());
+        assert_eq!(map.entries.len(), 16);
+        assert_eq!(map.to_new(1, 2, 2), Some((1, 1)));
+        assert_eq!(map.to_old(1, 1, 16), Some((2, 17)));
+        assert_eq!(map.to_new(1, 2, 1), None);
+        assert_eq!(map.format_to_old(1, 1), Some(2));
+        let fmt = root.get_format_by_idx(1).expect("format 1 should exist");
+        match &fmt.parsed {
+            DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) => assert_eq!(fmt.b_default_frame_index, 1),
+            _ => panic!("format 1 should be the MJPEG format"),
+        }
+        assert!(fmt.get_frame_by_idx(16).is_some());
+        assert!(root.renumber_uvc_indices().is_identity());
+    }
+
+    #[test]
+    fn test_razer_renumber_keeps_order() {
+        setup();
+        let bin_input = read_bin_file("13d3_56d5_razer_integrated_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let mut root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let map = root.renumber_uvc_indices();
+        assert!(map.is_identity());
+        assert_bin(&bin_input, &root);
+    }
+
+    #[test]
+    fn test_c925e_streaming_translator() {
+        // setup: offer the host MJPEG only, without 640x480 and at 20fps or less
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let device = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let mut host = device.clone();
+        let hdr = host.get_uvc_input_hdr().unwrap();
+        hdr.children.retain(|child| !matches!(child.parsed, DescriptorTypes::DescriptorUvcFormatUncompressed(_)));
+        hdr.children[0].children.retain(|child| !matches!(&child.parsed, DescriptorTypes::DescriptorUvcFrameMjpeg(frame) if frame.b_frame_index == 1));
+        hdr.children[0].children.iter_mut().for_each(|child| if let DescriptorTypes::DescriptorUvcFrameMjpeg(ref mut frame) = child.parsed {
+            frame.dw_frame_interval.retain(|interval| *interval >= 500000);
+        });
+        let map = host.renumber_uvc_indices();
+        host.fix_tree();
+        let translator = StreamingTranslator::new(&host, &device).with_index_map(map);
+        let mut probe = UvcStreamingControl::deserialize(&mut &[0u8; 34][..]);
+        probe.b_format_index = 1;
+        probe.b_frame_index = 1;
+        probe.dw_frame_interval = 500000;
+
+        // exercise
+        let to_device = translator.to_device(1, &probe).unwrap();
+        let mut response = to_device;
+        response.dw_frame_interval = 333333;
+        let to_host = translator.to_host(1, &response).unwrap();
+
+        // assert: host MJPEG frame 1 is the device's 160x90 MJPEG frame 2
+        assert_eq!((to_device.b_format_index, to_device.b_frame_index, to_device.dw_frame_interval), (2, 2, 500000));
+        assert_eq!((to_host.b_format_index, to_host.b_frame_index, to_host.dw_frame_interval), (1, 1, 500000));
+        assert_eq!(to_host.version, UvcStreamingControlVersion::Uvc11);
+        probe.b_frame_index = 17;
+        assert!(translator.to_device(1, &probe).is_err());
+        response.b_format_index = 1; // YUY2 is not offered to the host
+        assert!(translator.to_host(1, &response).is_err());
+    }
+
+    #[test]
+    fn test_streaming_translator_continuous_intervals() {
+        // setup: make the device's MJPEG 160x90 frame continuous from 30fps to 5fps in 30fps steps
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let mut device = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let host = device.clone();
+        let hdr = device.get_uvc_input_hdr().unwrap();
+        let mjpeg = hdr.children.iter_mut().find(|child| child.parsed.is_uvc_format() && matches!(child.parsed, DescriptorTypes::DescriptorUvcFormatMjpeg(_))).unwrap();
+        let frame = mjpeg.children.iter_mut().find(|child| matches!(&child.parsed, DescriptorTypes::DescriptorUvcFrameMjpeg(frame) if frame.b_frame_index == 2)).unwrap();
+        if let DescriptorTypes::DescriptorUvcFrameMjpeg(ref mut desc) = frame.parsed {
+            desc.dw_frame_interval.clear();
+        }
+        frame.extra = [333333u32, 2000000, 333333].iter().flat_map(|interval| interval.to_le_bytes()).collect();
+        let translator = StreamingTranslator::new(&host, &device);
+        let mut probe = UvcStreamingControl::deserialize(&mut &[0u8; 34][..]);
+        probe.b_format_index = 2;
+        probe.b_frame_index = 2;
+
+        // exercise & assert: intervals are clamped to the range and rounded to the step
+        for (interval, expected) in [(400000, 333333), (1000000, 999999), (1900000, 1999998), (5000000, 1999998), (100000, 333333)] {
+            probe.dw_frame_interval = interval;
+            assert_eq!(translator.to_device(1, &probe).unwrap().dw_frame_interval, expected);
+        }
+    }
+
+    #[test]
+    fn test_streaming_translator_two_mjpeg_formats() {
+        // setup: a device with a second MJPEG format, of which the host is only offered the second
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let mut device = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let hdr = device.get_uvc_input_hdr().unwrap();
+        let mut second = hdr.children.iter().find(|child| matches!(child.parsed, DescriptorTypes::DescriptorUvcFormatMjpeg(_))).unwrap().clone();
+        if let DescriptorTypes::DescriptorUvcFormatMjpeg(ref mut fmt) = second.parsed {
+            fmt.b_format_index = 3;
+        }
+        hdr.children.push(second);
+        let mut host = device.clone();
+        let hdr = host.get_uvc_input_hdr().unwrap();
+        hdr.children.retain(|child| !matches!(&child.parsed, DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) if fmt.b_format_index == 2));
+        hdr.children.retain(|child| !matches!(child.parsed, DescriptorTypes::DescriptorUvcFormatUncompressed(_)));
+        let map = host.renumber_uvc_indices();
+        let mut probe = UvcStreamingControl::deserialize(&mut &[0u8; 34][..]);
+        probe.b_format_index = 1;
+        probe.b_frame_index = 1;
+
+        // exercise
+        let by_kind = StreamingTranslator::new(&host, &device).to_device(1, &probe).unwrap();
+        let translator = StreamingTranslator::new(&host, &device).with_index_map(map);
+        let to_device = translator.to_device(1, &probe).unwrap();
+        let to_host = translator.to_host(1, &to_device).unwrap();
+
+        // assert: without the map the first MJPEG format of the device is picked
+        assert_eq!((by_kind.b_format_index, by_kind.b_frame_index), (2, 1));
+        assert_eq!((to_device.b_format_index, to_device.b_frame_index), (3, 1));
+        assert_eq!((to_host.b_format_index, to_host.b_frame_index), (1, 1));
+    }
+
+    #[test]
+    fn test_streaming_translator_two_mjpeg_formats_high_fps() {
+        // setup: a second MJPEG format whose first frame only runs at 60fps, so the host loses it
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let mut device = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let hdr = device.get_uvc_input_hdr().unwrap();
+        let mut second = hdr.children.iter().find(|child| matches!(child.parsed, DescriptorTypes::DescriptorUvcFormatMjpeg(_))).unwrap().clone();
+        if let DescriptorTypes::DescriptorUvcFormatMjpeg(ref mut fmt) = second.parsed {
+            fmt.b_format_index = 3;
+        }
+        second.children.iter_mut().for_each(|child| match child.parsed {
+            DescriptorTypes::DescriptorUvcFrameMjpeg(ref mut frame) if frame.b_frame_index == 1 => frame.dw_frame_interval = vec![166666],
+            _ => {}
+        });
+        hdr.children.push(second);
+        let mut host = device.clone();
+        let hdr = host.get_uvc_input_hdr().unwrap();
+        hdr.children.retain(|child| !matches!(&child.parsed, DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) if fmt.b_format_index == 2));
+        hdr.children.retain(|child| !matches!(child.parsed, DescriptorTypes::DescriptorUvcFormatUncompressed(_)));
+        host.remove_high_fps();
+        let map = host.renumber_uvc_indices();
+        host.fix_tree();
+        let mut probe = UvcStreamingControl::deserialize(&mut &[0u8; 34][..]);
+        probe.b_format_index = 1;
+        probe.b_frame_index = 1;
+
+        // exercise
+        let translator = StreamingTranslator::new(&host, &device).with_index_map(map);
+        let to_device = translator.to_device(1, &probe).unwrap();
+        let to_host = translator.to_host(1, &to_device).unwrap();
+
+        // assert: host frame 1 of format 1 is frame 2 of the device's second MJPEG format
+        assert!(host.renumber_uvc_indices().is_identity());
+        assert_eq!((to_device.b_format_index, to_device.b_frame_index), (3, 2));
+        assert_eq!((to_host.b_format_index, to_host.b_frame_index), (1, 1));
+        let mut response = to_device;
+        response.b_frame_index = 1; // dropped by remove_high_fps
+        assert!(translator.to_host(1, &response).is_err());
+    }
+
+    #[test]
+    fn test_bose_vb1_video_filter() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0xa213_0x05a7_114_Bose_Videobar_VB1_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let mut root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let filter = VideoFilter {
+            max_fps: Some(30),
+            max_height: Some(720),
+            allowed_fourccs: vec!["MJPG".to_string(), "NV12".to_string(), "H264".to_string()],
+            ..Default::default()
+        };
+
+        // exercise: YUY2 is not allowed and every H.264 frame is either 4K or 60fps
+        let report = root.apply_video_filter(&filter);
+        info!("filtered tree:\n{}", root);
+
+        // assert
+        assert!(root.validate().is_empty());
+        let hdr = root.get_uvc_input_hdr().unwrap();
+        match &hdr.parsed {
+            DescriptorTypes::UvcInputHeader(hdr) => assert_eq!(hdr.bma_controls, vec![0, 0]),
+            _ => panic!("expected an input header"),
+        }
+        let formats: Vec<(u8, usize)> = hdr.children.iter().filter_map(|child| match &child.parsed {
+            DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) => Some((fmt.b_format_index, child.uvc_frame_indices().len())),
+            DescriptorTypes::DescriptorUvcFormatUncompressed(fmt) if fmt.guid_format == UncompressedFormats::NV12 => Some((fmt.b_format_index, child.uvc_frame_indices().len())),
+            _ => None,
+        }).collect();
+        assert_eq!(formats, vec![(1, 6), (2, 1)]);
+        assert_eq!(root.num_uvc_formats(), 2);
+        assert!(!report.index_map.is_identity());
+        assert!(report.index_map.entries.iter().all(|entry| entry.new_format <= 2));
+    }
+
+    #[test]
+    fn test_c925e_audio_filter() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let mut root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+        let mut dual_rate = root.clone();
+        let mut alt = dual_rate.get_iface_by_num(IfaceAltSetting::new(3, 3)).unwrap().clone();
+        alt.children.iter_mut().for_each(|child| if let DescriptorTypes::UacFormatTypeI(ref mut fmt) = child.parsed {
+            fmt.t_sam_freq = vec![16000, 32000];
+            fmt.b_sam_freq_type = 2;
+        });
+        dual_rate.replace_node(&alt, |parsed| matches!(parsed, DescriptorTypes::Interface(iface) if iface.b_interface_number == 3 && iface.b_alternate_setting == 3));
+
+        // exercise
+        root.apply_audio_filter(&AudioFilter { sample_rates: vec![32000], ..Default::default() });
+        dual_rate.apply_audio_filter(&AudioFilter { sample_rates: vec![16000], ..Default::default() });
+
+        // assert: only the 32kHz setting is left and it became alternate setting 1
+        assert!(root.validate().is_empty());
+        assert!(root.get_iface_by_num(IfaceAltSetting::new(3, 2)).is_none());
+        let alt = root.get_iface_by_num(IfaceAltSetting::new(3, 1)).unwrap();
+        let fmt = alt.get_uac_fmt().unwrap();
+        assert!(matches!(&fmt.parsed, DescriptorTypes::UacFormatTypeI(fmt) if fmt.t_sam_freq == vec![32000]));
+        // assert: trimming 32kHz shrinks the endpoint to 16kHz worth of samples
+        let alt = dual_rate.get_iface_by_num(IfaceAltSetting::new(3, 2)).unwrap();
+        assert!(matches!(&alt.get_uac_ep().unwrap().parsed, DescriptorTypes::UacEndpoint(ep) if ep.w_max_packet_size == 68));
+    }
+
+    #[test]
+    fn test_c925e_audio_filter_removes_interface() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut slice = &bin_input[..];
+        let mut root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+
+        // exercise
+        let repairs = root.apply_audio_filter(&AudioFilter { sample_rates: vec![48000], ..Default::default() });
+        repairs.iter().for_each(|repair| info!("{}", repair));
+
+        // assert
+        assert_eq!(root.find_uac_ifaces(), vec![2]);
+        let ac = root.get_iface_by_num(IfaceAltSetting::new(2, 0)).unwrap();
+        assert!(matches!(&ac.children[0].parsed, DescriptorTypes::UacAcHeader(hdr) if hdr.ba_interface_nr.is_empty() && hdr.b_in_collection == 0));
+        assert!(repairs.iter().any(|repair| repair.field == "b_interface_count" && repair.new == "1"));
+        assert!(root.validate().is_empty());
+    }
+
+    #[test]
+    fn test_huddly_iq() {
+        let txt_filename = "0x2bd9_0x0021_7_Huddly_IQ_config_desc_0.txt";
+        let bin_filename = "0x2bd9_0x0021_7_Huddly_IQ_config_desc_0.bin";
+
+        descriptor_test(txt_filename, &bin_filename);
+    }
+
+    #[test]
+    fn test_c925e_lsusb() {
+        // setup: the expected text is the renderer's own output, kept to catch regressions; the
+        // lines below are checked against the layout of lsusb -v until a capture of a C925e replaces it
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let txt_expected = read_txt_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0_lsusb_rendered.txt");
+        let mut slice = &bin_input[..];
+        let root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+
+        // exercise
+        let txt_actual = root.lsusb().to_string();
+
+        // assert
+        for line in [
+            "    bmAttributes         0x80",
+            "      (Bus Powered)",
+            "    MaxPower              500mA",
+            "        bEndpointAddress     0x83  EP 3 IN",
+            "          Transfer Type            Isochronous",
+            "        wMaxPacketSize     0x0a80  2x 640 bytes",
+            "      VideoStreaming Interface Descriptor:",
+            "        guidFormat                            {32595559-0000-0010-8000-00aa00389b71}",
+            "        dwFrameInterval( 0)            333333",
+            "        tSamFreq[ 0]        16000",
+        ] {
+            assert!(txt_actual.lines().any(|actual| actual == line), "missing line {:?}", line);
+        }
+        assert_eq!(txt_actual, txt_expected.replace("\r", ""));
+    }
+
+    #[test]
+    fn test_lsusb_round_trip() {
+        setup();
+        let mut filenames: Vec<String> = fs::read_dir("test/fixtures").unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with("config_desc_0.bin"))
+            .collect();
+        filenames.sort();
+        for filename in filenames {
+            // setup
+            let bin_input = read_bin_file(&filename);
+            let mut slice = &bin_input[..];
+            let root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+            let mut bin_expected = vec![];
+            root.serialize(&mut bin_expected).unwrap();
+
+            // exercise
+            let parsed = parse_lsusb(&root.lsusb().to_string()).expect("Could not parse lsusb output");
+
+            // assert
+            assert!(parsed.device.is_none());
+            assert_eq!(parsed.root, root, "{}", filename);
+            assert_bin(&bin_expected, &parsed.root);
+        }
+    }
+
+    #[test]
+    fn test_c925e_parse_lsusb_device() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let txt_config = read_txt_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0_lsusb_rendered.txt");
+        let txt_input = format!("\
+Bus 001 Device 007: ID 046d:085b Logitech, Inc. Logitech Webcam C925e
+Device Descriptor:
+  bLength                18
+  bDescriptorType         1
+  bcdUSB               2.00
+  bDeviceClass          239 Miscellaneous Device
+  bDeviceSubClass         2 
+  bDeviceProtocol         1 Interface Association
+  bMaxPacketSize0        64
+  idVendor           0x046d Logitech, Inc.
+  idProduct          0x085b 
+  bcdDevice            0.16
+  iManufacturer           0 
+  iProduct                2 Logitech Webcam C925e
+  iSerial                 1 A1B2C3D4
+  bNumConfigurations      1
+{}Device Qualifier (for other device speed):
+  bLength                10
+  bDescriptorType         6
+  bcdUSB               2.00
+  bDeviceClass          239 Miscellaneous Device
+  bDeviceSubClass         2 
+  bDeviceProtocol         1 Interface Association
+  bMaxPacketSize0        64
+  bNumConfigurations      1
+Device Status:     0x0000
+  (Bus Powered)
+", txt_config);
+
+        // exercise
+        let parsed = parse_lsusb(&txt_input).expect("Could not parse lsusb output");
+
+        // assert
+        let device = parsed.device.expect("No device descriptor");
+        assert_eq!((device.id_vendor, device.id_product, device.bcd_device), (0x046d, 0x085b, 0x0016));
+        assert_eq!(device.i_serial_number, 1);
+        assert_bin(&bin_input, &parsed.root);
+    }
+
+    #[test]
+    fn test_parse_lsusb_keyboard() {
+        // setup: written in the layout of lsusb -v, with what the renderer never prints: string
+        // names after indices, a warning, HID report descriptors, BOS capabilities and the status
+        setup();
+        let txt_input = "\
+Bus 003 Device 004: ID 046d:c31c Logitech, Inc. Keyboard K120
+Couldn't open device, some information will be missing
+Device Descriptor:
+  bLength                18
+  bDescriptorType         1
+  bcdUSB               1.10
+  bDeviceClass            0 
+  bDeviceSubClass         0 
+  bDeviceProtocol         0 
+  bMaxPacketSize0         8
+  idVendor           0x046d Logitech, Inc.
+  idProduct          0xc31c Keyboard K120
+  bcdDevice           64.00
+  iManufacturer           1 Logitech
+  iProduct                2 USB Keyboard
+  iSerial                 0 
+  bNumConfigurations      1
+  Configuration Descriptor:
+    bLength                 9
+    bDescriptorType         2
+    wTotalLength       0x0022
+    bNumInterfaces          1
+    bConfigurationValue     1
+    iConfiguration          0 
+    bmAttributes         0xa0
+      (Bus Powered)
+      Remote Wakeup
+    MaxPower               90mA
+    Interface Descriptor:
+      bLength                 9
+      bDescriptorType         4
+      bInterfaceNumber        0
+      bAlternateSetting       0
+      bNumEndpoints           1
+      bInterfaceClass         3 Human Interface Device
+      bInterfaceSubClass      1 Boot Interface Subclass
+      bInterfaceProtocol      1 Keyboard
+      iInterface              2 USB Keyboard
+        HID Device Descriptor:
+          bLength                 9
+          bDescriptorType        33
+          bcdHID               1.10
+          bCountryCode            0 Not supported
+          bNumDescriptors         1
+          bDescriptorType        34 Report
+          wDescriptorLength      65
+          Report Descriptors: 
+            ** UNAVAILABLE **
+      Endpoint Descriptor:
+        bLength                 7
+        bDescriptorType         5
+        bEndpointAddress     0x81  EP 1 IN
+        bmAttributes            3
+          Transfer Type            Interrupt
+          Synch Type               None
+          Usage Type               Data
+        wMaxPacketSize     0x0008  1x 8 bytes
+        bInterval              10
+Binary Object Store Descriptor:
+  bLength                 5
+  bDescriptorType        15
+  wTotalLength       0x000c
+  bNumDeviceCaps          1
+  USB 2.0 Extension Device Capability:
+    bLength                 7
+    bDescriptorType        16
+    bDevCapabilityType      2
+    bmAttributes   0x00000002
+      HIRD Link Power Management (LPM) Supported
+Device Status:     0x0000
+  (Bus Powered)
+";
+
+        // exercise
+        let parsed = parse_lsusb(txt_input).expect("Could not parse lsusb output");
+
+        // assert
+        let device = parsed.device.expect("No device descriptor");
+        assert_eq!((device.id_vendor, device.id_product, device.bcd_device, device.i_product), (0x046d, 0xc31c, 0x6400, 2));
+        let bin_expected = vec![
+            0x09, 0x02, 0x22, 0x00, 0x01, 0x01, 0x00, 0xa0, 0x2d,
+            0x09, 0x04, 0x00, 0x00, 0x01, 0x03, 0x01, 0x01, 0x02,
+            0x09, 0x21, 0x10, 0x01, 0x00, 0x01, 0x22, 0x41, 0x00,
+            0x07, 0x05, 0x81, 0x03, 0x08, 0x00, 0x0a,
+        ];
+        assert_bin(&bin_expected, &parsed.root);
+    }
+
+    #[test]
+    fn test_parse_lsusb_errors() {
+        setup();
+        assert!(parse_lsusb("Bus 001 Device 007: ID 046d:085b\n").is_err());
+        assert!(parse_lsusb("  Configuration Descriptor:\n    ** UNRECOGNIZED:  zz 24\n").is_err());
+        assert!(parse_lsusb("  Configuration Descriptor:\n    bLength 0\n").is_err());
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        setup();
+        let mut filenames: Vec<String> = fs::read_dir("test/fixtures").unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with("config_desc_0.bin"))
+            .collect();
+        filenames.sort();
+        for filename in filenames {
+            // setup
+            let bin_input = read_bin_file(&filename);
+            let mut slice = &bin_input[..];
+            let root = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+            let mut bin_expected = vec![];
+            root.serialize(&mut bin_expected).unwrap();
+
+            // exercise
+            let parsed: TreeNode = format!("{}", root).parse().expect("Could not parse text");
+
+            // assert
+            assert_eq!(parsed, root, "{}", filename);
+            assert_bin(&bin_expected, &parsed);
+        }
+    }
+
+    #[test]
+    fn test_meetup_text_fixture_to_binary() {
+        // setup
+        setup();
+        let txt_input = read_txt_file("046d_0866_meetup_config_desc_0_no_60hz.txt");
+
+        // exercise
+        let root: TreeNode = txt_input.parse().expect("Could not parse text");
+        let mut bin_actual = vec![];
+        root.serialize(&mut bin_actual).unwrap();
+        let mut slice = &bin_actual[..];
+        let actual = TreeNode::deserialize(&mut slice).expect("Could not deserialize");
+
+        // assert
+        assert_txt(&txt_input, &actual);
+    }
+
+    #[test]
+    fn test_text_errors() {
+        setup();
+        let config = "Config(DescriptorConfig { w_total_length: 9, b_num_interfaces: 0, b_configuration_value: 1, i_configuration: 0, bm_attributes: 128, b_max_power: 250 })";
+        assert!(format!("Root\n\t{}\n", config).parse::<TreeNode>().is_ok());
+        assert!(format!("Root\n\t\t{}\n", config).parse::<TreeNode>().is_err());
+        assert!("Root\nRoot\n".parse::<TreeNode>().is_err());
+        assert!(format!("Root\n\t{}\n", config.replace("b_max_power", "b_max_powr")).parse::<TreeNode>().is_err());
+        assert!(format!("Root\n\t{}\n", config.replace("250", "500")).parse::<TreeNode>().is_err());
+        assert!("Root\n\tCustom(RawDescriptor { bytes: [5, 37, 3, 64, 0] })\n".parse::<TreeNode>().is_err());
+        assert!("".parse::<TreeNode>().is_err());
+    }
+
+    #[test]
+    fn test_c925e_hexdump() {
+        // setup
+        setup();
+        let bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+
+        // exercise
+        let annotations = annotate(&bin_input);
+        let dump = render_annotations(&annotations);
+
+        // assert
+        assert_eq!(annotations.iter().map(|annotation| annotation.bytes.len()).sum::<usize>(), bin_input.len());
+        assert!(annotations.iter().all(|annotation| annotation.parsed.is_some() && annotation.extra == 0));
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines[0], "0x0000  09 02 c7 09 04 01 00 80 fa                       Config wTotalLength=2503 bNumInterfaces=4 bConfigurationValue=1 iConfiguration=0 bmAttributes=128 bMaxPower=250");
+        assert_eq!(lines[3], "0x001a  0d 24 01 00 01 d6 00 00 a3 e1 11 01 01           UvcHeaderDescriptor bcdUVC=0x0100 wTotalLength=214 dwClockFrequency=300000000 bInCollection=1 baInterfaceNr=[1]");
+        assert_eq!(lines[5], "0x0037  0a 02");
+        assert!(!dump.contains("!!"));
+    }
+
+    #[test]
+    fn test_hexdump_extra_and_truncated_bytes() {
+        // setup
+        setup();
+        let blob = [
+            0x09, 0x02, 0x1b, 0x00, 0x01, 0x01, 0x00, 0x80, 0xfa, // config
+            0x0b, 0x04, 0x00, 0x00, 0x00, 0xff, 0x00, 0x00, 0x00, 0xde, 0xad, // interface with two vendor bytes
+            0x07, 0x05, 0x81, 0x02, // endpoint cut short
+        ];
+
+        // exercise
+        let annotations = annotate(&blob);
+        let dump = render_annotations(&annotations);
+
+        // assert
+        assert_eq!(annotations.len(), 3);
+        assert_eq!((annotations[1].offset, annotations[1].extra), (9, 2));
+        assert!(annotations[2].parsed.is_none());
+        assert_eq!(dump, "\
+0x0000  09 02 1b 00 01 01 00 80 fa                       Config wTotalLength=27 bNumInterfaces=1 bConfigurationValue=1 iConfiguration=0 bmAttributes=128 bMaxPower=250
+0x0009  0b 04 00 00 00 ff 00 00 00                       Interface bInterfaceNumber=0 bAlternateSetting=0 bNumEndpoints=0 bInterfaceClass=255 bInterfaceSubClass=0 bInterfaceProtocol=0 iInterface=0
+0x0012  de ad                                            !! 2 extra bytes not parsed
+0x0014  07 05 81 02                                      !! truncated: bLength 7 but only 4 bytes left
+");
+    }
+
+    #[test]
+    fn test_hexdump_short_typed_descriptor() {
+        // setup: a config descriptor followed by one cut down to 3 bytes
+        setup();
+        let blob = [9, 2, 9, 0, 1, 1, 0, 0x80, 50, 3, 2, 0];
+
+        // exercise
+        let annotations = annotate(&blob);
+        let dump = render_annotations(&annotations);
+
+        // assert
+        assert_eq!(annotations.len(), 2);
+        assert!(annotations[1].parsed.is_none());
+        assert!(dump.ends_with("0x0009  03 02 00                                         !! bLength 3 too short for descriptor type 0x02\n"));
+    }
+
+    #[test]
+    fn test_parse_invalid_b_length() {
+        setup();
+        for b_length in [0u8, 1] {
+            // setup: a config descriptor followed by one whose bLength can not even hold its header
+            let blob = [9, 2, 12, 0, 1, 1, 0, 0x80, 50, b_length, 4, 0];
+
+            // exercise
+            let mut slice = &blob[..];
+            let root = parse_list(&mut slice);
+
+            // assert: parsing stops in front of it
+            assert_eq!(root.children.len(), 1, "bLength {}", b_length);
+            assert_eq!(slice, &blob[9..]);
+            assert!(TreeNode::deserialize(&mut &blob[..]).is_err(), "bLength {}", b_length);
+        }
+    }
+
+    #[test]
+    fn test_parse_truncated_typed_descriptor() {
+        // setup: a video streaming interface followed by an MJPEG format cut down to 6 of 11 bytes
+        setup();
+        let blob = [
+            9, 2, 24, 0, 1, 1, 0, 0x80, 50,
+            9, 4, 1, 0, 0, 0x0e, 2, 0, 0,
+            6, 0x24, 6, 1, 1, 0,
+        ];
+
+        // exercise
+        let root = TreeNode::deserialize(&mut &blob[..]).expect("Could not deserialize");
+        let mut bin_output = vec![];
+        root.serialize(&mut bin_output).unwrap();
+
+        // assert
+        let iface = &root.children[0].children[0];
+        assert!(matches!(&iface.children[0].parsed, DescriptorTypes::Unknown(desc) if desc.desc_type == 0x24 && desc.bytes == [6, 1, 1, 0]));
+        assert_eq!(bin_output, blob);
+    }
+
+    #[test]
+    fn test_round_trip_fixtures() {
+        // the other .bin fixtures are HID report descriptors, not descriptor trees
+        let mut filenames: Vec<String> = fs::read_dir("test/fixtures").unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with("config_desc_0.bin"))
+            .collect();
+        filenames.sort();
+        assert!(!filenames.is_empty());
+        for filename in filenames {
+            let bin_input = read_bin_file(&filename);
+            let root = TreeNode::deserialize(&mut &bin_input[..]).expect("Could not deserialize");
+            let mut bin_output = vec![];
+            root.serialize(&mut bin_output).unwrap();
+            assert_eq!(bin_input, bin_output, "{}", filename);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_extra_bytes() {
+        setup_logger();
+        // vendor bytes after the C925e's VS input header and its first format descriptor
+        let mut bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let annotations = annotate(&bin_input);
+        let hdr = annotations.iter().find(|a| matches!(a.parsed, Some(DescriptorTypes::UvcInputHeader(_)))).unwrap();
+        let fmt = annotations.iter().find(|a| matches!(&a.parsed, Some(parsed) if parsed.is_uvc_format())).unwrap();
+        let (hdr_offset, hdr_len, fmt_offset, fmt_len) = (hdr.offset, hdr.bytes.len(), fmt.offset, fmt.bytes.len());
+        bin_input.splice(fmt_offset + fmt_len..fmt_offset + fmt_len, [0xde, 0xad, 0xbe]);
+        bin_input[fmt_offset] += 3;
+        bin_input.splice(hdr_offset + hdr_len..hdr_offset + hdr_len, [0x42, 0x43]);
+        bin_input[hdr_offset] += 2;
+        let hdr_total = u16::from_le_bytes([bin_input[hdr_offset + 4], bin_input[hdr_offset + 5]]) + 5;
+        bin_input[hdr_offset + 4..hdr_offset + 6].copy_from_slice(&hdr_total.to_le_bytes());
+        let cfg_total = u16::from_le_bytes([bin_input[2], bin_input[3]]) + 5;
+        bin_input[2..4].copy_from_slice(&cfg_total.to_le_bytes());
+
+        let root = TreeNode::deserialize(&mut &bin_input[..]).unwrap();
+        let hdr = root.get_node(|parsed| matches!(parsed, DescriptorTypes::UvcInputHeader(_))).unwrap();
+        assert_eq!(hdr.extra, vec![0x42, 0x43]);
+        assert_eq!(hdr.children[0].extra, vec![0xde, 0xad, 0xbe]);
+        let mut bin_output = vec![];
+        root.serialize(&mut bin_output).unwrap();
+        assert_eq!(bin_input, bin_output);
+
+        let text = root.to_string();
+        assert!(text.contains(" extra: [66, 67]\n"));
+        assert_eq!(text.parse::<TreeNode>().unwrap(), root);
+    }
+
+    #[test]
+    fn test_input_header_short_total_length() {
+        setup_logger();
+        // a VS input header whose wTotalLength does not even cover itself
+        let mut bin_input = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let annotations = annotate(&bin_input);
+        let hdr_offset = annotations.iter().find(|a| matches!(a.parsed, Some(DescriptorTypes::UvcInputHeader(_)))).unwrap().offset;
+        bin_input[hdr_offset + 4..hdr_offset + 6].copy_from_slice(&5u16.to_le_bytes());
+
+        let root = TreeNode::deserialize(&mut &bin_input[..]).unwrap();
+        let hdr = root.get_node(|parsed| matches!(parsed, DescriptorTypes::UvcInputHeader(_))).unwrap();
+        assert!(hdr.children.is_empty());
+        assert_bin(&bin_input, &root);
+    }
+
+    #[test]
+    fn test_sysfs_devices() {
+        let root = std::env::temp_dir().join(format!("usb-desc-parse-sysfs-{}", std::process::id()));
+        let devices = root.join("bus/usb/devices");
+        let camera = DescriptorDevice {
+            bcd_usb: 0x0200, b_device_class: 0xef, b_device_sub_class: 2, b_device_protocol: 1, b_max_packet_size0: 64,
+            id_vendor: 0x046d, id_product: 0x085b, bcd_device: 0x0016, i_manufacturer: 0, i_product: 2, i_serial_number: 1,
+            b_num_configurations: 1,
+        };
+        let config = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let mut descriptors = vec![];
+        camera.serialize(&mut descriptors);
+        descriptors.extend(&config);
+        let attrs = [("busnum", "1\n"), ("devnum", "7\n"), ("bConfigurationValue", "1\n"), ("speed", "480\n"),
+            ("product", "Logitech Webcam C925e\n"), ("serial", "8C2E1F9F\n")];
+        fs::create_dir_all(devices.join("1-2")).unwrap();
+        fs::write(devices.join("1-2/descriptors"), &descriptors).unwrap();
+        for (name, value) in attrs {
+            fs::write(devices.join("1-2").join(name), value).unwrap();
+        }
+        // interfaces are skipped, unreadable devices are left out
+        fs::create_dir_all(devices.join("1-2:1.0")).unwrap();
+        fs::create_dir_all(devices.join("1-3")).unwrap();
+        fs::write(devices.join("1-3/busnum"), "1\n").unwrap();
+        fs::write(devices.join("1-3/devnum"), "8\n").unwrap();
+        fs::write(devices.join("1-3/descriptors"), [0x12, 0x01, 0x00]).unwrap();
+
+        let found = Sysfs::new(&root).devices();
+        fs::remove_dir_all(&root).unwrap();
+        let found = found.unwrap();
+        assert_eq!(found.len(), 1);
+        let device = &found[&DeviceAddress::new(1, 7)];
+        assert_eq!(device.path, devices.join("1-2"));
+        assert_eq!(device.device, camera);
+        assert_eq!(device.configuration_value, Some(1));
+        assert_eq!(device.speed, Some(UsbSpeed::High));
+        assert_eq!(device.manufacturer, None);
+        assert_eq!(device.product.as_deref(), Some("Logitech Webcam C925e"));
+        assert_eq!(device.serial.as_deref(), Some("8C2E1F9F"));
+        assert_eq!(device.root, TreeNode::deserialize(&mut &config[..]).unwrap());
+        assert!(parse_descriptors(&config).is_err());
+    }
+
+    #[test]
+    fn test_parse_descriptors_malformed() {
+        // setup
+        setup();
+        let device = DescriptorDevice {
+            bcd_usb: 0x0200, b_device_class: 0, b_device_sub_class: 0, b_device_protocol: 0, b_max_packet_size0: 64,
+            id_vendor: 0x046d, id_product: 0x085b, bcd_device: 0x0016, i_manufacturer: 0, i_product: 0, i_serial_number: 0,
+            b_num_configurations: 1,
+        };
+        let mut descriptors = vec![];
+        device.serialize(&mut descriptors);
+        let with_config = |config: &[u8]| [&descriptors[..], config].concat();
+
+        // exercise and assert: a bLength of 0, an endpoint cut short and a config too short for its type
+        let err = parse_descriptors(&with_config(&[9, 2, 12, 0, 1, 1, 0, 0x80, 50, 0, 4, 0])).unwrap_err();
+        assert!(err.to_string().contains("046d:085b"), "{}", err);
+        assert!(parse_descriptors(&with_config(&[9, 2, 16, 0, 1, 1, 0, 0x80, 50, 7, 5, 0x81, 2, 0x40, 0])).is_err());
+        let (_, root) = parse_descriptors(&with_config(&[9, 2, 12, 0, 1, 1, 0, 0x80, 50, 3, 2, 0])).unwrap();
+        assert!(matches!(root.children[0].children[0].parsed, DescriptorTypes::Unknown(_)));
+        let mut short_device = with_config(&[9, 2, 9, 0, 1, 1, 0, 0x80, 50]);
+        short_device[0] = 9;
+        assert!(parse_descriptors(&short_device).is_err());
+    }
+
+    fn write_sysfs_device(devices: &std::path::Path, name: &str, devnum: u8, config_filename: &str) {
+        let device = DescriptorDevice {
+            bcd_usb: 0x0200, b_device_class: 0xef, b_device_sub_class: 2, b_device_protocol: 1, b_max_packet_size0: 64,
+            id_vendor: 0x046d, id_product: devnum as u16, bcd_device: 0x0100, i_manufacturer: 0, i_product: 0, i_serial_number: 0,
+            b_num_configurations: 1,
+        };
+        let mut descriptors = vec![];
+        device.serialize(&mut descriptors);
+        descriptors.extend(read_bin_file(config_filename));
+        let dir = devices.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("descriptors"), descriptors).unwrap();
+        fs::write(dir.join("busnum"), "1\n").unwrap();
+        fs::write(dir.join("devnum"), format!("{}\n", devnum)).unwrap();
+        fs::write(dir.join("bConfigurationValue"), "1\n").unwrap();
+    }
+
+    #[test]
+    fn test_device_inventory() {
+        setup_logger();
+        let root = std::env::temp_dir().join(format!("usb-desc-parse-inventory-{}", std::process::id()));
+        let devices = root.join("bus/usb/devices");
+        write_sysfs_device(&devices, "1-2", 7, "0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        write_sysfs_device(&devices, "1-3", 9, "0x0867_0x046d_MeetUp_Speakerphone_config_desc_0.bin");
+        let mut inventory = DeviceInventory::new(Sysfs::new(&root));
+        inventory.scan().unwrap();
+        assert_eq!(inventory.len(), 2);
+        assert_eq!(inventory.uvc_streaming_interfaces(), vec![InterfaceAddress::new(1, 7, 1)]);
+        assert_eq!(inventory.uac_interfaces(), vec![
+            InterfaceAddress::new(1, 7, 2), InterfaceAddress::new(1, 7, 3),
+            InterfaceAddress::new(1, 9, 0), InterfaceAddress::new(1, 9, 1), InterfaceAddress::new(1, 9, 2),
+        ]);
+        assert_eq!(inventory.speakerphones(), vec![Speakerphone {
+            mic_iface: InterfaceAddress::new(1, 9, 1),
+            mic_ep: EndpointAddress::new(1, 9, 0x82),
+            speaker_iface: InterfaceAddress::new(1, 9, 2),
+            speaker_ep: EndpointAddress::new(1, 9, 0x01),
+        }]);
+
+        // the camera is unplugged and comes back with a new device number
+        let remove = b"remove@/devices/pci0000:00/0000:00:14.0/usb1/1-2\0ACTION=remove\0DEVPATH=/devices/pci0000:00/0000:00:14.0/usb1/1-2\0SUBSYSTEM=usb\0DEVTYPE=usb_device\0BUSNUM=001\0DEVNUM=007\0";
+        let add = b"add@/devices/pci0000:00/0000:00:14.0/usb1/1-2\0ACTION=add\0DEVPATH=/devices/pci0000:00/0000:00:14.0/usb1/1-2\0SUBSYSTEM=usb\0DEVTYPE=usb_device\0BUSNUM=001\0DEVNUM=010\0";
+        let iface = b"add@/devices/pci0000:00/0000:00:14.0/usb1/1-2/1-2:1.0\0ACTION=add\0SUBSYSTEM=usb\0DEVTYPE=usb_interface\0";
+        assert_eq!(HotplugEvent::from_uevent(iface), None);
+        assert_eq!(HotplugEvent::from_uevent(remove), Some(HotplugEvent::Removed(DeviceAddress::new(1, 7))));
+        inventory.ingest(HotplugEvent::from_uevent(remove));
+        assert!(inventory.get(DeviceAddress::new(1, 7)).is_none());
+        assert!(inventory.uvc_streaming_interfaces().is_empty());
+
+        write_sysfs_device(&devices, "1-2", 10, "0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        assert_eq!(HotplugEvent::from_uevent(add), Some(HotplugEvent::Added("1-2".to_string())));
+        inventory.ingest(HotplugEvent::from_uevent(add));
+        // a device that can not be read is not added
+        assert!(inventory.handle(&HotplugEvent::Added("1-4".to_string())).is_err());
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(inventory.len(), 2);
+        assert_eq!(inventory.uvc_streaming_interfaces(), vec![InterfaceAddress::new(1, 10, 1)]);
+    }
+
+    #[test]
+    fn test_usbmon_descriptors() {
+        setup_logger();
+        let camera = read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin");
+        let speakerphone = read_bin_file("0x0867_0x046d_MeetUp_Speakerphone_config_desc_0.bin");
+        // the same capture as little endian pcap and big endian pcapng
+        for filename in ["usbmon_enumeration.pcap", "usbmon_enumeration.pcapng"] {
+            let packets = read_capture(&read_bin_file(filename)).unwrap();
+            assert_eq!(packets.len(), 16, "{}", filename);
+            let transfers = control_transfers(&packets);
+            assert_eq!(transfers.len(), 8);
+            assert_eq!(transfers[3].setup, SetupPacket { bm_request_type: 0, b_request: 9, w_value: 1, w_index: 0, w_length: 0 });
+            assert_eq!((transfers[3].setup.dir(), transfers[3].setup.xfer_type(), transfers[3].setup.recip()), (XferDir::ToDev, XferType::Std, Recip::Dev));
+            assert_eq!(transfers[3].status, Some(0));
+
+            let devices = captured_descriptors(&transfers).unwrap();
+            assert_eq!(devices.len(), 2);
+            let cam = &devices[&DeviceAddress::new(1, 7)];
+            assert_eq!(cam.device.map(|device| device.id_product), Some(0x085b));
+            assert_eq!(cam.root, TreeNode::deserialize(&mut &camera[..]).unwrap());
+            assert!(cam.partial.is_empty() && cam.failed.is_empty());
+
+            // the speakerphone's configuration broke off after 100 bytes and a string request
+            // never completed
+            let spk = &devices[&DeviceAddress::new(1, 8)];
+            assert_eq!(spk.device.map(|device| device.id_product), Some(0x0867));
+            assert!(spk.root.children.is_empty());
+            assert_eq!(spk.partial.get(&0), Some(&speakerphone[..100].to_vec()));
+            assert_eq!(spk.failed.iter().map(|transfer| (transfer.setup.w_value, transfer.status)).collect::<Vec<_>>(),
+                       vec![(0x0200, Some(-71)), (0x0302, None)]);
+        }
+        assert!(read_capture(&read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin")).is_err());
+    }
+
+    #[test]
+    fn test_usbmon_malformed_config() {
+        // setup: a complete 12 byte configuration whose second descriptor has a bLength of 0
+        setup_logger();
+        let blob = vec![9, 2, 12, 0, 1, 1, 0, 0x80, 50, 0, 4, 0];
+        let transfer = ControlTransfer {
+            address: DeviceAddress::new(1, 9),
+            setup: SetupPacket::new(XferDir::ToHost, XferType::Std, Recip::Dev, 6, 0x0200, 0, 255),
+            ts_sec: 0,
+            ts_usec: 0,
+            status: Some(0),
+            data: blob.clone(),
+        };
+
+        // exercise
+        let devices = captured_descriptors(&[transfer]).unwrap();
+
+        // assert
+        let device = &devices[&DeviceAddress::new(1, 9)];
+        assert!(device.root.children.is_empty());
+        assert_eq!(device.partial.get(&0), Some(&blob));
+    }
+
+    #[test]
+    fn test_setup_packet_standard_requests() {
+        let cases = [
+            (SetupPacket::new(XferDir::ToHost, XferType::Std, Recip::Dev, 6, 0x0200, 0, 9), "GET_DESCRIPTOR CONFIGURATION 0, 9 bytes"),
+            (SetupPacket::new(XferDir::ToHost, XferType::Std, Recip::Dev, 6, 0x0302, 0x0409, 255), "GET_DESCRIPTOR STRING 2 language 0x0409, 255 bytes"),
+            (SetupPacket::new(XferDir::ToHost, XferType::Std, Recip::Iface, 6, 0x2200, 3, 74), "GET_DESCRIPTOR REPORT 0 of interface 3, 74 bytes"),
+            (SetupPacket::new(XferDir::ToDev, XferType::Std, Recip::Dev, 5, 7, 0, 0), "SET_ADDRESS 7"),
+            (SetupPacket::new(XferDir::ToDev, XferType::Std, Recip::Dev, 9, 1, 0, 0), "SET_CONFIGURATION 1"),
+            (SetupPacket::new(XferDir::ToDev, XferType::Std, Recip::Iface, 11, 2, 1, 0), "SET_INTERFACE interface 1 alt 2"),
+            (SetupPacket::new(XferDir::ToDev, XferType::Std, Recip::Ep, 1, 0, 0x81, 0), "CLEAR_FEATURE ENDPOINT_HALT on endpoint 0x81"),
+            (SetupPacket::new(XferDir::ToHost, XferType::Std, Recip::Dev, 0, 0, 0, 2), "GET_STATUS device"),
+            (SetupPacket::new(XferDir::ToHost, XferType::Vendor, Recip::Dev, 0x42, 1, 2, 4), "vendor request 0x42 to device: wValue 0x0001, wIndex 0x0002, wLength 4"),
+        ];
+        for (setup, expected) in cases {
+            assert_eq!(setup.to_string(), expected);
+            let mut bytes = vec![];
+            setup.serialize(&mut bytes);
+            assert_eq!(bytes.len(), 8);
+            assert_eq!(SetupPacket::deserialize(&mut &bytes[..]).unwrap(), setup);
+        }
+        let mut bytes = vec![];
+        SetupPacket::new(XferDir::ToHost, XferType::Std, Recip::Dev, 6, 0x0100, 0, 64).serialize(&mut bytes);
+        assert_eq!(bytes, [0x80, 0x06, 0x00, 0x01, 0x00, 0x00, 0x40, 0x00]);
+        assert_eq!(SetupPacket::deserialize(&mut &bytes[..]).unwrap().standard_request(), Some(StandardRequest::GetDescriptor));
+    }
+
+    #[test]
+    fn test_setup_packet_class_requests() {
+        let camera = TreeNode::deserialize(&mut &read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin")[..]).unwrap();
+        let speakerphone = TreeNode::deserialize(&mut &read_bin_file("0x0867_0x046d_MeetUp_Speakerphone_config_desc_0.bin")[..]).unwrap();
+        let class = |dir, recip, b_request, w_value, w_index, w_length| SetupPacket::new(dir, XferType::Class, recip, b_request, w_value, w_index, w_length);
+        let cases = [
+            (&camera, class(XferDir::ToDev, Recip::Iface, 0x01, 0x0200, 1, 26), "SET_CUR VS_COMMIT_CONTROL on interface 1"),
+            (&camera, class(XferDir::ToHost, Recip::Iface, 0x83, 0x0100, 1, 26), "GET_MAX VS_PROBE_CONTROL on interface 1"),
+            (&camera, class(XferDir::ToHost, Recip::Iface, 0x81, 0x0200, 0, 1), "GET_CUR VC_REQUEST_ERROR_CODE_CONTROL on interface 0"),
+            (&camera, class(XferDir::ToHost, Recip::Iface, 0x86, 0x0200, 0x0300, 1), "GET_INFO PU_BRIGHTNESS_CONTROL on processing unit 3"),
+            (&camera, class(XferDir::ToHost, Recip::Iface, 0x82, 0x0400, 0x0100, 4), "GET_MIN CT_EXPOSURE_TIME_ABSOLUTE_CONTROL on camera terminal 1"),
+            // extension unit controls are vendor defined
+            (&camera, class(XferDir::ToHost, Recip::Iface, 0x81, 0x0100, 0x0600, 4), "GET_CUR selector 0x01 on extension unit 6"),
+            (&camera, class(XferDir::ToHost, Recip::Iface, 0x82, 0x0200, 0x0502, 2), "GET_MIN volume on feature unit 5 channel 0"),
+            (&speakerphone, class(XferDir::ToDev, Recip::Iface, 0x01, 0x0100, 0x0600, 1), "SET_CUR mute on feature unit 6 channel 0"),
+            (&speakerphone, class(XferDir::ToHost, Recip::Iface, 0x82, 0x0202, 0x0200, 2), "GET_MIN volume on feature unit 2 channel 2"),
+            (&speakerphone, class(XferDir::ToDev, Recip::Ep, 0x01, 0x0100, 0x01, 3), "SET_CUR sampling frequency on endpoint 0x01"),
+            // interfaces the tree does not have are shown raw
+            (&speakerphone, class(XferDir::ToHost, Recip::Iface, 0x81, 0x0100, 7, 1), "class request 0x81 to interface 7: wValue 0x0100, wIndex 0x0007, wLength 1"),
+        ];
+        for (tree, setup, expected) in cases {
+            assert_eq!(setup.describe(tree), expected);
+        }
+        // standard requests read the same with or without a tree
+        let set_iface = SetupPacket::new(XferDir::ToDev, XferType::Std, Recip::Iface, 11, 2, 1, 0);
+        assert_eq!(set_iface.describe(&camera), set_iface.to_string());
+    }
+
+    #[test]
+    fn test_uac_feature_unit_controls() {
+        let speakerphone = TreeNode::deserialize(&mut &read_bin_file("0x0867_0x046d_MeetUp_Speakerphone_config_desc_0.bin")[..]).unwrap();
+        let controls: Vec<_> = FeatureUnitControl::find_all(&speakerphone).into_iter().map(|control| (control.unit_id, control.selector, control.channel)).collect();
+        assert_eq!(controls, vec![
+            (2, FeatureUnitControlSelectors::UacFuMute, 0),
+            (2, FeatureUnitControlSelectors::UacFuVolume, 0),
+            (6, FeatureUnitControlSelectors::UacFuMute, 0),
+            (6, FeatureUnitControlSelectors::UacFuVolume, 0),
+        ]);
+        let volume = FeatureUnitControl::find(&speakerphone, 2, FeatureUnitControlSelectors::UacFuVolume, 0).unwrap();
+        assert_eq!(volume.iface, 0);
+        assert!(FeatureUnitControl::find(&speakerphone, 2, FeatureUnitControlSelectors::UacFuBass, 0).is_none());
+
+        let get_min = volume.request(UacRequestCodes::GetMin);
+        assert_eq!(get_min, SetupPacket::new(XferDir::ToHost, XferType::Class, Recip::Iface, 0x82, 0x0200, 0x0200, 2));
+        assert_eq!(get_min.describe(&speakerphone), "GET_MIN volume on feature unit 2 channel 0");
+        let (set_cur, data) = volume.set_cur(&FeatureUnitValue::Volume(-0x0c00)).unwrap();
+        assert_eq!(set_cur, SetupPacket::new(XferDir::ToDev, XferType::Class, Recip::Iface, 0x01, 0x0200, 0x0200, 2));
+        assert_eq!(data, vec![0x00, 0xf4]);
+        assert_eq!(volume.decode(&data).unwrap(), FeatureUnitValue::Volume(-0x0c00));
+        assert!(volume.set_cur(&FeatureUnitValue::Mute(true)).is_err());
+        assert!(volume.decode(&[0x00]).is_err());
+        let range = volume.uac2_request(Uac2RequestCodes::Range, XferDir::ToHost, Uac2Range::<i16>::block_len(1));
+        assert_eq!(range, SetupPacket::new(XferDir::ToHost, XferType::Class, Recip::Iface, 0x02, 0x0200, 0x0200, 8));
+
+        let values = [
+            FeatureUnitValue::Mute(true),
+            FeatureUnitValue::Treble(-12),
+            FeatureUnitValue::GraphicEqualizer { bands: 0b1011, gains: vec![4, -4, 0] },
+            FeatureUnitValue::Delay(640),
+            FeatureUnitValue::Loudness(false),
+        ];
+        for value in values {
+            let mut data = vec![];
+            value.serialize(&mut data);
+            assert_eq!(FeatureUnitValue::deserialize(value.selector(), &data).unwrap(), value);
+        }
+        assert!(FeatureUnitValue::deserialize(FeatureUnitControlSelectors::UacFuGraphicEqualizer, &[0b11, 0, 0, 0, 1]).is_err());
+
+        let volume = UacVolume::from_responses(&[0x00, 0xc4], &[0x00, 0x00], &[0x00, 0xf4]).unwrap();
+        assert_eq!((volume.min(), volume.max(), volume.cur), (-0x3c00, 0, -0x0c00));
+        assert!(UacVolume::from_responses(&[0x00, 0x00], &[0x00, 0xc4], &[0x00, 0xf4]).is_err());
+    }
+
+    #[test]
+    fn test_uac_sampling_freq_and_ranges() {
+        let owl = TreeNode::deserialize(&mut &read_bin_file("0x0204_0x2e43_33_Meeting_Owl_Pro_config_desc_0.bin")[..]).unwrap();
+        let speakerphone = TreeNode::deserialize(&mut &read_bin_file("0x0867_0x046d_MeetUp_Speakerphone_config_desc_0.bin")[..]).unwrap();
+        assert_eq!(SamplingFreqControl::find_all(&owl), vec![SamplingFreqControl { ep: 0x01 }, SamplingFreqControl { ep: 0x83 }]);
+        // the speakerphone runs at a fixed rate
+        assert!(SamplingFreqControl::find_all(&speakerphone).is_empty());
+        let control = SamplingFreqControl { ep: 0x01 };
+        let (setup, data) = control.set_cur(48000);
+        assert_eq!(setup, SetupPacket::new(XferDir::ToDev, XferType::Class, Recip::Ep, 0x01, 0x0100, 0x01, 3));
+        assert_eq!(data, vec![0x80, 0xbb, 0x00]);
+        assert_eq!(SamplingFreqControl::decode(&data).unwrap(), 48000);
+        assert!(SamplingFreqControl::decode(&data[..2]).is_err());
+        assert_eq!(control.request(UacRequestCodes::GetCur).describe(&speakerphone), "GET_CUR sampling frequency on endpoint 0x01");
+
+        fn round_trip<T: crate::uac_control::RangeValue + PartialEq + std::fmt::Debug>(ranges: &[Uac2Range<T>]) {
+            let mut block = vec![];
+            Uac2Range::serialize_block(ranges, &mut block);
+            assert_eq!(block.len(), Uac2Range::<T>::block_len(ranges.len()) as usize);
+            assert_eq!(Uac2Range::<T>::deserialize_block(&block).unwrap(), ranges);
+        }
+        round_trip(&[Uac2Range { min: 0u8, max: 1, res: 1 }]);
+        round_trip(&[Uac2Range { min: -0x3c00i16, max: 0, res: 0x100 }]);
+        round_trip(&[Uac2Range { min: 44100u32, max: 44100, res: 0 }, Uac2Range { min: 48000, max: 96000, res: 48000 }]);
+        assert!(Uac2Range::<u32>::deserialize_block(&[2, 0, 0x44, 0xac, 0, 0]).is_err());
+        assert!(Uac2Range::<u8>::deserialize_block(&[1]).is_err());
+
+        let ranges = Uac2Range::<i16>::deserialize_block(&[1, 0, 0x00, 0xc4, 0x00, 0x00, 0x00, 0x01]).unwrap();
+        let volume = UacVolume::from_uac2_range(&ranges, -0x100).unwrap();
+        assert_eq!((volume.min(), volume.max(), volume.cur), (-0x3c00, 0, -0x100));
+        assert!(UacVolume::from_uac2_range(&[], 0).is_err());
+    }
+
+    #[test]
+    fn test_uvc_controls() {
+        let camera = TreeNode::deserialize(&mut &read_bin_file("0x046d_0x085b_7_Logitech_Webcam_C925e_config_desc_0.bin")[..]).unwrap();
+        let names = |tree: &TreeNode| -> Vec<(u8, u8, &str)> {
+            UvcControl::find_all(tree).into_iter().map(|control| (control.iface, control.unit_id, control.spec.name)).collect()
+        };
+        assert_eq!(names(&camera), vec![
+            (0, 1, "CT_AE_MODE_CONTROL"),
+            (0, 1, "CT_AE_PRIORITY_CONTROL"),
+            (0, 1, "CT_EXPOSURE_TIME_ABSOLUTE_CONTROL"),
+            (0, 1, "CT_FOCUS_ABSOLUTE_CONTROL"),
+            (0, 1, "CT_ZOOM_ABSOLUTE_CONTROL"),
+            (0, 1, "CT_PANTILT_ABSOLUTE_CONTROL"),
+            (0, 1, "CT_FOCUS_AUTO_CONTROL"),
+            (0, 3, "PU_BRIGHTNESS_CONTROL"),
+            (0, 3, "PU_CONTRAST_CONTROL"),
+            (0, 3, "PU_SATURATION_CONTROL"),
+            (0, 3, "PU_SHARPNESS_CONTROL"),
+            (0, 3, "PU_WHITE_BALANCE_TEMPERATURE_CONTROL"),
+            (0, 3, "PU_BACKLIGHT_COMPENSATION_CONTROL"),
+            (0, 3, "PU_GAIN_CONTROL"),
+            (0, 3, "PU_POWER_LINE_FREQUENCY_CONTROL"),
+            (0, 3, "PU_WHITE_BALANCE_TEMPERATURE_AUTO_CONTROL"),
+        ]);
+        // a UVC 1.5 processing unit with a 3 byte bmControls
+        let huddly = TreeNode::deserialize(&mut &read_bin_file("0x2bd9_0x0021_7_Huddly_IQ_config_desc_0.bin")[..]).unwrap();
+        assert_eq!(names(&huddly)[4..], [
+            (0, 3, "PU_BRIGHTNESS_CONTROL"),
+            (0, 3, "PU_SATURATION_CONTROL"),
+            (0, 3, "PU_GAIN_CONTROL"),
+            (0, 3, "PU_POWER_LINE_FREQUENCY_CONTROL"),
+        ]);
+        assert!(UvcControl::find(&camera, UvcControlSelector::Camera(UvcCtControlSelectors::PrivacyControl)).is_none());
+
+        let pantilt = UvcControl::find(&camera, UvcControlSelector::Camera(UvcCtControlSelectors::PantiltAbsoluteControl)).unwrap();
+        let get_max = pantilt.request(UvcRequestCodes::GetMax);
+        assert_eq!(get_max, SetupPacket::new(XferDir::ToHost, XferType::Class, Recip::Iface, 0x83, 0x0d00, 0x0100, 8));
+        assert_eq!(get_max.describe(&camera), "GET_MAX CT_PANTILT_ABSOLUTE_CONTROL on camera terminal 1");
+        assert_eq!(pantilt.request(UvcRequestCodes::GetInfo).w_length, 1);
+        assert_eq!(pantilt.request(UvcRequestCodes::GetLen).w_length, 2);
+        let (set_cur, data) = pantilt.set_cur(&[-36000, 7200]).unwrap();
+        assert_eq!(set_cur, SetupPacket::new(XferDir::ToDev, XferType::Class, Recip::Iface, 0x01, 0x0d00, 0x0100, 8));
+        assert_eq!(data, vec![0x60, 0x73, 0xff, 0xff, 0x20, 0x1c, 0x00, 0x00]);
+        assert_eq!(pantilt.decode(&data).unwrap(), vec![-36000, 7200]);
+        assert!(pantilt.decode(&data[..4]).is_err());
+        assert!(pantilt.set_cur(&[0]).is_err());
+
+        let brightness = UvcControl::find(&camera, UvcControlSelector::Processing(UvcPuControlSelectors::BrightnessControl)).unwrap();
+        assert_eq!(brightness.request(UvcRequestCodes::GetDef).w_index, 0x0300);
+        assert_eq!(brightness.decode(&[0xf6, 0xff]).unwrap(), vec![-10]);
+        assert!(brightness.set_cur(&[0x8000]).is_err());
+
+        // every selector is in the catalogue once, with its bit free within the entity
+        for spec in UVC_CONTROLS {
+            assert_eq!(UvcControlSpec::lookup(spec.selector), Some(spec));
+            assert_eq!(UVC_CONTROLS.iter().filter(|other| other.selector.kind() == spec.selector.kind() && other.bit == spec.bit).count(), 1);
+        }
+        let zoom = UvcControlSpec::lookup(UvcControlSelector::Camera(UvcCtControlSelectors::ZoomRelativeControl)).unwrap();
+        assert_eq!(zoom.w_length(), 3);
+        assert_eq!(zoom.encode(&[-1, 1, 4]).unwrap(), vec![0xff, 0x01, 0x04]);
+        assert!(zoom.encode(&[-1, 2, 4]).is_err());
+        let window = UvcControlSpec::lookup(UvcControlSelector::Camera(UvcCtControlSelectors::WindowControl)).unwrap();
+        assert_eq!(window.w_length(), 12);
+
+        let info = UvcControlInfo::decode(&[0x0b]).unwrap();
+        assert!(info.supports_get && info.supports_set && info.autoupdate && !info.disabled_by_auto && !info.asynchronous);
+        assert!(UvcControlInfo::decode(&[]).is_err());
+    }
+
+    #[test]
+    fn test_builder_4k_60fps_bit_rates() {
+        let mut root = ConfigBuilder::new()
+            .uvc_control(|c| c)
+            .uvc_streaming(|s| s.mjpeg().frame(3840, 2160, &[60, 30]))
+            .build()
+            .unwrap();
+        let frame = root.get_uvc_input_hdr().unwrap().children[0].children[0].parsed.clone();
+        match frame {
+            DescriptorTypes::DescriptorUvcFrameMjpeg(frame) => {
+                // 3840 * 2160 * 16 * 30 still fits, at 60fps the rate saturates
+                assert_eq!(frame.dw_min_bit_rate, 3_981_312_000);
+                assert_eq!(frame.dw_max_bit_rate, u32::MAX);
+                assert_eq!(frame.dw_max_video_frame_buffer_size, 3840 * 2160 * 2);
+                assert_eq!(frame.dw_frame_interval, vec![166666, 333333]);
+            }
+            other => panic!("expected an MJPEG frame, got {:?}", other),
+        }
     }
 }
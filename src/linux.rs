@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Error;
+
+use crate::TreeNode;
+use crate::usb_proto::{DescriptorDevice, DescriptorTypes, DeviceAddress, UsbDescriptorTypes};
+
+const DEVICE_DESCRIPTOR_LEN: usize = 18;
+
+/// Link speed as sysfs reports it in a device's `speed` attribute, in Mbit/s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UsbSpeed {
+    Low,
+    Full,
+    High,
+    Super,
+    SuperPlus,
+    SuperPlusX2,
+}
+
+impl UsbSpeed {
+    pub fn from_sysfs(speed: &str) -> Option<UsbSpeed> {
+        match speed.trim() {
+            "1.5" => Some(UsbSpeed::Low),
+            "12" => Some(UsbSpeed::Full),
+            "480" => Some(UsbSpeed::High),
+            "5000" => Some(UsbSpeed::Super),
+            "10000" => Some(UsbSpeed::SuperPlus),
+            "20000" => Some(UsbSpeed::SuperPlusX2),
+            _ => None,
+        }
+    }
+}
+
+/// One device below `/sys/bus/usb/devices`, read by `Sysfs`.
+#[derive(Debug, Clone)]
+pub struct SysfsDevice {
+    pub address: DeviceAddress,
+    /// Directory of the device, e.g. `/sys/bus/usb/devices/1-2`
+    pub path: PathBuf,
+    pub device: DescriptorDevice,
+    /// Every configuration of the device, as `TreeNode::deserialize` returns them
+    pub root: TreeNode,
+    /// Absent while the device is unconfigured
+    pub configuration_value: Option<u8>,
+    /// Absent when the kernel reports a speed this crate does not know
+    pub speed: Option<UsbSpeed>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial: Option<String>,
+}
+
+impl SysfsDevice {
+    /// The configuration selected by bConfigurationValue.
+    pub fn active_config(&self) -> Option<&TreeNode> {
+        let value = self.configuration_value?;
+        self.root.children.iter().find(|child| matches!(&child.parsed, DescriptorTypes::Config(cfg) if cfg.b_configuration_value == value))
+    }
+}
+
+/// Reads USB devices from sysfs instead of opening them, so descriptors are available without
+/// access to the device nodes.
+#[derive(Debug, Clone)]
+pub struct Sysfs {
+    root: PathBuf,
+}
+
+impl Default for Sysfs {
+    fn default() -> Self {
+        Sysfs::new("/sys")
+    }
+}
+
+impl Sysfs {
+    /// `root` is where sysfs is mounted; tests point it at a directory laid out the same way.
+    pub fn new(root: impl Into<PathBuf>) -> Sysfs {
+        Sysfs { root: root.into() }
+    }
+
+    pub fn devices_dir(&self) -> PathBuf {
+        self.root.join("bus/usb/devices")
+    }
+
+    /// Every device in `bus/usb/devices`, root hubs included. Interface directories (`1-2:1.0`)
+    /// are skipped; a device that can not be read is logged and left out, since devices can
+    /// disappear while they are enumerated.
+    pub fn devices(&self) -> Result<HashMap<DeviceAddress, SysfsDevice>, Error> {
+        let dir = self.devices_dir();
+        let entries = fs::read_dir(&dir).map_err(|e| anyhow!("Could not list {}: {}", dir.display(), e))?;
+        let mut devices = HashMap::new();
+        for entry in entries {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if name.contains(':') {
+                continue;
+            }
+            match self.device(&name) {
+                Ok(device) => { devices.insert(device.address, device); }
+                Err(e) => warn!("Skipping USB device {}: {}", name, e),
+            }
+        }
+        Ok(devices)
+    }
+
+    /// Reads one device by its sysfs name, e.g. `1-2.3` or `usb1`.
+    pub fn device(&self, name: &str) -> Result<SysfsDevice, Error> {
+        let path = self.devices_dir().join(name);
+        let address = DeviceAddress::new(read_num(&path, "busnum")?, read_num(&path, "devnum")?);
+        let descriptors = fs::read(path.join("descriptors"))
+            .map_err(|e| anyhow!("Could not read {}: {}", path.join("descriptors").display(), e))?;
+        let (device, root) = parse_descriptors(&descriptors)?;
+        let configuration_value = if read_attr(&path, "bConfigurationValue")?.is_empty() {
+            None
+        } else {
+            Some(read_num(&path, "bConfigurationValue")?)
+        };
+        let speed = read_attr(&path, "speed").ok().and_then(|speed| UsbSpeed::from_sysfs(&speed));
+        Ok(SysfsDevice {
+            address,
+            device,
+            root,
+            configuration_value,
+            speed,
+            manufacturer: read_attr(&path, "manufacturer").ok(),
+            product: read_attr(&path, "product").ok(),
+            serial: read_attr(&path, "serial").ok(),
+            path,
+        })
+    }
+}
+
+/// Parses the descriptors of a device as sysfs' `descriptors` attribute and usbfs' device nodes
+/// (`/dev/bus/usb/BBB/DDD`) return them: the device descriptor followed by every configuration.
+pub fn parse_descriptors(bytes: &[u8]) -> Result<(DescriptorDevice, TreeNode), Error> {
+    if bytes.len() < DEVICE_DESCRIPTOR_LEN || bytes[0] as usize != DEVICE_DESCRIPTOR_LEN || bytes[1] != UsbDescriptorTypes::Device as u8 {
+        return Err(anyhow!("Descriptors do not start with a device descriptor"));
+    }
+    let device = DescriptorDevice::deserialize(&mut &bytes[2..DEVICE_DESCRIPTOR_LEN])?;
+    let root = TreeNode::deserialize(&mut &bytes[DEVICE_DESCRIPTOR_LEN..])
+        .map_err(|e| anyhow!("Could not parse the configurations of {:04x}:{:04x}: {}", device.id_vendor, device.id_product, e))?;
+    Ok((device, root))
+}
+
+/// An attribute's value without the trailing newline.
+fn read_attr(path: &Path, name: &str) -> Result<String, Error> {
+    let value = fs::read_to_string(path.join(name))
+        .map_err(|e| anyhow!("Could not read {}: {}", path.join(name).display(), e))?;
+    Ok(value.trim_end().to_string())
+}
+
+fn read_num(path: &Path, name: &str) -> Result<u8, Error> {
+    let value = read_attr(path, name)?;
+    value.parse().map_err(|_| anyhow!("Invalid {} {:?} in {}", name, value, path.display()))
+}
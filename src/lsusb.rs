@@ -0,0 +1,897 @@
+use std::fmt;
+use std::fmt::Write;
+
+use anyhow::Error;
+use libusb1_sys::constants::{LIBUSB_CLASS_AUDIO, LIBUSB_CLASS_HID, LIBUSB_CLASS_VIDEO};
+
+use crate::TreeNode;
+use crate::uac_proto::UacInterfaceSubclass;
+use crate::usb_proto::{DescriptorDevice, DescriptorTypes, UsbDescriptorTypes};
+use crate::uvc_proto::UvcInterfaceSubClass;
+
+/// Column at which most `lsusb -v` values end; VideoStreaming and DFU descriptors use `WIDE`.
+const NARROW: usize = 25;
+const WIDE: usize = 37;
+const CAMERA: usize = 31;
+
+// indentation of the "Xxx Descriptor:" line for each level, fields are indented two more
+const CONFIG: usize = 2;
+const INTERFACE: usize = 4;
+const CLASS: usize = 6;
+const ENDPOINT_CLASS: usize = 8;
+
+/// Renders a tree in the layout of `lsusb -v`, see `TreeNode::lsusb`.
+pub struct Lsusb<'a>(&'a TreeNode);
+
+impl TreeNode {
+    /// Formats the tree the way `lsusb -v` prints descriptors, so it can be compared with logs
+    /// taken on a customer's machine. String descriptors are not available and are left empty.
+    pub fn lsusb(&self) -> Lsusb<'_> {
+        Lsusb(self)
+    }
+}
+
+impl fmt::Display for Lsusb<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut renderer = Renderer { out: String::new(), class: 0, subclass: 0, unknown_indent: INTERFACE };
+        renderer.node(self.0)?;
+        f.write_str(&renderer.out)
+    }
+}
+
+/// A device read back from `lsusb -v` output by `parse_lsusb`.
+#[derive(Debug, Clone)]
+pub struct LsusbDevice {
+    /// Absent when the text starts at the configuration descriptor
+    pub device: Option<DescriptorDevice>,
+    /// The configurations, as `TreeNode::deserialize` returns them
+    pub root: TreeNode,
+}
+
+/// Reconstructs the descriptors of one device from `lsusb -v` output, e.g. a field report.
+///
+/// lsusb prints every descriptor field in wire order and names it with its type prefix (`b`, `w`,
+/// `dw`, `bcd`, `guid`, ...), so each block is encoded back to bytes and the result goes through
+/// `TreeNode::deserialize`. `** UNRECOGNIZED:` dumps are taken as they are. Decoded flags, string
+/// descriptors, the BOS and device status are ignored, and wTotalLength is recomputed from the
+/// descriptors that were found.
+pub fn parse_lsusb(text: &str) -> Result<LsusbDevice, Error> {
+    let mut blocks: Vec<Block> = vec![];
+    let mut bcd_usb = 0u16;
+    for (idx, line) in text.lines().enumerate() {
+        let indent = line.len() - line.trim_start().len();
+        let content = line.trim();
+        if let Some(hex) = content.strip_prefix("** UNRECOGNIZED:") {
+            let bytes = hex.split_whitespace()
+                .map(|byte| u8::from_str_radix(byte, 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| anyhow!("Line {}: invalid hex dump", idx + 1))?;
+            blocks.push(Block { line: idx + 1, field_indent: Some(indent), bytes, control_size: None });
+            continue;
+        }
+        if content.ends_with(':') && !content.starts_with("**") {
+            blocks.push(Block { line: idx + 1, field_indent: None, bytes: vec![], control_size: None });
+            continue;
+        }
+        let Some(block) = blocks.last_mut() else { continue };
+        if matches!(block.field_indent, Some(field_indent) if field_indent != indent) {
+            continue; // decoded flags are indented below their field
+        }
+        if let Some((name, value)) = split_field(content) {
+            if block.push_field(name, value, bcd_usb) {
+                block.field_indent = Some(indent);
+                if name == "bcdUSB" {
+                    bcd_usb = parse_bcd(value).unwrap_or_default() as u16;
+                }
+            }
+        }
+    }
+
+    let mut device = None;
+    let mut configs: Vec<Vec<u8>> = vec![];
+    for block in blocks.into_iter().filter(|block| !block.bytes.is_empty()) {
+        let line = block.line;
+        let bytes = block.finish()?;
+        match bytes[1] {
+            t if t == UsbDescriptorTypes::Device as u8 => device = Some(DescriptorDevice::deserialize(&mut &bytes[2..])?),
+            t if t == UsbDescriptorTypes::Config as u8 => configs.push(bytes),
+            // device qualifier, other speed configuration, BOS, device capability, hub
+            0x06 | 0x07 | 0x0f | 0x10 | 0x29 | 0x2a => {}
+            _ => match configs.last_mut() {
+                Some(config) => config.extend(bytes),
+                None => warn!("Line {}: descriptor outside of a configuration ignored", line),
+            },
+        }
+    }
+    if configs.is_empty() {
+        return Err(anyhow!("No configuration descriptor found in lsusb output"));
+    }
+
+    let mut buffer = vec![];
+    for mut config in configs {
+        let total_length = config.len() as u16;
+        let listed = u16::from_le_bytes([config[2], config[3]]);
+        if listed != total_length {
+            warn!("wTotalLength {} does not match the {} bytes of descriptors listed", listed, total_length);
+            config[2..4].copy_from_slice(&total_length.to_le_bytes());
+        }
+        buffer.extend(config);
+    }
+    let root = TreeNode::deserialize(&mut &buffer[..])?;
+    Ok(LsusbDevice { device, root })
+}
+
+/// The fields of one lsusb block, encoded as they were read.
+struct Block {
+    line: usize,
+    /// Indentation of the block's fields, known after the first one
+    field_indent: Option<usize>,
+    bytes: Vec<u8>,
+    /// bControlSize, which sizes bmControls and bmaControls
+    control_size: Option<usize>,
+}
+
+impl Block {
+    /// Appends a field, returning false for lines that are not one.
+    fn push_field(&mut self, name: &str, value: &str, bcd_usb: u16) -> bool {
+        let base = name.split(['(', '[']).next().unwrap_or(name);
+        let indexed = base.len() != name.len();
+        if base.starts_with("guid") {
+            return match parse_guid(value) {
+                Some(guid) => {
+                    self.bytes.extend(guid);
+                    true
+                }
+                None => false,
+            };
+        }
+        let (size, number) = match base {
+            // lsusb prints MaxPower in mA, the descriptor counts 2mA units (8mA for SuperSpeed)
+            "MaxPower" => (1, value.trim_end_matches("mA").parse::<u64>().ok().map(|ma| ma / if bcd_usb >= 0x0300 { 8 } else { 2 })),
+            "dwClockFrequency" => (4, value.strip_suffix("MHz")
+                .map(|mhz| mhz.parse::<f64>().ok().map(|mhz| (mhz * 1_000_000.0).round() as u64))
+                .unwrap_or_else(|| parse_number(value))),
+            _ if base.starts_with("bcd") => (2, parse_bcd(value)),
+            _ if base.starts_with("dw") => (4, parse_number(value)),
+            "bmControls" | "bmaControls" if !(indexed && base == "bmControls") =>
+                (self.control_size.unwrap_or_else(|| hex_width(value)), parse_number(value)),
+            _ if base.starts_with("bm") => (hex_width(value), parse_number(value)),
+            _ if base.starts_with('w') || base.starts_with("id") => (2, parse_number(value)),
+            _ if base.starts_with('t') => (3, parse_number(value)),
+            _ if base.starts_with('b') || base.starts_with('i') => (1, parse_number(value)),
+            _ => return false,
+        };
+        let Some(number) = number else { return false };
+        if base == "bControlSize" {
+            self.control_size = Some(number as usize);
+        }
+        self.bytes.extend(&number.to_le_bytes()[..size.min(8)]);
+        self.bytes.resize(self.bytes.len() + size.saturating_sub(8), 0);
+        true
+    }
+
+    /// The descriptor's bytes, cut or padded to its bLength so the following ones stay aligned.
+    fn finish(mut self) -> Result<Vec<u8>, Error> {
+        let length = self.bytes[0] as usize;
+        if length < 2 || self.bytes.len() < 2 {
+            return Err(anyhow!("Line {}: invalid descriptor of {} bytes", self.line, length));
+        }
+        if self.bytes.len() != length {
+            warn!("Line {}: bLength is {} but {} bytes of fields were listed", self.line, length, self.bytes.len());
+            self.bytes.resize(length, 0);
+        }
+        Ok(self.bytes)
+    }
+}
+
+/// Splits `baInterfaceNr( 0)       1` into its name and the first word of its value.
+fn split_field(content: &str) -> Option<(&str, &str)> {
+    let mut end = content.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(content.len());
+    if content[end..].starts_with(['(', '[']) {
+        end += content[end..].find([')', ']'])? + 1;
+    }
+    let value = content[end..].split_whitespace().next()?;
+    Some((&content[..end], value))
+}
+
+fn parse_number(value: &str) -> Option<u64> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// `2.00` is 0x0200; lsusb prints both halves in hex.
+fn parse_bcd(value: &str) -> Option<u64> {
+    let (major, minor) = value.split_once('.')?;
+    Some(u64::from_str_radix(major, 16).ok()? << 8 | u64::from_str_radix(minor, 16).ok()?)
+}
+
+/// Bytes of a field printed as `0x` followed by two digits per byte, one otherwise.
+#[allow(clippy::manual_div_ceil)] // usize::div_ceil needs Rust 1.73
+fn hex_width(value: &str) -> usize {
+    value.strip_prefix("0x").map(|hex| (hex.len() + 1) / 2).unwrap_or(1).max(1)
+}
+
+/// The inverse of `guid_string`.
+fn parse_guid(value: &str) -> Option<Vec<u8>> {
+    let hex: String = value.trim_start_matches('{').trim_end_matches('}').split('-').collect();
+    if hex.len() != 32 {
+        return None;
+    }
+    let mut raw = (0..16).map(|idx| u8::from_str_radix(&hex[idx * 2..idx * 2 + 2], 16).ok()).collect::<Option<Vec<u8>>>()?;
+    raw[0..4].reverse();
+    raw[4..6].reverse();
+    raw[6..8].reverse();
+    Some(raw)
+}
+
+struct Renderer {
+    out: String,
+    /// class and subclass of the interface being rendered, class-specific descriptors depend on them
+    class: u8,
+    subclass: u8,
+    /// where unrecognized descriptors go: below the configuration, interface or endpoint they follow
+    unknown_indent: usize,
+}
+
+impl Renderer {
+    fn node(&mut self, node: &TreeNode) -> fmt::Result {
+        let mut bytes = vec![];
+        TreeNode { children: vec![], parsed: node.parsed.clone(), extra: node.extra.clone() }.serialize(&mut bytes).map_err(|_| fmt::Error)?;
+        self.descriptor(&node.parsed, &bytes)?;
+        node.children.iter().try_for_each(|child| self.node(child))
+    }
+
+    fn descriptor(&mut self, parsed: &DescriptorTypes, bytes: &[u8]) -> fmt::Result {
+        match parsed {
+            DescriptorTypes::Root() => Ok(()),
+            DescriptorTypes::Device(dev) => {
+                self.header(0, "Device Descriptor:", bytes)?;
+                let i = 2;
+                self.field(i, "bcdUSB", bcd(dev.bcd_usb), NARROW)?;
+                self.named(i, "bDeviceClass", dev.b_device_class, NARROW, device_class_name(dev.b_device_class))?;
+                self.named(i, "bDeviceSubClass", dev.b_device_sub_class, NARROW, "")?;
+                let protocol = if dev.b_device_class == 0xef && dev.b_device_sub_class == 2 && dev.b_device_protocol == 1 { "Interface Association" } else { "" };
+                self.named(i, "bDeviceProtocol", dev.b_device_protocol, NARROW, protocol)?;
+                self.field(i, "bMaxPacketSize0", dev.b_max_packet_size0, NARROW)?;
+                self.named(i, "idVendor", format!("{:#06x}", dev.id_vendor), NARROW, "")?;
+                self.named(i, "idProduct", format!("{:#06x}", dev.id_product), NARROW, "")?;
+                self.field(i, "bcdDevice", bcd(dev.bcd_device), NARROW)?;
+                self.named(i, "iManufacturer", dev.i_manufacturer, NARROW, "")?;
+                self.named(i, "iProduct", dev.i_product, NARROW, "")?;
+                self.named(i, "iSerial", dev.i_serial_number, NARROW, "")?;
+                self.field(i, "bNumConfigurations", dev.b_num_configurations, NARROW)
+            }
+            DescriptorTypes::Config(conf) => {
+                self.unknown_indent = INTERFACE;
+                let i = self.header(CONFIG, "Configuration Descriptor:", bytes)?;
+                self.field(i, "wTotalLength", format!("{:#06x}", conf.w_total_length), NARROW)?;
+                self.field(i, "bNumInterfaces", conf.b_num_interfaces, NARROW)?;
+                self.field(i, "bConfigurationValue", conf.b_configuration_value, NARROW)?;
+                self.named(i, "iConfiguration", conf.i_configuration, NARROW, "")?;
+                self.field(i, "bmAttributes", format!("{:#04x}", conf.bm_attributes), NARROW)?;
+                if conf.bm_attributes & 0x80 == 0 {
+                    self.line(i + 2, "(Missing must-be-set bit!)")?;
+                }
+                self.line(i + 2, if conf.bm_attributes & 0x40 != 0 { "Self Powered" } else { "(Bus Powered)" })?;
+                if conf.bm_attributes & 0x20 != 0 {
+                    self.line(i + 2, "Remote Wakeup")?;
+                }
+                if conf.bm_attributes & 0x10 != 0 {
+                    self.line(i + 2, "Battery Powered")?;
+                }
+                self.field(i, "MaxPower", format!("{}mA", conf.b_max_power as u32 * 2), NARROW + 2)
+            }
+            DescriptorTypes::InterfaceAssociation(assoc) => {
+                let i = self.header(INTERFACE, "Interface Association:", bytes)?;
+                self.field(i, "bFirstInterface", assoc.b_first_interface, NARROW)?;
+                self.field(i, "bInterfaceCount", assoc.b_interface_count, NARROW)?;
+                let (class, subclass, protocol) = class_names(assoc.b_function_class, assoc.b_function_sub_class, assoc.b_function_protocol);
+                self.named(i, "bFunctionClass", assoc.b_function_class, NARROW, class)?;
+                self.named(i, "bFunctionSubClass", assoc.b_function_sub_class, NARROW, subclass)?;
+                self.named(i, "bFunctionProtocol", assoc.b_function_protocol, NARROW, protocol)?;
+                self.named(i, "iFunction", assoc.i_function, NARROW, "")
+            }
+            DescriptorTypes::Interface(iface) => {
+                self.unknown_indent = CLASS;
+                self.class = iface.b_interface_class;
+                self.subclass = iface.b_interface_sub_class;
+                let i = self.header(INTERFACE, "Interface Descriptor:", bytes)?;
+                self.field(i, "bInterfaceNumber", iface.b_interface_number, NARROW)?;
+                self.field(i, "bAlternateSetting", iface.b_alternate_setting, NARROW)?;
+                self.field(i, "bNumEndpoints", iface.b_num_endpoints, NARROW)?;
+                let (class, subclass, protocol) = class_names(iface.b_interface_class, iface.b_interface_sub_class, iface.b_interface_protocol);
+                self.named(i, "bInterfaceClass", iface.b_interface_class, NARROW, class)?;
+                self.named(i, "bInterfaceSubClass", iface.b_interface_sub_class, NARROW, subclass)?;
+                self.named(i, "bInterfaceProtocol", iface.b_interface_protocol, NARROW, protocol)?;
+                self.named(i, "iInterface", iface.i_interface, NARROW, "")
+            }
+            DescriptorTypes::Endpoint(ep) | DescriptorTypes::HidEndpoint(ep) => {
+                let i = self.endpoint(bytes, ep.b_endpoint_address, ep.bm_attributes, ep.w_max_packet_size)?;
+                self.field(i, "bInterval", ep.b_interval, NARROW)
+            }
+            DescriptorTypes::UacEndpoint(ep) => {
+                let i = self.endpoint(bytes, ep.b_endpoint_address, ep.bm_attributes, ep.w_max_packet_size)?;
+                self.field(i, "bInterval", ep.b_interval, NARROW)?;
+                self.field(i, "bRefresh", ep.b_refresh, NARROW)?;
+                self.field(i, "bSynchAddress", ep.b_synch_address, NARROW)
+            }
+            DescriptorTypes::SsEpComp(comp) => {
+                let i = self.header(ENDPOINT_CLASS, "SuperSpeed Endpoint Companion Descriptor:", bytes)?;
+                self.field(i, "bMaxBurst", comp.b_max_burst, NARROW)?;
+                self.field(i, "bmAttributes", format!("{:#04x}", comp.bm_attributes), NARROW)?;
+                self.field(i, "wBytesPerInterval", format!("{:#06x}", comp.w_bytes_per_interval), NARROW)
+            }
+            DescriptorTypes::SspIsochEpComp(comp) => {
+                let i = self.header(ENDPOINT_CLASS, "SuperSpeedPlus Isochronous Endpoint Companion Descriptor:", bytes)?;
+                self.field(i, "dwBytesPerInterval", format!("{:#010x}", comp.dw_bytes_per_interval), NARROW + 4)
+            }
+            DescriptorTypes::CsDevice(_) if self.class == LIBUSB_CLASS_HID && bytes.len() >= 9 => self.hid(bytes),
+            DescriptorTypes::DfuFunctional(dfu) => {
+                let i = self.header(CLASS, "Device Firmware Upgrade Interface Descriptor:", bytes)?;
+                self.field(i, "bmAttributes", dfu.bm_attributes, WIDE)?;
+                self.line(i + 2, if dfu.bm_attributes & 0x08 != 0 { "Will Detach" } else { "Will Not Detach" })?;
+                self.line(i + 2, if dfu.bm_attributes & 0x04 != 0 { "Manifestation Tolerant" } else { "Manifestation Intolerant" })?;
+                self.line(i + 2, if dfu.bm_attributes & 0x02 != 0 { "Upload Supported" } else { "Upload Unsupported" })?;
+                self.line(i + 2, if dfu.bm_attributes & 0x01 != 0 { "Download Supported" } else { "Download Unsupported" })?;
+                self.named(i, "wDetachTimeout", dfu.w_detach_time_out, WIDE, "milliseconds")?;
+                self.named(i, "wTransferSize", dfu.w_transfer_size, WIDE, "bytes")?;
+                match dfu.bcd_dfu_version {
+                    Some(version) => self.field(i, "bcdDFUVersion", bcd(version), WIDE),
+                    None => Ok(()),
+                }
+            }
+            DescriptorTypes::CsEndpoint(_) if self.class == LIBUSB_CLASS_VIDEO && bytes.len() >= 5 && bytes[2] == 3 => {
+                let i = self.header(ENDPOINT_CLASS, "VideoControl Endpoint Descriptor:", bytes)?;
+                self.subtype(i, bytes[2], "EP_INTERRUPT", NARROW)?;
+                self.field(i, "wMaxTransferSize", u16::from_le_bytes([bytes[3], bytes[4]]), NARROW)
+            }
+            DescriptorTypes::UacIsoEndpointDescriptor(ep) => {
+                let i = self.header(ENDPOINT_CLASS, "AudioStreaming Endpoint Descriptor:", bytes)?;
+                self.subtype(i, ep.b_descriptor_subtype, "EP_GENERAL", NARROW)?;
+                self.field(i, "bmAttributes", format!("{:#04x}", ep.bm_attributes), NARROW)?;
+                self.flags(i + 2, ep.bm_attributes as u32, &[(0, "Sampling Frequency"), (1, "Pitch"), (7, "MaxPacketsOnly")])?;
+                let units = ["Undefined", "Milliseconds", "Decoded PCM samples"].get(ep.b_lock_delay_units as usize).copied().unwrap_or("Reserved");
+                self.named(i, "bLockDelayUnits", ep.b_lock_delay_units, NARROW, units)?;
+                self.field(i, "wLockDelay", format!("{:#06x}", ep.w_lock_delay), NARROW)
+            }
+            DescriptorTypes::UvcHeaderDescriptor(hdr) => {
+                let i = self.vc_header(bytes, "HEADER")?;
+                self.field(i, "bcdUVC", bcd(hdr.bcd_uvc), NARROW)?;
+                self.field(i, "wTotalLength", format!("{:#06x}", hdr.w_total_length), NARROW)?;
+                let clock = hdr.dw_clock_frequency;
+                self.field(i, "dwClockFrequency", clock / 1_000_000, NARROW)?;
+                self.out.pop();
+                writeln!(self.out, ".{:06}MHz", clock % 1_000_000)?;
+                self.field(i, "bInCollection", hdr.b_in_collection, NARROW)?;
+                hdr.ba_interface_nr.iter().enumerate()
+                    .try_for_each(|(idx, nr)| self.field(i, &format!("baInterfaceNr({:2})", idx), nr, NARROW))
+            }
+            DescriptorTypes::UvcVcInputTerminal(term) => {
+                let i = self.vc_header(bytes, "INPUT_TERMINAL")?;
+                self.field(i, "bTerminalID", term.b_terminal_id, NARROW)?;
+                self.named(i, "wTerminalType", format!("{:#06x}", term.w_terminal_type), NARROW, video_terminal_name(term.w_terminal_type))?;
+                self.field(i, "bAssocTerminal", term.b_assoc_terminal, NARROW)?;
+                self.named(i, "iTerminal", term.i_terminal, NARROW, "")?;
+                let xtra = &term.xtra;
+                if term.w_terminal_type == 0x0201 && xtra.len() >= 7 {
+                    self.field(i, "wObjectiveFocalLengthMin", u16::from_le_bytes([xtra[0], xtra[1]]), CAMERA)?;
+                    self.field(i, "wObjectiveFocalLengthMax", u16::from_le_bytes([xtra[2], xtra[3]]), CAMERA)?;
+                    self.field(i, "wOcularFocalLength", u16::from_le_bytes([xtra[4], xtra[5]]), CAMERA)?;
+                    self.field(i, "bControlSize", xtra[6], CAMERA)?;
+                    let controls = le_bits(&xtra[7..(7 + xtra[6] as usize).min(xtra.len())]);
+                    self.field(i, "bmControls", format!("{:#010x}", controls), CAMERA)?;
+                    self.bit_names(i + 2, controls, CAMERA_CONTROLS)?;
+                }
+                Ok(())
+            }
+            DescriptorTypes::UvcVcOutputTerminal(term) => {
+                let i = self.vc_header(bytes, "OUTPUT_TERMINAL")?;
+                self.field(i, "bTerminalID", term.b_terminal_id, NARROW)?;
+                self.named(i, "wTerminalType", format!("{:#06x}", term.w_terminal_type), NARROW, video_terminal_name(term.w_terminal_type))?;
+                self.field(i, "bAssocTerminal", term.b_assoc_terminal, NARROW)?;
+                self.field(i, "bSourceID", term.b_source_id, NARROW)?;
+                self.named(i, "iTerminal", term.i_terminal, NARROW, "")
+            }
+            DescriptorTypes::UvcVcProcessingUnit(unit) => {
+                let i = self.vc_header(bytes, "PROCESSING_UNIT")?;
+                self.field(i, "bUnitID", unit.b_unit_id, NARROW)?;
+                self.field(i, "bSourceID", unit.b_source_id, NARROW)?;
+                self.field(i, "wMaxMultiplier", unit.w_max_multiplier, NARROW)?;
+                self.field(i, "bControlSize", unit.b_control_size, NARROW)?;
+                self.field(i, "bmControls", format!("{:#010x}", unit.bm_controls), NARROW)?;
+                self.bit_names(i + 2, unit.bm_controls as u32, PROCESSING_CONTROLS)?;
+                self.named(i, "iProcessing", unit.i_processing, NARROW, "")?;
+                if let Some(standards) = unit.xtra.first() {
+                    self.field(i, "bmVideoStandards", format!("{:#04x}", standards), NARROW)?;
+                    self.bit_names(i + 2, *standards as u32, VIDEO_STANDARDS)?;
+                }
+                Ok(())
+            }
+            DescriptorTypes::UvcVcExtensionUnit(unit) => {
+                let i = self.vc_header(bytes, "EXTENSION_UNIT")?;
+                self.field(i, "bUnitID", unit.b_unit_id, NARROW)?;
+                self.guid(i, "guidExtensionCode", bytes.get(4..20), NARROW + 1)?;
+                self.field(i, "bNumControls", unit.b_num_controls, NARROW)?;
+                self.field(i, "bNrInPins", unit.b_nr_in_pins, NARROW)?;
+                unit.ba_source_id.iter().enumerate()
+                    .try_for_each(|(idx, id)| self.field(i, &format!("baSourceID({:2})", idx), id, NARROW))?;
+                self.field(i, "bControlSize", unit.b_control_size, NARROW)?;
+                unit.bm_controls.iter().enumerate()
+                    .try_for_each(|(idx, ctrl)| self.field(i, &format!("bmControls({:2})", idx), format!("{:#04x}", ctrl), NARROW))?;
+                self.named(i, "iExtension", unit.i_extension, NARROW, "")
+            }
+            DescriptorTypes::UvcInputHeader(hdr) => {
+                let i = self.vs_header(bytes, "INPUT_HEADER")?;
+                let num_formats = bytes.get(3).copied().unwrap_or_default();
+                self.field(i, "bNumFormats", num_formats, WIDE)?;
+                self.field(i, "wTotalLength", format!("{:#06x}", hdr.w_total_length), WIDE)?;
+                self.field(i, "bEndpointAddress", format!("{:#04x}", hdr.b_endpoint_address), WIDE)?;
+                self.out.pop();
+                writeln!(self.out, "  EP {} {}", hdr.b_endpoint_address & 0x0f, if hdr.b_endpoint_address & 0x80 != 0 { "IN" } else { "OUT" })?;
+                self.field(i, "bmInfo", hdr.bm_info, WIDE)?;
+                self.field(i, "bTerminalLink", hdr.b_terminal_link, WIDE)?;
+                self.field(i, "bStillCaptureMethod", hdr.b_still_capture_method, WIDE)?;
+                self.field(i, "bTriggerSupport", hdr.b_trigger_support, WIDE)?;
+                self.field(i, "bTriggerUsage", hdr.b_trigger_usage, WIDE)?;
+                self.field(i, "bControlSize", hdr.b_control_size, WIDE)?;
+                let size = (hdr.b_control_size as usize).max(1);
+                for (idx, controls) in hdr.bma_controls.chunks(size).enumerate() {
+                    let controls = le_bits(controls);
+                    self.field(i, &format!("bmaControls({:2})", idx), controls, WIDE)?;
+                    self.bit_names(i + 2, controls, VS_CONTROLS)?;
+                }
+                Ok(())
+            }
+            DescriptorTypes::DescriptorUvcFormatUncompressed(fmt) => {
+                let i = self.vs_header(bytes, "FORMAT_UNCOMPRESSED")?;
+                self.field(i, "bFormatIndex", fmt.b_format_index, WIDE)?;
+                self.field(i, "bNumFrameDescriptors", fmt.b_num_frame_descriptors, WIDE)?;
+                self.guid(i, "guidFormat", bytes.get(5..21), WIDE + 1)?;
+                self.field(i, "bBitsPerPixel", fmt.b_bits_per_pixel, WIDE)?;
+                self.field(i, "bDefaultFrameIndex", fmt.b_default_frame_index, WIDE)?;
+                self.field(i, "bAspectRatioX", fmt.b_aspect_ratio_x, WIDE)?;
+                self.field(i, "bAspectRatioY", fmt.b_aspect_ratio_y, WIDE)?;
+                self.interlace(i, fmt.bm_interface_flags)?;
+                self.field(i, "bCopyProtect", fmt.b_copy_protect, WIDE)
+            }
+            DescriptorTypes::UvcFormatFrameBased(fmt) => {
+                let i = self.vs_header(bytes, "FORMAT_FRAME_BASED")?;
+                self.field(i, "bFormatIndex", fmt.b_format_index, WIDE)?;
+                self.field(i, "bNumFrameDescriptors", fmt.b_num_frame_descriptors, WIDE)?;
+                self.guid(i, "guidFormat", bytes.get(5..21), WIDE + 1)?;
+                self.field(i, "bBitsPerPixel", fmt.b_bits_per_pixel, WIDE)?;
+                self.field(i, "bDefaultFrameIndex", fmt.b_default_frame_index, WIDE)?;
+                self.field(i, "bAspectRatioX", fmt.b_aspect_ratio_x, WIDE)?;
+                self.field(i, "bAspectRatioY", fmt.b_aspect_ratio_y, WIDE)?;
+                self.interlace(i, fmt.bm_interface_flags)?;
+                self.field(i, "bCopyProtect", fmt.b_copy_protect, WIDE)?;
+                self.field(i, "bVariableSize", fmt.b_variable_size, WIDE)
+            }
+            DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) => {
+                let i = self.vs_header(bytes, "FORMAT_MJPEG")?;
+                self.field(i, "bFormatIndex", fmt.b_format_index, WIDE)?;
+                self.field(i, "bNumFrameDescriptors", fmt.b_num_frame_descriptors, WIDE)?;
+                self.field(i, "bFlags", fmt.bm_flags, WIDE)?;
+                self.line(i + 2, &format!("Fixed-size samples: {}", yes_no(fmt.bm_flags & 0x01 != 0)))?;
+                self.field(i, "bDefaultFrameIndex", fmt.b_default_frame_index, WIDE)?;
+                self.field(i, "bAspectRatioX", fmt.b_aspect_ratio_x, WIDE)?;
+                self.field(i, "bAspectRatioY", fmt.b_aspect_ratio_y, WIDE)?;
+                self.interlace(i, fmt.bm_interface_flags)?;
+                self.field(i, "bCopyProtect", fmt.b_copy_protect, WIDE)
+            }
+            DescriptorTypes::DescriptorUvcFrameUncompressed(frame) => {
+                let i = self.vs_header(bytes, "FRAME_UNCOMPRESSED")?;
+                self.frame_common(i, frame.b_frame_index, frame.bm_capabilities, frame.w_width, frame.w_height, frame.dw_min_bit_rate, frame.dw_max_bit_rate)?;
+                self.field(i, "dwMaxVideoFrameBufferSize", frame.dw_max_video_frame_buffer_size, WIDE)?;
+                self.field(i, "dwDefaultFrameInterval", frame.dw_default_frame_interval, WIDE)?;
+                self.intervals(i, bytes.get(25).copied().unwrap_or_default(), &frame.dw_frame_interval)
+            }
+            DescriptorTypes::DescriptorUvcFrameMjpeg(frame) => {
+                let i = self.vs_header(bytes, "FRAME_MJPEG")?;
+                self.frame_common(i, frame.b_frame_index, frame.bm_capabilities, frame.w_width, frame.w_height, frame.dw_min_bit_rate, frame.dw_max_bit_rate)?;
+                self.field(i, "dwMaxVideoFrameBufferSize", frame.dw_max_video_frame_buffer_size, WIDE)?;
+                self.field(i, "dwDefaultFrameInterval", frame.dw_default_frame_interval, WIDE)?;
+                self.intervals(i, bytes.get(25).copied().unwrap_or_default(), &frame.dw_frame_interval)
+            }
+            DescriptorTypes::UvcFrameFrameBased(frame) => {
+                let i = self.vs_header(bytes, "FRAME_FRAME_BASED")?;
+                self.frame_common(i, frame.b_frame_index, frame.bm_capabilities, frame.w_width, frame.w_height, frame.dw_min_bit_rate, frame.dw_max_bit_rate)?;
+                self.field(i, "dwDefaultFrameInterval", frame.dw_default_frame_interval, WIDE)?;
+                let interval_type = bytes.get(21).copied().unwrap_or_default();
+                self.field(i, "bFrameIntervalType", interval_type, WIDE)?;
+                self.field(i, "dwBytesPerLine", frame.dw_bytes_per_line, WIDE)?;
+                self.interval_values(i, interval_type, &frame.dw_frame_interval)
+            }
+            DescriptorTypes::DescriptorUvcVsInterfaceUnknown(desc) if desc.iface_subclass == 0x0d && desc.bytes.len() >= 3 => {
+                let i = self.vs_header(bytes, "COLORFORMAT")?;
+                let name = |names: &[&'static str], value: u8| names.get(value as usize).copied().unwrap_or("Unknown");
+                self.named(i, "bColorPrimaries", desc.bytes[0], WIDE, &format!("({})", name(COLOR_PRIMARIES, desc.bytes[0])))?;
+                self.named(i, "bTransferCharacteristics", desc.bytes[1], WIDE, &format!("({})", name(TRANSFER_CHARACTERISTICS, desc.bytes[1])))?;
+                self.named(i, "bMatrixCoefficients", desc.bytes[2], WIDE, &format!("({})", name(MATRIX_COEFFICIENTS, desc.bytes[2])))
+            }
+            DescriptorTypes::UacAcHeader(hdr) => {
+                let i = self.ac_header(bytes, "HEADER")?;
+                self.field(i, "bcdADC", bcd(hdr.bcd_adc), NARROW)?;
+                self.field(i, "wTotalLength", format!("{:#06x}", hdr.w_total_length), NARROW)?;
+                self.field(i, "bInCollection", hdr.b_in_collection, NARROW)?;
+                hdr.ba_interface_nr.iter().enumerate()
+                    .try_for_each(|(idx, nr)| self.field(i, &format!("baInterfaceNr({})", idx), nr, NARROW))
+            }
+            DescriptorTypes::UacInputTerminal(term) => {
+                let i = self.ac_header(bytes, "INPUT_TERMINAL")?;
+                self.field(i, "bTerminalID", term.b_terminal_id, NARROW)?;
+                self.named(i, "wTerminalType", format!("{:#06x}", term.w_terminal_type), NARROW, audio_terminal_name(term.w_terminal_type))?;
+                self.field(i, "bAssocTerminal", term.b_assoc_terminal, NARROW)?;
+                self.field(i, "bNrChannels", term.b_nr_channels, NARROW)?;
+                self.field(i, "wChannelConfig", format!("{:#06x}", term.w_channel_config), NARROW)?;
+                self.bit_names(i + 2, term.w_channel_config as u32, CHANNELS)?;
+                self.named(i, "iChannelNames", term.i_channel_names, NARROW, "")?;
+                self.named(i, "iTerminal", term.i_terminal, NARROW, "")
+            }
+            DescriptorTypes::UacOutputTerminal(term) => {
+                let i = self.ac_header(bytes, "OUTPUT_TERMINAL")?;
+                self.field(i, "bTerminalID", term.b_terminal_id, NARROW)?;
+                self.named(i, "wTerminalType", format!("{:#06x}", term.w_terminal_type), NARROW, audio_terminal_name(term.w_terminal_type))?;
+                self.field(i, "bAssocTerminal", term.b_assoc_terminal, NARROW)?;
+                self.field(i, "bSourceID", term.b_source_id, NARROW)?;
+                self.named(i, "iTerminal", term.i_terminal, NARROW, "")
+            }
+            DescriptorTypes::UacFeatureUnit(unit) => {
+                let i = self.ac_header(bytes, "FEATURE_UNIT")?;
+                self.field(i, "bUnitID", unit.b_unit_id, NARROW)?;
+                self.field(i, "bSourceID", unit.b_source_id, NARROW)?;
+                self.field(i, "bControlSize", unit.b_control_size, NARROW)?;
+                // bmaControls is followed by iFeature
+                let size = (unit.b_control_size as usize).max(1);
+                let (controls, i_feature) = unit.bma_controls.split_at(unit.bma_controls.len().saturating_sub(1));
+                for (idx, ctrl) in controls.chunks(size).enumerate() {
+                    let ctrl = le_bits(ctrl);
+                    self.field(i, &format!("bmaControls({})", idx), format!("{:#0w$x}", ctrl, w = 2 + 2 * size), NARROW)?;
+                    self.bit_names(i + 2, ctrl, FEATURE_CONTROLS)?;
+                }
+                self.named(i, "iFeature", i_feature.first().copied().unwrap_or_default(), NARROW, "")
+            }
+            DescriptorTypes::UacAsGeneral(hdr) => {
+                let i = self.header(CLASS, "AudioStreaming Interface Descriptor:", bytes)?;
+                self.subtype(i, bytes.get(2).copied().unwrap_or_default(), "AS_GENERAL", NARROW)?;
+                self.field(i, "bTerminalLink", hdr.b_terminal_link, NARROW)?;
+                self.named(i, "bDelay", hdr.b_delay, NARROW, "frames")?;
+                self.named(i, "wFormatTag", format!("{:#06x}", hdr.w_format_tag), NARROW, format_tag_name(hdr.w_format_tag))
+            }
+            DescriptorTypes::UacFormatTypeI(fmt) => {
+                let i = self.header(CLASS, "AudioStreaming Interface Descriptor:", bytes)?;
+                self.subtype(i, bytes.get(2).copied().unwrap_or_default(), "FORMAT_TYPE", NARROW)?;
+                self.named(i, "bFormatType", bytes.get(3).copied().unwrap_or_default(), NARROW, "(FORMAT_TYPE_I)")?;
+                self.field(i, "bNrChannels", fmt.b_nr_channels, NARROW)?;
+                self.field(i, "bSubframeSize", fmt.b_subframe_size, NARROW)?;
+                self.field(i, "bBitResolution", fmt.b_bit_resolution, NARROW)?;
+                if fmt.b_sam_freq_type == 0 && fmt.t_sam_freq.len() >= 2 {
+                    self.named(i, "bSamFreqType", fmt.b_sam_freq_type, NARROW, "Continuous")?;
+                    self.field(i, "tLowerSamFreq", fmt.t_sam_freq[0], NARROW)?;
+                    self.field(i, "tUpperSamFreq", fmt.t_sam_freq[1], NARROW)
+                } else {
+                    self.named(i, "bSamFreqType", fmt.b_sam_freq_type, NARROW, "Discrete")?;
+                    fmt.t_sam_freq.iter().enumerate()
+                        .try_for_each(|(idx, freq)| self.field(i, &format!("tSamFreq[{:2}]", idx), freq, NARROW))
+                }
+            }
+            _ => self.unrecognized(bytes),
+        }
+    }
+
+    fn write_indent(&mut self, indent: usize) -> fmt::Result {
+        write!(self.out, "{:indent$}", "", indent = indent)
+    }
+
+    fn line(&mut self, indent: usize, text: &str) -> fmt::Result {
+        self.write_indent(indent)?;
+        writeln!(self.out, "{}", text)
+    }
+
+    /// `name` followed by `value`, right aligned so that both end at column `width`.
+    fn field(&mut self, indent: usize, name: &str, value: impl fmt::Display, width: usize) -> fmt::Result {
+        let value = value.to_string();
+        let pad = width.saturating_sub(name.len() + value.len()).max(1);
+        self.write_indent(indent)?;
+        writeln!(self.out, "{}{:pad$}{}", name, "", value, pad = pad)
+    }
+
+    /// A field followed by a description, e.g. a class name; lsusb keeps the separating space
+    /// even when there is no description.
+    fn named(&mut self, indent: usize, name: &str, value: impl fmt::Display, width: usize, text: &str) -> fmt::Result {
+        self.field(indent, name, value, width)?;
+        self.out.pop();
+        writeln!(self.out, " {}", text)
+    }
+
+    /// Writes the title and the bLength/bDescriptorType lines, returning the field indentation.
+    fn header(&mut self, indent: usize, title: &str, bytes: &[u8]) -> Result<usize, fmt::Error> {
+        let width = if title.starts_with("VideoStreaming") || title.starts_with("Device Firmware") { WIDE } else { NARROW };
+        self.line(indent, title)?;
+        self.field(indent + 2, "bLength", bytes.first().copied().unwrap_or_default(), width)?;
+        self.field(indent + 2, "bDescriptorType", bytes.get(1).copied().unwrap_or_default(), width)?;
+        Ok(indent + 2)
+    }
+
+    fn subtype(&mut self, indent: usize, subtype: u8, name: &str, width: usize) -> fmt::Result {
+        self.named(indent, "bDescriptorSubtype", subtype, width, &format!("({})", name))
+    }
+
+    fn vc_header(&mut self, bytes: &[u8], name: &str) -> Result<usize, fmt::Error> {
+        let i = self.header(CLASS, "VideoControl Interface Descriptor:", bytes)?;
+        self.subtype(i, bytes.get(2).copied().unwrap_or_default(), name, NARROW)?;
+        Ok(i)
+    }
+
+    fn vs_header(&mut self, bytes: &[u8], name: &str) -> Result<usize, fmt::Error> {
+        let i = self.header(CLASS, "VideoStreaming Interface Descriptor:", bytes)?;
+        self.subtype(i, bytes.get(2).copied().unwrap_or_default(), name, WIDE)?;
+        Ok(i)
+    }
+
+    fn ac_header(&mut self, bytes: &[u8], name: &str) -> Result<usize, fmt::Error> {
+        let i = self.header(CLASS, "AudioControl Interface Descriptor:", bytes)?;
+        self.subtype(i, bytes.get(2).copied().unwrap_or_default(), name, NARROW)?;
+        Ok(i)
+    }
+
+    fn endpoint(&mut self, bytes: &[u8], address: u8, attributes: u8, max_packet_size: u16) -> Result<usize, fmt::Error> {
+        let i = self.header(CLASS, "Endpoint Descriptor:", bytes)?;
+        self.unknown_indent = ENDPOINT_CLASS;
+        self.field(i, "bEndpointAddress", format!("{:#04x}", address), NARROW)?;
+        self.out.pop();
+        writeln!(self.out, "  EP {} {}", address & 0x0f, if address & 0x80 != 0 { "IN" } else { "OUT" })?;
+        self.field(i, "bmAttributes", attributes, NARROW)?;
+        let transfer = ["Control", "Isochronous", "Bulk", "Interrupt"][(attributes & 0x03) as usize];
+        let synch = ["None", "Asynchronous", "Adaptive", "Synchronous"][((attributes >> 2) & 0x03) as usize];
+        let usage = ["Data", "Feedback", "Implicit feedback Data", "(reserved)"][((attributes >> 4) & 0x03) as usize];
+        self.line(i + 2, &format!("Transfer Type            {}", transfer))?;
+        self.line(i + 2, &format!("Synch Type               {}", synch))?;
+        self.line(i + 2, &format!("Usage Type               {}", usage))?;
+        let mult = ["1x", "2x", "3x", "(reserved)"][((max_packet_size >> 11) & 0x03) as usize];
+        self.field(i, "wMaxPacketSize", format!("{:#06x}", max_packet_size), NARROW)?;
+        self.out.pop();
+        writeln!(self.out, "  {} {} bytes", mult, max_packet_size & 0x7ff)?;
+        Ok(i)
+    }
+
+    fn hid(&mut self, bytes: &[u8]) -> fmt::Result {
+        let i = self.header(ENDPOINT_CLASS, "HID Device Descriptor:", bytes)?;
+        self.field(i, "bcdHID", bcd(u16::from_le_bytes([bytes[2], bytes[3]])), NARROW)?;
+        self.named(i, "bCountryCode", bytes[4], NARROW, if bytes[4] == 0 { "Not supported" } else { "" })?;
+        self.field(i, "bNumDescriptors", bytes[5], NARROW)?;
+        for desc in bytes[6..].chunks_exact(3) {
+            self.named(i, "bDescriptorType", desc[0], NARROW, if desc[0] == 0x22 { "Report" } else { "" })?;
+            self.field(i, "wDescriptorLength", u16::from_le_bytes([desc[1], desc[2]]), NARROW)?;
+        }
+        self.line(i - 1, "Report Descriptors: ")?;
+        self.line(i + 1, "** UNAVAILABLE **")
+    }
+
+    fn guid(&mut self, indent: usize, name: &str, raw: Option<&[u8]>, column: usize) -> fmt::Result {
+        let guid = raw.map(guid_string).unwrap_or_default();
+        self.write_indent(indent)?;
+        writeln!(self.out, "{:<w$}{}", name, guid, w = column)
+    }
+
+    fn interlace(&mut self, indent: usize, flags: u8) -> fmt::Result {
+        self.field(indent, "bmInterlaceFlags", format!("{:#04x}", flags), WIDE)?;
+        self.line(indent + 2, &format!("Interlaced stream or variable: {}", yes_no(flags & 0x01 != 0)))?;
+        self.line(indent + 2, &format!("Fields per frame: {} fields", if flags & 0x02 != 0 { 1 } else { 2 }))?;
+        self.line(indent + 2, &format!("Field 1 first: {}", yes_no(flags & 0x04 != 0)))?;
+        let pattern = ["Field 1 only", "Field 2 only", "Regular pattern of fields 1 and 2", "Random pattern of fields 1 and 2"];
+        self.line(indent + 2, &format!("Field pattern: {}", pattern[((flags >> 4) & 0x03) as usize]))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn frame_common(&mut self, i: usize, index: u8, caps: u8, width: u16, height: u16, min_bit_rate: u32, max_bit_rate: u32) -> fmt::Result {
+        self.field(i, "bFrameIndex", index, WIDE)?;
+        self.field(i, "bmCapabilities", format!("{:#04x}", caps), WIDE)?;
+        self.line(i + 2, if caps & 0x01 != 0 { "Still image supported" } else { "Still image unsupported" })?;
+        if caps & 0x02 != 0 {
+            self.line(i + 2, "Fixed frame-rate")?;
+        }
+        self.field(i, "wWidth", width, WIDE)?;
+        self.field(i, "wHeight", height, WIDE)?;
+        self.field(i, "dwMinBitRate", min_bit_rate, WIDE)?;
+        self.field(i, "dwMaxBitRate", max_bit_rate, WIDE)
+    }
+
+    fn intervals(&mut self, i: usize, interval_type: u8, intervals: &[u32]) -> fmt::Result {
+        self.field(i, "bFrameIntervalType", interval_type, WIDE)?;
+        self.interval_values(i, interval_type, intervals)
+    }
+
+    fn interval_values(&mut self, i: usize, interval_type: u8, intervals: &[u32]) -> fmt::Result {
+        if interval_type == 0 && intervals.len() >= 3 {
+            self.field(i, "dwMinFrameInterval", intervals[0], WIDE)?;
+            self.field(i, "dwMaxFrameInterval", intervals[1], WIDE)?;
+            return self.field(i, "dwFrameIntervalStep", intervals[2], WIDE);
+        }
+        intervals.iter().enumerate()
+            .try_for_each(|(idx, interval)| self.field(i, &format!("dwFrameInterval({:2})", idx), interval, WIDE))
+    }
+
+    fn flags(&mut self, indent: usize, value: u32, names: &[(u32, &str)]) -> fmt::Result {
+        names.iter()
+            .filter(|(bit, _)| value & (1 << bit) != 0)
+            .try_for_each(|(_, name)| self.line(indent, name))
+    }
+
+    /// Names of the set bits of `value`, indexed by bit number.
+    fn bit_names(&mut self, indent: usize, value: u32, names: &[&str]) -> fmt::Result {
+        names.iter().enumerate()
+            .filter(|(bit, _)| value & (1 << bit) != 0)
+            .try_for_each(|(_, name)| self.line(indent, name))
+    }
+
+    fn unrecognized(&mut self, bytes: &[u8]) -> fmt::Result {
+        self.write_indent(self.unknown_indent)?;
+        write!(self.out, "** UNRECOGNIZED: ")?;
+        bytes.iter().try_for_each(|b| write!(self.out, " {:02x}", b))?;
+        writeln!(self.out)
+    }
+}
+
+fn bcd(value: u16) -> String {
+    format!("{:x}.{:02x}", value >> 8, value & 0xff)
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "Yes" } else { "No" }
+}
+
+fn le_bits(bytes: &[u8]) -> u32 {
+    bytes.iter().take(4).rev().fold(0, |acc, b| acc << 8 | *b as u32)
+}
+
+/// GUIDs are stored with their first three groups little endian, like Windows prints them.
+fn guid_string(raw: &[u8]) -> String {
+    if raw.len() != 16 {
+        return String::new();
+    }
+    format!("{{{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{}}}",
+            raw[3], raw[2], raw[1], raw[0], raw[5], raw[4], raw[7], raw[6], raw[8], raw[9],
+            raw[10..].iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+fn device_class_name(class: u8) -> &'static str {
+    match class {
+        0x00 => "(Defined at Interface level)",
+        0xef => "Miscellaneous Device",
+        other => class_names(other, 0xff, 0xff).0,
+    }
+}
+
+/// usb.ids names for the class triples found in cameras and speakerphones.
+fn class_names(class: u8, subclass: u8, protocol: u8) -> (&'static str, &'static str, &'static str) {
+    match class {
+        LIBUSB_CLASS_AUDIO => ("Audio", match subclass {
+            s if s == UacInterfaceSubclass::AudioControl as u8 => "Control Device",
+            s if s == UacInterfaceSubclass::AudioStreaming as u8 => "Streaming",
+            3 => "MIDI Streaming",
+            _ => "",
+        }, ""),
+        0x02 => ("Communications", "", ""),
+        LIBUSB_CLASS_HID => ("Human Interface Device", match subclass {
+            0 => "No Subclass",
+            1 => "Boot Interface Subclass",
+            _ => "",
+        }, match protocol {
+            0 => "None",
+            1 => "Keyboard",
+            2 => "Mouse",
+            _ => "",
+        }),
+        0x08 => ("Mass Storage", "", ""),
+        0x09 => ("Hub", "", ""),
+        0x0a => ("CDC Data", "", ""),
+        LIBUSB_CLASS_VIDEO => ("Video", match subclass {
+            s if s == UvcInterfaceSubClass::VideoControl as u8 => "Video Control",
+            s if s == UvcInterfaceSubClass::VideoStreaming as u8 => "Video Streaming",
+            3 => "Video Interface Collection",
+            _ => "",
+        }, ""),
+        0xfe => ("Application Specific Interface", if subclass == 1 { "Device Firmware Update" } else { "" }, ""),
+        0xff => ("Vendor Specific Class",
+                 if subclass == 0xff { "Vendor Specific Subclass" } else { "" },
+                 if protocol == 0xff { "Vendor Specific Protocol" } else { "" }),
+        _ => ("", "", ""),
+    }
+}
+
+fn video_terminal_name(terminal_type: u16) -> &'static str {
+    match terminal_type {
+        0x0100 => "USB Vendor Specific",
+        0x0101 => "USB Streaming",
+        0x0200 => "Input Vendor Specific",
+        0x0201 => "Camera Sensor",
+        0x0202 => "Sequential Media",
+        0x0300 => "Output Vendor Specific",
+        0x0301 => "Generic Display",
+        0x0302 => "Sequential Media",
+        _ => "",
+    }
+}
+
+fn audio_terminal_name(terminal_type: u16) -> &'static str {
+    match terminal_type {
+        0x0100 => "USB Undefined",
+        0x0101 => "USB Streaming",
+        0x01ff => "USB Vendor Specific",
+        0x0200 => "Input Undefined",
+        0x0201 => "Microphone",
+        0x0202 => "Desktop Microphone",
+        0x0203 => "Personal Microphone",
+        0x0204 => "Omni-directional Microphone",
+        0x0205 => "Microphone Array",
+        0x0206 => "Processing Microphone Array",
+        0x0300 => "Output Undefined",
+        0x0301 => "Speaker",
+        0x0302 => "Headphones",
+        0x0303 => "Head Mounted Display Audio",
+        0x0304 => "Desktop Speaker",
+        0x0305 => "Room Speaker",
+        0x0306 => "Communication Speaker",
+        0x0307 => "Low Frequency Effects Speaker",
+        0x0400 => "Bidirectional Undefined",
+        0x0401 => "Handset",
+        0x0402 => "Headset",
+        0x0403 => "Speakerphone, no echo reduction",
+        0x0404 => "Echo-suppressing speakerphone",
+        0x0405 => "Echo-canceling speakerphone",
+        _ => "",
+    }
+}
+
+fn format_tag_name(tag: u16) -> &'static str {
+    match tag {
+        0x0001 => "PCM",
+        0x0002 => "PCM8",
+        0x0003 => "IEEE_FLOAT",
+        0x0004 => "ALAW",
+        0x0005 => "MULAW",
+        0x1001 => "MPEG",
+        0x1002 => "AC-3",
+        _ => "",
+    }
+}
+
+const CAMERA_CONTROLS: &[&str] = &[
+    "Scanning Mode", "Auto-Exposure Mode", "Auto-Exposure Priority", "Exposure Time (Absolute)",
+    "Exposure Time (Relative)", "Focus (Absolute)", "Focus (Relative)", "Iris (Absolute)", "Iris (Relative)",
+    "Zoom (Absolute)", "Zoom (Relative)", "PanTilt (Absolute)", "PanTilt (Relative)", "Roll (Absolute)",
+    "Roll (Relative)", "Reserved", "Reserved", "Focus, Auto", "Privacy", "Focus, Simple", "Window",
+    "Region of Interest",
+];
+
+const PROCESSING_CONTROLS: &[&str] = &[
+    "Brightness", "Contrast", "Hue", "Saturation", "Sharpness", "Gamma", "White Balance Temperature",
+    "White Balance Component", "Backlight Compensation", "Gain", "Power Line Frequency", "Hue, Auto",
+    "White Balance Temperature, Auto", "White Balance Component, Auto", "Digital Multiplier",
+    "Digital Multiplier Limit", "Analog Video Standard", "Analog Video Lock Status", "Contrast, Auto",
+];
+
+const VIDEO_STANDARDS: &[&str] = &["None", "NTSC - 525/60", "PAL - 625/50", "SECAM - 625/50", "NTSC - 625/50", "PAL - 525/60"];
+
+const VS_CONTROLS: &[&str] = &["wKeyFrameRate", "wPFrameRate", "wCompQuality", "wCompWindowSize", "Generate Key Frame", "Update Frame Segment"];
+
+const COLOR_PRIMARIES: &[&str] = &["Unspecified", "BT.709,sRGB", "BT.470-2 (M)", "BT.470-2 (B,G)", "SMPTE 170M", "SMPTE 240M"];
+
+const TRANSFER_CHARACTERISTICS: &[&str] = &["Unspecified", "BT.709", "BT.470-2 (M)", "BT.470-2 (B,G)", "SMPTE 170M", "SMPTE 240M", "Linear", "sRGB"];
+
+const MATRIX_COEFFICIENTS: &[&str] = &["Unspecified", "BT.709", "FCC", "BT.470-2 (B,G)", "SMPTE 170M (BT.601)", "SMPTE 240M"];
+
+const CHANNELS: &[&str] = &[
+    "Left Front (L)", "Right Front (R)", "Center Front (C)", "Low Frequency Enhancement (LFE)",
+    "Left Surround (LS)", "Right Surround (RS)", "Left of Center (LC)", "Right of Center (RC)",
+    "Surround (S)", "Side Left (SL)", "Side Right (SR)", "Top (T)",
+];
+
+const FEATURE_CONTROLS: &[&str] = &[
+    "Mute Control", "Volume Control", "Bass Control", "Mid Control", "Treble Control",
+    "Graphic Equalizer Control", "Automatic Gain Control", "Delay Control", "Bass Boost Control",
+    "Loudness Control",
+];
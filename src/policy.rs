@@ -0,0 +1,277 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::TreeNode;
+use crate::filter::{AudioFilter, VideoFilter};
+use crate::repair::{Recorder, Repair, UvcIndexMap};
+use crate::usb_proto::DescriptorTypes;
+use crate::validate::node_label;
+
+/// A set of per-device rewrite policies, usually loaded from a JSON file:
+///
+/// ```json
+/// { "policies": [
+///     { "name": "MeetUp: no 60Hz", "vendor_id": "0x046d", "product_id": "0x0866",
+///       "steps": [{ "op": "remove_high_fps" }] },
+///     { "name": "HuddleSHOT: no audio", "vendor_id": "0x25c1", "product_id": "0x0018", "bcd_device": "0x0100",
+///       "steps": [{ "op": "remove_uac" }, { "op": "set_field", "target": { "descriptor": "config" }, "field": "b_max_power", "value": 250 }] }
+/// ] }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PolicySet {
+    pub policies: Vec<Policy>,
+}
+
+impl PolicySet {
+    pub fn from_json(json: &str) -> Result<PolicySet, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<PolicySet, Error> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path).map_err(|e| anyhow!("Could not read policy file {}: {}", path.display(), e))?;
+        PolicySet::from_json(&json)
+    }
+
+    /// The policy for a device, preferring one pinned to `bcd_device` over one for any revision.
+    pub fn find(&self, vendor_id: u16, product_id: u16, bcd_device: u16) -> Option<&Policy> {
+        let mut matching = self.policies.iter().filter(|policy| policy.matches(vendor_id, product_id, bcd_device));
+        let first = matching.clone().next();
+        matching.find(|policy| policy.bcd_device.is_some()).or(first)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Policy {
+    #[serde(default)]
+    pub name: String,
+    /// Numbers, hex strings such as "0x046d" or decimal strings such as "1133"
+    #[serde(deserialize_with = "de_id")]
+    pub vendor_id: u16,
+    #[serde(deserialize_with = "de_id")]
+    pub product_id: u16,
+    /// Matches every revision when absent
+    #[serde(default, deserialize_with = "de_opt_id")]
+    pub bcd_device: Option<u16>,
+    /// Applied in order, each followed by `fix_tree`
+    pub steps: Vec<PolicyStep>,
+}
+
+impl Policy {
+    pub fn matches(&self, vendor_id: u16, product_id: u16, bcd_device: u16) -> bool {
+        self.vendor_id == vendor_id && self.product_id == product_id && (self.bcd_device.is_none() || self.bcd_device == Some(bcd_device))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PolicyStep {
+    /// Removes interfaces by number; interface associations left empty are removed with them
+    RemoveIfaces { ifaces: Vec<u8> },
+    /// Removes every audio interface together with its interface association
+    RemoveUac,
+    /// Removes frame based formats from every UVC input header
+    RemoveH264,
+    RemoveHighFps,
+    RemoveHighResolution,
+    VideoFilter(VideoFilter),
+    AudioFilter(AudioFilter),
+    /// Overrides a field that is not derived from the tree's structure
+    SetField { target: FieldTarget, field: String, value: u32 },
+}
+
+impl fmt::Display for PolicyStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+/// The descriptor a `PolicyStep::SetField` writes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "descriptor", rename_all = "snake_case")]
+pub enum FieldTarget {
+    Config,
+    Interface { interface: u8, alt: u8 },
+    Endpoint { address: u8 },
+}
+
+impl FieldTarget {
+    fn matches(&self, parsed: &DescriptorTypes) -> bool {
+        match (self, parsed) {
+            (FieldTarget::Config, DescriptorTypes::Config(_)) => true,
+            (FieldTarget::Interface { interface, alt }, DescriptorTypes::Interface(iface)) =>
+                iface.b_interface_number == *interface && iface.b_alternate_setting == *alt,
+            (FieldTarget::Endpoint { address }, DescriptorTypes::Endpoint(ep)) => ep.b_endpoint_address == *address,
+            (FieldTarget::Endpoint { address }, DescriptorTypes::UacEndpoint(ep)) => ep.b_endpoint_address == *address,
+            _ => false,
+        }
+    }
+}
+
+/// What `apply_policy` changed, step by step.
+#[derive(Debug, Clone, Default)]
+pub struct PolicyReport {
+    pub policy: String,
+    pub steps: Vec<StepReport>,
+    /// Indices of every remaining frame before and after the policy, e.g. for `StreamingTranslator`
+    pub index_map: UvcIndexMap,
+}
+
+#[derive(Debug, Clone)]
+pub struct StepReport {
+    pub step: PolicyStep,
+    pub repairs: Vec<Repair>,
+}
+
+impl fmt::Display for PolicyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "policy {}", self.policy)?;
+        for step in self.steps.iter() {
+            writeln!(f, "\t{}", step.step)?;
+            for repair in step.repairs.iter() {
+                writeln!(f, "\t\t{}", repair)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Applies the steps of `policy` in order and then renumbers format and frame indices. The tree
+/// is only modified if every step succeeds.
+pub fn apply_policy(root: &mut TreeNode, policy: &Policy) -> Result<PolicyReport, Error> {
+    let mut tree = root.clone();
+    let mut report = PolicyReport { policy: policy.name.clone(), ..Default::default() };
+    let mut index_maps = vec![];
+    for step in policy.steps.iter() {
+        let repairs = apply_step(&mut tree, step, &mut index_maps).map_err(|e| anyhow!("Policy '{}' step {} failed: {}", policy.name, step, e))?;
+        report.steps.push(StepReport { step: step.clone(), repairs });
+    }
+    index_maps.push(tree.renumber_uvc_indices());
+    report.index_map = index_maps.into_iter().reduce(|map, next| map.then(&next)).unwrap_or_default();
+    *root = tree;
+    Ok(report)
+}
+
+/// Runs one step and `fix_tree`; steps that renumber indices add their mapping to `index_maps`.
+fn apply_step(tree: &mut TreeNode, step: &PolicyStep, index_maps: &mut Vec<UvcIndexMap>) -> Result<Vec<Repair>, Error> {
+    let mut repairs = vec![];
+    match step {
+        PolicyStep::RemoveIfaces { ifaces } => {
+            tree.remove_ifaces(ifaces);
+            remove_empty_iface_assoc(tree);
+        }
+        PolicyStep::RemoveUac => {
+            let ids = tree.find_uac_ifaces();
+            tree.remove_iface_assoc(&ids);
+            tree.remove_ifaces(&ids);
+        }
+        PolicyStep::RemoveH264 => remove_h264_all(tree)?,
+        PolicyStep::RemoveHighFps => tree.remove_high_fps(),
+        PolicyStep::RemoveHighResolution => tree.remove_high_resolution(),
+        PolicyStep::VideoFilter(filter) => {
+            let report = tree.apply_video_filter(filter);
+            index_maps.push(report.index_map);
+            repairs = report.repairs;
+        }
+        PolicyStep::AudioFilter(filter) => repairs = tree.apply_audio_filter(filter),
+        PolicyStep::SetField { target, field, value } => {
+            if !set_field(tree, target, field, *value, &mut vec![], &mut repairs)? {
+                return Err(anyhow!("No {:?} descriptor in the tree", target));
+            }
+        }
+    }
+    repairs.append(&mut tree.fix_tree());
+    Ok(repairs)
+}
+
+fn remove_empty_iface_assoc(node: &mut TreeNode) {
+    node.children.retain(|child| match child.parsed {
+        DescriptorTypes::InterfaceAssociation(_) => child.children.iter().any(|iface| matches!(iface.parsed, DescriptorTypes::Interface(_))),
+        _ => true,
+    });
+    node.children.iter_mut().for_each(remove_empty_iface_assoc);
+}
+
+fn remove_h264_all(node: &mut TreeNode) -> Result<(), Error> {
+    if let DescriptorTypes::UvcInputHeader(_) = node.parsed {
+        return node.remove_h264();
+    }
+    node.children.iter_mut().try_for_each(remove_h264_all)
+}
+
+/// Sets `field` on every descriptor `target` matches, returning whether there was one.
+fn set_field(node: &mut TreeNode, target: &FieldTarget, field: &str, value: u32, path: &mut Vec<String>, repairs: &mut Vec<Repair>) -> Result<bool, Error> {
+    path.push(node_label(&node.parsed));
+    let mut found = false;
+    if target.matches(&node.parsed) {
+        let mut recorder = Recorder { path, repairs };
+        let u8_value = || u8::try_from(value).map_err(|_| anyhow!("{} does not fit {}", value, field));
+        let u16_value = || u16::try_from(value).map_err(|_| anyhow!("{} does not fit {}", value, field));
+        match &mut node.parsed {
+            DescriptorTypes::Config(config) => match field {
+                "b_configuration_value" => recorder.set("b_configuration_value", &mut config.b_configuration_value, u8_value()?),
+                "i_configuration" => recorder.set("i_configuration", &mut config.i_configuration, u8_value()?),
+                "bm_attributes" => recorder.set("bm_attributes", &mut config.bm_attributes, u8_value()?),
+                "b_max_power" => recorder.set("b_max_power", &mut config.b_max_power, u8_value()?),
+                _ => return Err(anyhow!("Field {} of a configuration can not be overridden", field)),
+            },
+            DescriptorTypes::Interface(iface) => match field {
+                "b_interface_class" => recorder.set("b_interface_class", &mut iface.b_interface_class, u8_value()?),
+                "b_interface_sub_class" => recorder.set("b_interface_sub_class", &mut iface.b_interface_sub_class, u8_value()?),
+                "b_interface_protocol" => recorder.set("b_interface_protocol", &mut iface.b_interface_protocol, u8_value()?),
+                "i_interface" => recorder.set("i_interface", &mut iface.i_interface, u8_value()?),
+                _ => return Err(anyhow!("Field {} of an interface can not be overridden", field)),
+            },
+            DescriptorTypes::Endpoint(ep) => match field {
+                "bm_attributes" => recorder.set("bm_attributes", &mut ep.bm_attributes, u8_value()?),
+                "w_max_packet_size" => recorder.set("w_max_packet_size", &mut ep.w_max_packet_size, u16_value()?),
+                "b_interval" => recorder.set("b_interval", &mut ep.b_interval, u8_value()?),
+                _ => return Err(anyhow!("Field {} of an endpoint can not be overridden", field)),
+            },
+            DescriptorTypes::UacEndpoint(ep) => match field {
+                "bm_attributes" => recorder.set("bm_attributes", &mut ep.bm_attributes, u8_value()?),
+                "w_max_packet_size" => recorder.set("w_max_packet_size", &mut ep.w_max_packet_size, u16_value()?),
+                "b_interval" => recorder.set("b_interval", &mut ep.b_interval, u8_value()?),
+                _ => return Err(anyhow!("Field {} of an endpoint can not be overridden", field)),
+            },
+            _ => {}
+        }
+        found = true;
+    }
+    for child in node.children.iter_mut() {
+        found |= set_field(child, target, field, value, path, repairs)?;
+    }
+    path.pop();
+    Ok(found)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Id {
+    Number(u16),
+    Text(String),
+}
+
+impl Id {
+    fn parse<E: serde::de::Error>(self) -> Result<u16, E> {
+        match self {
+            Id::Number(id) => Ok(id),
+            Id::Text(text) => match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+                Some(hex) => u16::from_str_radix(hex, 16),
+                None => text.parse(),
+            }.map_err(|_| E::custom(format!("invalid id {:?}", text))),
+        }
+    }
+}
+
+fn de_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    Id::deserialize(deserializer)?.parse()
+}
+
+fn de_opt_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u16>, D::Error> {
+    Option::<Id>::deserialize(deserializer)?.map(Id::parse).transpose()
+}
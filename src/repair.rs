@@ -0,0 +1,338 @@
+use std::fmt;
+use std::fmt::Debug;
+
+use crate::TreeNode;
+use crate::usb_proto::DescriptorTypes;
+use crate::validate::{nearest, node_label};
+
+/// A single field rewritten by `TreeNode::fix_tree`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repair {
+    /// Location of the descriptor before the repair, e.g. `Config 1 / IAD 0..1 / Interface 1.0 / UvcInputHeader`
+    pub path: String,
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} {} -> {}", self.path, self.field, self.old, self.new)
+    }
+}
+
+impl TreeNode {
+    /// Restores every count, length and index derived from the tree's structure after nodes have
+    /// been added or removed, and returns the fields that changed.
+    ///
+    /// Interface numbers, endpoint addresses and format and frame indices are left as they are, so
+    /// call `renumber_uvc_indices` to close gaps left by removed formats and frames; headers drop
+    /// interfaces that no longer exist and b_default_frame_index moves to the nearest remaining
+    /// frame. bmaControls of a UVC input header keeps the entries of the remaining formats, by
+    /// their b_format_index, and is zero-padded for formats that have none.
+    pub fn fix_tree(&mut self) -> Vec<Repair> {
+        let mut repairs = vec![];
+        repair_node(self, None, &mut vec![], &mut repairs);
+        repairs
+    }
+
+    /// Compacts `b_format_index` and `b_frame_index` to 1..n, keeping their relative order, and
+    /// moves `b_default_frame_index` along. Returns the old -> new mapping for each streaming
+    /// interface so probe/commit requests can be translated.
+    pub fn renumber_uvc_indices(&mut self) -> UvcIndexMap {
+        let mut map = UvcIndexMap::default();
+        renumber_node(self, 0, &mut map);
+        map
+    }
+}
+
+/// One frame's indices before and after `renumber_uvc_indices`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UvcIndexMapping {
+    pub interface: u8,
+    pub old_format: u8,
+    pub old_frame: u8,
+    pub new_format: u8,
+    pub new_frame: u8,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UvcIndexMap {
+    pub entries: Vec<UvcIndexMapping>,
+}
+
+impl UvcIndexMap {
+    /// True if no index changed.
+    pub fn is_identity(&self) -> bool {
+        self.entries.iter().all(|entry| entry.old_format == entry.new_format && entry.old_frame == entry.new_frame)
+    }
+
+    /// Translates device (format, frame) indices to the renumbered ones.
+    pub fn to_new(&self, interface: u8, format: u8, frame: u8) -> Option<(u8, u8)> {
+        self.entries.iter()
+            .find(|entry| entry.interface == interface && entry.old_format == format && entry.old_frame == frame)
+            .map(|entry| (entry.new_format, entry.new_frame))
+    }
+
+    /// Translates renumbered (format, frame) indices back to the device's.
+    pub fn to_old(&self, interface: u8, format: u8, frame: u8) -> Option<(u8, u8)> {
+        self.entries.iter()
+            .find(|entry| entry.interface == interface && entry.new_format == format && entry.new_frame == frame)
+            .map(|entry| (entry.old_format, entry.old_frame))
+    }
+
+    pub fn format_to_new(&self, interface: u8, format: u8) -> Option<u8> {
+        self.entries.iter()
+            .find(|entry| entry.interface == interface && entry.old_format == format)
+            .map(|entry| entry.new_format)
+    }
+
+    pub fn format_to_old(&self, interface: u8, format: u8) -> Option<u8> {
+        self.entries.iter()
+            .find(|entry| entry.interface == interface && entry.new_format == format)
+            .map(|entry| entry.old_format)
+    }
+
+    /// The mapping of `self` followed by `next`, e.g. of two renumberings in a row. Frames `next`
+    /// has no entry for, because they were removed in between, are left out.
+    pub fn then(&self, next: &UvcIndexMap) -> UvcIndexMap {
+        let entries = self.entries.iter().filter_map(|entry| {
+            let (new_format, new_frame) = next.to_new(entry.interface, entry.new_format, entry.new_frame)?;
+            Some(UvcIndexMapping { new_format, new_frame, ..*entry })
+        }).collect();
+        UvcIndexMap { entries }
+    }
+}
+
+fn renumber_node(node: &mut TreeNode, iface: u8, map: &mut UvcIndexMap) {
+    let iface = match &node.parsed {
+        DescriptorTypes::Interface(desc) => desc.b_interface_number,
+        _ => iface,
+    };
+    if let DescriptorTypes::UvcInputHeader(_) = node.parsed {
+        let mut recorder = Recorder { path: &[], repairs: &mut vec![] };
+        let old_formats = uvc_format_indices(node);
+        let new_formats = compact(&old_formats);
+        let formats = node.children.iter_mut().filter(|child| child.parsed.is_uvc_format());
+        for ((fmt, old_format), new_format) in formats.zip(old_formats).zip(new_formats) {
+            let old_frames = fmt.uvc_frame_indices();
+            for (old_frame, new_frame) in old_frames.iter().zip(compact(&old_frames)) {
+                map.entries.push(UvcIndexMapping { interface: iface, old_format, old_frame: *old_frame, new_format, new_frame });
+            }
+            renumber_frames(fmt, &mut recorder);
+        }
+        renumber_formats(node, &mut recorder);
+    }
+    node.children.iter_mut().for_each(|child| renumber_node(child, iface, map));
+}
+
+pub(crate) struct Recorder<'a> {
+    pub(crate) path: &'a [String],
+    pub(crate) repairs: &'a mut Vec<Repair>,
+}
+
+impl<'a> Recorder<'a> {
+    pub(crate) fn set<T: PartialEq + Debug>(&mut self, field: &'static str, slot: &mut T, new: T) {
+        if *slot != new {
+            self.repairs.push(Repair { path: self.path.join(" / "), field, old: format!("{:?}", slot), new: format!("{:?}", new) });
+            *slot = new;
+        }
+    }
+}
+
+/// Children are repaired before their parent so that lengths are computed over repaired descriptors.
+/// `ifaces` holds the interface numbers of the enclosing configuration, if it is part of the tree.
+fn repair_node(node: &mut TreeNode, ifaces: Option<&[u8]>, path: &mut Vec<String>, repairs: &mut Vec<Repair>) {
+    path.push(node_label(&node.parsed));
+
+    let config_ifaces = match node.parsed {
+        DescriptorTypes::Config(_) => Some(node.find_ifaces()),
+        _ => None,
+    };
+    let ifaces = config_ifaces.as_deref().or(ifaces);
+    for child in node.children.iter_mut() {
+        repair_node(child, ifaces, path, repairs);
+    }
+
+    let mut recorder = Recorder { path, repairs };
+    match &mut node.parsed {
+        DescriptorTypes::UvcHeaderDescriptor(hdr) => {
+            if let Some(ifaces) = ifaces {
+                let listed: Vec<u8> = hdr.ba_interface_nr.iter().copied().filter(|num| ifaces.contains(num)).collect();
+                recorder.set("ba_interface_nr", &mut hdr.ba_interface_nr, listed);
+            }
+            recorder.set("b_in_collection", &mut hdr.b_in_collection, hdr.ba_interface_nr.len() as u8);
+        }
+        DescriptorTypes::UacAcHeader(hdr) => {
+            if let Some(ifaces) = ifaces {
+                let listed: Vec<u8> = hdr.ba_interface_nr.iter().copied().filter(|num| ifaces.contains(num)).collect();
+                recorder.set("ba_interface_nr", &mut hdr.ba_interface_nr, listed);
+            }
+            recorder.set("b_in_collection", &mut hdr.b_in_collection, hdr.ba_interface_nr.len() as u8);
+        }
+        DescriptorTypes::DescriptorUvcFrameMjpeg(frame) => {
+            let interval = nearest(frame.dw_default_frame_interval, &frame.dw_frame_interval);
+            recorder.set("dw_default_frame_interval", &mut frame.dw_default_frame_interval, interval);
+        }
+        DescriptorTypes::DescriptorUvcFrameUncompressed(frame) => {
+            let interval = nearest(frame.dw_default_frame_interval, &frame.dw_frame_interval);
+            recorder.set("dw_default_frame_interval", &mut frame.dw_default_frame_interval, interval);
+        }
+        DescriptorTypes::UvcFrameFrameBased(frame) => {
+            let interval = nearest(frame.dw_default_frame_interval, &frame.dw_frame_interval);
+            recorder.set("dw_default_frame_interval", &mut frame.dw_default_frame_interval, interval);
+        }
+        _ => {}
+    }
+
+    let frame_idxs = node.uvc_frame_indices();
+    match &mut node.parsed {
+        DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) => {
+            recorder.set("b_num_frame_descriptors", &mut fmt.b_num_frame_descriptors, frame_idxs.len() as u8);
+        }
+        DescriptorTypes::DescriptorUvcFormatUncompressed(fmt) => {
+            recorder.set("b_num_frame_descriptors", &mut fmt.b_num_frame_descriptors, frame_idxs.len() as u8);
+        }
+        DescriptorTypes::UvcFormatFrameBased(fmt) => {
+            recorder.set("b_num_frame_descriptors", &mut fmt.b_num_frame_descriptors, frame_idxs.len() as u8);
+        }
+        _ => {}
+    }
+    match &mut node.parsed {
+        DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) => fix_default_frame(&mut fmt.b_default_frame_index, &frame_idxs, &mut recorder),
+        DescriptorTypes::DescriptorUvcFormatUncompressed(fmt) => fix_default_frame(&mut fmt.b_default_frame_index, &frame_idxs, &mut recorder),
+        DescriptorTypes::UvcFormatFrameBased(fmt) => fix_default_frame(&mut fmt.b_default_frame_index, &frame_idxs, &mut recorder),
+        _ => {}
+    }
+
+    if let DescriptorTypes::UvcInputHeader(_) = node.parsed {
+        let fmt_idxs = uvc_format_indices(node);
+        if let DescriptorTypes::UvcInputHeader(hdr) = &mut node.parsed {
+            let size = hdr.b_control_size as usize;
+            if size > 0 && hdr.bma_controls.len() > fmt_idxs.len() * size {
+                // one entry per format by b_format_index, so removed formats take theirs along
+                let controls = fmt_idxs.iter().flat_map(|idx| {
+                    (*idx as usize).checked_sub(1)
+                        .and_then(|pos| hdr.bma_controls.get(pos * size..(pos + 1) * size))
+                        .map(<[u8]>::to_vec)
+                        .unwrap_or_else(|| vec![0; size])
+                }).collect();
+                recorder.set("bma_controls", &mut hdr.bma_controls, controls);
+            } else if size > 0 {
+                let mut controls = hdr.bma_controls.clone();
+                controls.resize(fmt_idxs.len() * size, 0);
+                recorder.set("bma_controls", &mut hdr.bma_controls, controls);
+            }
+        }
+    }
+
+    let tree_len = node.serialized_len();
+    let iface_cnt = node.find_ifaces().len();
+    let ep_cnt = node.endpoint_count();
+    let cs_len = node.class_specific_len();
+    let child_ifaces: Vec<u8> = node.children.iter().filter_map(|child| match &child.parsed {
+        DescriptorTypes::Interface(iface) => Some(iface.b_interface_number),
+        _ => None,
+    }).collect();
+    match &mut node.parsed {
+        DescriptorTypes::Config(conf) => {
+            recorder.set("w_total_length", &mut conf.w_total_length, tree_len as u16);
+            recorder.set("b_num_interfaces", &mut conf.b_num_interfaces, iface_cnt as u8);
+        }
+        DescriptorTypes::InterfaceAssociation(assoc) => {
+            if let (Some(first), Some(last)) = (child_ifaces.iter().min(), child_ifaces.iter().max()) {
+                recorder.set("b_first_interface", &mut assoc.b_first_interface, *first);
+                recorder.set("b_interface_count", &mut assoc.b_interface_count, last - first + 1);
+            }
+        }
+        DescriptorTypes::Interface(iface) => {
+            recorder.set("b_num_endpoints", &mut iface.b_num_endpoints, ep_cnt as u8);
+        }
+        DescriptorTypes::UvcInputHeader(hdr) => {
+            recorder.set("w_total_length", &mut hdr.w_total_length, tree_len as u16);
+        }
+        DescriptorTypes::Custom(desc) => desc.fix(&node.children),
+        _ => {}
+    }
+
+    // VC/AC header lengths cover the descriptors next to them, so they are fixed from the interface
+    if let DescriptorTypes::Interface(_) = node.parsed {
+        for child in node.children.iter_mut() {
+            match &mut child.parsed {
+                DescriptorTypes::UvcHeaderDescriptor(hdr) => recorder.set("w_total_length", &mut hdr.w_total_length, cs_len as u16),
+                DescriptorTypes::UacAcHeader(hdr) => recorder.set("w_total_length", &mut hdr.w_total_length, cs_len as u16),
+                _ => {}
+            }
+        }
+    }
+
+    path.pop();
+}
+
+/// Points b_default_frame_index at the nearest of `frame_idxs` if its frame was removed.
+fn fix_default_frame(slot: &mut u8, frame_idxs: &[u8], recorder: &mut Recorder) {
+    if !frame_idxs.is_empty() && !frame_idxs.contains(slot) {
+        recorder.set("b_default_frame_index", slot, nearest(*slot, frame_idxs));
+    }
+}
+
+/// Renumbers the frames of a format node 1..n, keeping their relative order, and moves
+/// b_default_frame_index along (to the nearest remaining frame if its frame was removed).
+fn renumber_frames(node: &mut TreeNode, recorder: &mut Recorder) {
+    let old_idxs = node.uvc_frame_indices();
+    let new_idxs = compact(&old_idxs);
+    let default_slot = match &mut node.parsed {
+        DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) => &mut fmt.b_default_frame_index,
+        DescriptorTypes::DescriptorUvcFormatUncompressed(fmt) => &mut fmt.b_default_frame_index,
+        DescriptorTypes::UvcFormatFrameBased(fmt) => &mut fmt.b_default_frame_index,
+        _ => return,
+    };
+    if let Some(pos) = old_idxs.iter().position(|idx| *idx == nearest(*default_slot, &old_idxs)) {
+        recorder.set("b_default_frame_index", default_slot, new_idxs[pos]);
+    }
+
+    let format = node.parsed.clone();
+    let mut new_idxs = new_idxs.into_iter();
+    for child in node.children.iter_mut() {
+        let slot = match (&format, &mut child.parsed) {
+            (DescriptorTypes::DescriptorUvcFormatMjpeg(_), DescriptorTypes::DescriptorUvcFrameMjpeg(frame)) => &mut frame.b_frame_index,
+            (DescriptorTypes::DescriptorUvcFormatUncompressed(_), DescriptorTypes::DescriptorUvcFrameUncompressed(frame)) => &mut frame.b_frame_index,
+            (DescriptorTypes::UvcFormatFrameBased(_), DescriptorTypes::UvcFrameFrameBased(frame)) => &mut frame.b_frame_index,
+            _ => continue,
+        };
+        recorder.set("b_frame_index", slot, new_idxs.next().unwrap_or_default());
+    }
+}
+
+/// Renumbers the formats of a UVC input header node 1..n, keeping their relative order.
+fn renumber_formats(node: &mut TreeNode, recorder: &mut Recorder) {
+    let mut new_idxs = compact(&uvc_format_indices(node)).into_iter();
+    for child in node.children.iter_mut() {
+        let slot = match &mut child.parsed {
+            DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) => &mut fmt.b_format_index,
+            DescriptorTypes::DescriptorUvcFormatUncompressed(fmt) => &mut fmt.b_format_index,
+            DescriptorTypes::UvcFormatFrameBased(fmt) => &mut fmt.b_format_index,
+            _ => continue,
+        };
+        recorder.set("b_format_index", slot, new_idxs.next().unwrap_or_default());
+    }
+}
+
+fn uvc_format_indices(node: &TreeNode) -> Vec<u8> {
+    node.children.iter().filter_map(|child| match &child.parsed {
+        DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) => Some(fmt.b_format_index),
+        DescriptorTypes::DescriptorUvcFormatUncompressed(fmt) => Some(fmt.b_format_index),
+        DescriptorTypes::UvcFormatFrameBased(fmt) => Some(fmt.b_format_index),
+        _ => None,
+    }).collect()
+}
+
+/// Replaces each index by its rank among `idxs`, so e.g. [3, 1, 4] becomes [2, 1, 3]. Duplicates
+/// are numbered in order of appearance.
+fn compact(idxs: &[u8]) -> Vec<u8> {
+    let mut order: Vec<usize> = (0..idxs.len()).collect();
+    order.sort_by_key(|pos| (idxs[*pos], *pos));
+    let mut new_idxs = vec![0; idxs.len()];
+    order.iter().enumerate().for_each(|(rank, pos)| new_idxs[*pos] = rank as u8 + 1);
+    new_idxs
+}
@@ -0,0 +1,254 @@
+use std::fmt;
+
+use libusb1_sys::constants::{LIBUSB_CLASS_APPLICATION, LIBUSB_CLASS_AUDIO, LIBUSB_CLASS_VIDEO};
+use num_traits::FromPrimitive;
+
+use crate::TreeNode;
+use crate::dfu_proto::{DfuRequestCodes, DFU_IFACE_SUBCLASS};
+use crate::uac_proto::{FeatureUnitControlSelectors, UacEndpointControlSelectors, UacInterfaceSubclass, UacRequestCodes};
+use crate::usb_proto::{DescriptorTypes, IfaceAltSetting, Recip, SetupPacket, StandardRequest, UsbDescriptorTypes, XferType, USB_DEVICE_REMOTE_WAKEUP, USB_ENDPOINT_HALT, USB_TEST_MODE};
+use crate::uvc_control::{UvcControlSelector, UvcControlSpec, UvcEntityKind};
+use crate::uvc_proto::{UvcInterfaceSubClass, UvcRequestCodes, UvcRequestTypes, UvcVcControlSelectors};
+
+// B.1 Terminal Types
+const UVC_ITT_CAMERA: u16 = 0x0201;
+
+impl fmt::Display for SetupPacket {
+    /// Decodes standard requests, e.g. `SET_INTERFACE interface 1 alt 2`; anything else is shown
+    /// with its raw fields. See `describe` for class requests.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(request) = self.standard_request() else {
+            let kind = match self.xfer_type() {
+                XferType::Class => "class",
+                XferType::Vendor => "vendor",
+                _ => "standard",
+            };
+            return write!(f, "{} request {:#04x} to {}: wValue {:#06x}, wIndex {:#06x}, wLength {}",
+                          kind, self.b_request, self.recipient(), self.w_value, self.w_index, self.w_length);
+        };
+        match request {
+            StandardRequest::GetStatus => write!(f, "GET_STATUS {}", self.recipient()),
+            StandardRequest::ClearFeature | StandardRequest::SetFeature => {
+                let name = if request == StandardRequest::ClearFeature { "CLEAR_FEATURE" } else { "SET_FEATURE" };
+                let feature = match self.w_value {
+                    USB_ENDPOINT_HALT if self.recip() == Recip::Ep => "ENDPOINT_HALT".to_string(),
+                    USB_DEVICE_REMOTE_WAKEUP if self.recip() == Recip::Dev => "DEVICE_REMOTE_WAKEUP".to_string(),
+                    USB_TEST_MODE if self.recip() == Recip::Dev => "TEST_MODE".to_string(),
+                    feature => format!("feature {}", feature),
+                };
+                write!(f, "{} {} on {}", name, feature, self.recipient())
+            }
+            StandardRequest::SetAddress => write!(f, "SET_ADDRESS {}", self.w_value),
+            StandardRequest::GetDescriptor | StandardRequest::SetDescriptor => {
+                let name = if request == StandardRequest::GetDescriptor { "GET_DESCRIPTOR" } else { "SET_DESCRIPTOR" };
+                let index = self.w_value & 0xff;
+                write!(f, "{} {} {}", name, self.descriptor_type_name(), index)?;
+                if self.w_value >> 8 == UsbDescriptorTypes::String as u16 && index != 0 {
+                    write!(f, " language {:#06x}", self.w_index)?;
+                } else if self.recip() == Recip::Iface {
+                    write!(f, " of interface {}", self.w_index)?;
+                }
+                write!(f, ", {} bytes", self.w_length)
+            }
+            StandardRequest::GetConfiguration => write!(f, "GET_CONFIGURATION"),
+            StandardRequest::SetConfiguration => write!(f, "SET_CONFIGURATION {}", self.w_value),
+            StandardRequest::GetInterface => write!(f, "GET_INTERFACE interface {}", self.w_index),
+            StandardRequest::SetInterface => write!(f, "SET_INTERFACE interface {} alt {}", self.w_index, self.w_value),
+            StandardRequest::SynchFrame => write!(f, "SYNCH_FRAME endpoint {:#04x}", self.w_index),
+        }
+    }
+}
+
+impl SetupPacket {
+    /// Like `Display`, but decodes UVC, UAC and DFU class requests by looking up the interface,
+    /// unit or endpoint they address in `tree`, e.g. `SET_CUR VS_COMMIT_CONTROL on interface 1` or
+    /// `GET_MIN volume on feature unit 2 channel 0`.
+    pub fn describe(&self, tree: &TreeNode) -> String {
+        if self.xfer_type() != XferType::Class {
+            return self.to_string();
+        }
+        let described = match self.recip() {
+            Recip::Iface => self.describe_iface_request(tree),
+            Recip::Ep => self.describe_ep_request(tree),
+            _ => None,
+        };
+        described.unwrap_or_else(|| self.to_string())
+    }
+
+    fn describe_iface_request(&self, tree: &TreeNode) -> Option<String> {
+        let iface_num = (self.w_index & 0xff) as u8;
+        let entity_id = (self.w_index >> 8) as u8;
+        let node = tree.get_iface_by_num(IfaceAltSetting::new(iface_num, 0))?;
+        let DescriptorTypes::Interface(iface) = &node.parsed else { return None };
+        let selector = (self.w_value >> 8) as u8;
+        match (iface.b_interface_class, iface.b_interface_sub_class) {
+            (LIBUSB_CLASS_VIDEO, subclass) => {
+                let request = uvc_request_name(self.b_request)?;
+                if subclass == UvcInterfaceSubClass::VideoStreaming as u8 {
+                    let control = uvc_vs_selector_name(selector).map(str::to_string).unwrap_or_else(|| format!("selector {:#04x}", selector));
+                    return Some(format!("{} {} on interface {}", request, control, iface_num));
+                }
+                if entity_id == 0 {
+                    let control = uvc_vc_selector_name(selector).map(str::to_string).unwrap_or_else(|| format!("selector {:#04x}", selector));
+                    return Some(format!("{} {} on interface {}", request, control, iface_num));
+                }
+                let control = UvcEntityKind::of(node, entity_id)
+                    .and_then(|kind| UvcControlSelector::from_entity(kind, selector))
+                    .and_then(UvcControlSpec::lookup)
+                    .map(|spec| spec.name.to_string())
+                    .unwrap_or_else(|| format!("selector {:#04x}", selector));
+                Some(format!("{} {} on {}", request, control, entity_name(node, entity_id)))
+            }
+            (LIBUSB_CLASS_AUDIO, subclass) if subclass == UacInterfaceSubclass::AudioControl as u8 => {
+                let request = uac_request_name(self.b_request)?;
+                let entity = entity_name(node, entity_id);
+                let is_feature_unit = node.children.iter().any(|child| matches!(&child.parsed, DescriptorTypes::UacFeatureUnit(fu) if fu.b_unit_id == entity_id));
+                if is_feature_unit {
+                    let control = FeatureUnitControlSelectors::from_u8(selector).map(feature_unit_selector_name).map(str::to_string)
+                        .unwrap_or_else(|| format!("selector {:#04x}", selector));
+                    return Some(format!("{} {} on {} channel {}", request, control, entity, self.w_value & 0xff));
+                }
+                Some(format!("{} selector {:#04x} on {}", request, selector, entity))
+            }
+            (LIBUSB_CLASS_APPLICATION, DFU_IFACE_SUBCLASS) => {
+                let request = match DfuRequestCodes::from_u8(self.b_request)? {
+                    DfuRequestCodes::Detach => "DFU_DETACH",
+                    DfuRequestCodes::Dnload => "DFU_DNLOAD",
+                    DfuRequestCodes::Upload => "DFU_UPLOAD",
+                    DfuRequestCodes::GetStatus => "DFU_GETSTATUS",
+                    DfuRequestCodes::ClrStatus => "DFU_CLRSTATUS",
+                    DfuRequestCodes::GetState => "DFU_GETSTATE",
+                    DfuRequestCodes::Abort => "DFU_ABORT",
+                };
+                Some(format!("{} on interface {}", request, iface_num))
+            }
+            _ => None,
+        }
+    }
+
+    fn describe_ep_request(&self, tree: &TreeNode) -> Option<String> {
+        let ep = (self.w_index & 0xff) as u8;
+        tree.get_node(|parsed| matches!(parsed, DescriptorTypes::UacEndpoint(uac_ep) if uac_ep.b_endpoint_address == ep))?;
+        let request = uac_request_name(self.b_request)?;
+        let control = match UacEndpointControlSelectors::from_u16(self.w_value >> 8) {
+            Some(UacEndpointControlSelectors::SamplingFreq) => "sampling frequency".to_string(),
+            Some(UacEndpointControlSelectors::Pitch) => "pitch".to_string(),
+            None => format!("selector {:#04x}", self.w_value >> 8),
+        };
+        Some(format!("{} {} on endpoint {:#04x}", request, control, ep))
+    }
+
+    /// `device`, `interface 1` or `endpoint 0x81`, from wIndex.
+    fn recipient(&self) -> String {
+        match self.recip() {
+            Recip::Dev => "device".to_string(),
+            Recip::Iface => format!("interface {}", self.w_index & 0xff),
+            Recip::Ep => format!("endpoint {:#04x}", self.w_index & 0xff),
+            Recip::Other => "other".to_string(),
+            recip => format!("recipient {}", recip as u8),
+        }
+    }
+
+    fn descriptor_type_name(&self) -> String {
+        let desc_type = (self.w_value >> 8) as u8;
+        let name = match UsbDescriptorTypes::from_u8(desc_type) {
+            Some(UsbDescriptorTypes::Device) => "DEVICE",
+            Some(UsbDescriptorTypes::Config) => "CONFIGURATION",
+            Some(UsbDescriptorTypes::String) => "STRING",
+            Some(UsbDescriptorTypes::DeviceQualifier) => "DEVICE_QUALIFIER",
+            Some(UsbDescriptorTypes::OtherSpeedConfig) => "OTHER_SPEED_CONFIGURATION",
+            Some(UsbDescriptorTypes::Bos) => "BOS",
+            // HID class descriptors are requested from the interface
+            Some(UsbDescriptorTypes::CsDevice) if self.recip() == Recip::Iface => "HID",
+            Some(UsbDescriptorTypes::CsConfig) if self.recip() == Recip::Iface => "REPORT",
+            _ => return format!("type {:#04x}", desc_type),
+        };
+        name.to_string()
+    }
+}
+
+fn uvc_request_name(b_request: u8) -> Option<&'static str> {
+    Some(match UvcRequestCodes::from_u8(b_request)? {
+        UvcRequestCodes::Undefined => return None,
+        UvcRequestCodes::SetCur => "SET_CUR",
+        UvcRequestCodes::GetCur => "GET_CUR",
+        UvcRequestCodes::GetMin => "GET_MIN",
+        UvcRequestCodes::GetMax => "GET_MAX",
+        UvcRequestCodes::GetRes => "GET_RES",
+        UvcRequestCodes::GetLen => "GET_LEN",
+        UvcRequestCodes::GetInfo => "GET_INFO",
+        UvcRequestCodes::GetDef => "GET_DEF",
+    })
+}
+
+fn uac_request_name(b_request: u8) -> Option<&'static str> {
+    Some(match UacRequestCodes::from_u8(b_request)? {
+        UacRequestCodes::Undefined => return None,
+        UacRequestCodes::SetCur => "SET_CUR",
+        UacRequestCodes::GetCur => "GET_CUR",
+        UacRequestCodes::GetMin => "GET_MIN",
+        UacRequestCodes::GetMax => "GET_MAX",
+        UacRequestCodes::GetResolution => "GET_RES",
+        UacRequestCodes::GetLen => "GET_LEN",
+        UacRequestCodes::GetInfo => "GET_INFO",
+        UacRequestCodes::GetDef => "GET_DEF",
+    })
+}
+
+fn uvc_vs_selector_name(selector: u8) -> Option<&'static str> {
+    Some(match UvcRequestTypes::from_u8(selector)? {
+        UvcRequestTypes::ControlUndefined => return None,
+        UvcRequestTypes::ProbeControl => "VS_PROBE_CONTROL",
+        UvcRequestTypes::CommitControl => "VS_COMMIT_CONTROL",
+        UvcRequestTypes::StillProbeControl => "VS_STILL_PROBE_CONTROL",
+        UvcRequestTypes::StillCommitControl => "VS_STILL_COMMIT_CONTROL",
+        UvcRequestTypes::StillImageTriggerControl => "VS_STILL_IMAGE_TRIGGER_CONTROL",
+        UvcRequestTypes::StreamErrorCodeControl => "VS_STREAM_ERROR_CODE_CONTROL",
+        UvcRequestTypes::GenerateKeyFrameControl => "VS_GENERATE_KEY_FRAME_CONTROL",
+        UvcRequestTypes::UpdateFrameSegmentControl => "VS_UPDATE_FRAME_SEGMENT_CONTROL",
+        UvcRequestTypes::SyncDelayControl => "VS_SYNC_DELAY_CONTROL",
+    })
+}
+
+fn uvc_vc_selector_name(selector: u8) -> Option<&'static str> {
+    Some(match UvcVcControlSelectors::from_u8(selector)? {
+        UvcVcControlSelectors::ControlUndefined => return None,
+        UvcVcControlSelectors::VideoPowerModeControl => "VC_VIDEO_POWER_MODE_CONTROL",
+        UvcVcControlSelectors::RequestErrorCodeControl => "VC_REQUEST_ERROR_CODE_CONTROL",
+    })
+}
+
+fn feature_unit_selector_name(selector: FeatureUnitControlSelectors) -> &'static str {
+    match selector {
+        FeatureUnitControlSelectors::UacFuMute => "mute",
+        FeatureUnitControlSelectors::UacFuVolume => "volume",
+        FeatureUnitControlSelectors::UacFuBass => "bass",
+        FeatureUnitControlSelectors::UacFuMid => "mid",
+        FeatureUnitControlSelectors::UacFuTreble => "treble",
+        FeatureUnitControlSelectors::UacFuGraphicEqualizer => "graphic equalizer",
+        FeatureUnitControlSelectors::UacFuAutomaticGain => "automatic gain",
+        FeatureUnitControlSelectors::UacFuDelay => "delay",
+        FeatureUnitControlSelectors::UacFuBassBoost => "bass boost",
+        FeatureUnitControlSelectors::UacFuLoudness => "loudness",
+    }
+}
+
+/// `processing unit 3`, `camera terminal 1`, ... for the unit or terminal with ID `id` among the
+/// class-specific descriptors of a VideoControl or AudioControl interface.
+fn entity_name(iface: &TreeNode, id: u8) -> String {
+    let kind = iface.children.iter().find_map(|child| match &child.parsed {
+        DescriptorTypes::UvcVcInputTerminal(it) if it.b_terminal_id == id =>
+            Some(if it.w_terminal_type == UVC_ITT_CAMERA { "camera terminal" } else { "input terminal" }),
+        DescriptorTypes::UvcVcOutputTerminal(ot) if ot.b_terminal_id == id => Some("output terminal"),
+        DescriptorTypes::UvcVcProcessingUnit(pu) if pu.b_unit_id == id => Some("processing unit"),
+        DescriptorTypes::UvcVcExtensionUnit(xu) if xu.b_unit_id == id => Some("extension unit"),
+        DescriptorTypes::UacInputTerminal(it) if it.b_terminal_id == id => Some("input terminal"),
+        DescriptorTypes::UacOutputTerminal(ot) if ot.b_terminal_id == id => Some("output terminal"),
+        DescriptorTypes::UacFeatureUnit(fu) if fu.b_unit_id == id => Some("feature unit"),
+        // selector, mixer, processing and encoding units are kept as bytes, led by their ID
+        DescriptorTypes::DescriptorUvcVcInterfaceUnknown(unit) if unit.bytes.first() == Some(&id) => Some("unit"),
+        DescriptorTypes::DescriptorUacInterfaceUnknown(unit) if unit.bytes.first() == Some(&id) => Some("unit"),
+        _ => None,
+    }).unwrap_or("unknown entity");
+    format!("{} {}", kind, id)
+}
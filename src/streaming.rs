@@ -0,0 +1,159 @@
+use anyhow::Error;
+use uuid::Uuid;
+
+use crate::TreeNode;
+use crate::repair::UvcIndexMap;
+use crate::usb_proto::DescriptorTypes;
+use crate::uvc_proto::UvcStreamingControl;
+use crate::validate::nearest;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Mjpeg,
+    Uncompressed(Uuid),
+    FrameBased(Uuid),
+}
+
+/// Frame intervals of a frame descriptor, in 100ns units.
+#[derive(Debug, Clone)]
+enum Intervals {
+    Discrete(Vec<u32>),
+    /// bFrameIntervalType 0: dwMinFrameInterval, dwMaxFrameInterval and dwFrameIntervalStep
+    Continuous { min: u32, max: u32, step: u32 },
+}
+
+impl Intervals {
+    fn nearest(&self, interval: u32) -> u32 {
+        match *self {
+            Intervals::Discrete(ref intervals) => nearest(interval, intervals),
+            Intervals::Continuous { min, max, step } => {
+                let clamped = interval.clamp(min, max.max(min));
+                if step == 0 {
+                    return clamped;
+                }
+                let mut steps = ((clamped - min) as u64 + step as u64 / 2) / step as u64;
+                if steps > 0 && min as u64 + steps * step as u64 > max as u64 {
+                    steps -= 1;
+                }
+                (min as u64 + steps * step as u64) as u32
+            }
+        }
+    }
+}
+
+/// A frame descriptor flattened together with its format and streaming interface.
+#[derive(Debug, Clone)]
+struct FrameEntry {
+    iface: u8,
+    format: u8,
+    frame: u8,
+    kind: FrameKind,
+    width: u16,
+    height: u16,
+    default_interval: u32,
+    intervals: Intervals,
+}
+
+/// Rewrites VS_PROBE_CONTROL/VS_COMMIT_CONTROL payloads between a host-facing descriptor tree
+/// (e.g. after `remove_high_fps`, `remove_h264` and `renumber_uvc_indices`) and the tree of the
+/// device the stream is forwarded to.
+///
+/// Frames are matched by format and resolution rather than by index, so the host tree may be
+/// filtered and renumbered in any way as long as interface numbers are kept.
+pub struct StreamingTranslator {
+    host: Vec<FrameEntry>,
+    device: Vec<FrameEntry>,
+    index_map: Option<UvcIndexMap>,
+}
+
+impl StreamingTranslator {
+    pub fn new(host: &TreeNode, device: &TreeNode) -> StreamingTranslator {
+        let mut translator = StreamingTranslator { host: vec![], device: vec![], index_map: None };
+        collect_frames(host, 0, &mut translator.host);
+        collect_frames(device, 0, &mut translator.device);
+        translator
+    }
+
+    /// Uses the mapping of the original indices of the host tree to its current ones to tell
+    /// formats of the same kind apart, e.g. two MJPEG formats on one interface. That is the map
+    /// `renumber_uvc_indices` returns when run on the filtered host tree, or the `index_map` of a
+    /// `VideoFilterReport` or `PolicyReport`; once a tree is renumbered, later calls only return
+    /// an identity map.
+    pub fn with_index_map(mut self, index_map: UvcIndexMap) -> StreamingTranslator {
+        self.index_map = Some(index_map);
+        self
+    }
+
+    /// Translates a request from the host (SET_CUR) into one for the device.
+    pub fn to_device(&self, iface: u8, ctrl: &UvcStreamingControl) -> Result<UvcStreamingControl, Error> {
+        let format = self.index_map.as_ref().and_then(|map| map.format_to_old(iface, ctrl.b_format_index));
+        translate(&self.host, &self.device, iface, format, ctrl)
+    }
+
+    /// Translates a response from the device (GET_CUR/GET_MIN/GET_MAX/GET_DEF) into one for the
+    /// host, clamping the frame interval to the ones the host was offered.
+    pub fn to_host(&self, iface: u8, ctrl: &UvcStreamingControl) -> Result<UvcStreamingControl, Error> {
+        let format = self.index_map.as_ref().and_then(|map| map.format_to_new(iface, ctrl.b_format_index));
+        translate(&self.device, &self.host, iface, format, ctrl)
+    }
+}
+
+fn translate(from: &[FrameEntry], to: &[FrameEntry], iface: u8, format: Option<u8>, ctrl: &UvcStreamingControl) -> Result<UvcStreamingControl, Error> {
+    // GET_MIN/GET_MAX responses and partially filled probes may leave the indices at zero
+    if ctrl.b_format_index == 0 || ctrl.b_frame_index == 0 {
+        return Ok(*ctrl);
+    }
+    let src = from.iter()
+        .find(|entry| entry.iface == iface && entry.format == ctrl.b_format_index && entry.frame == ctrl.b_frame_index)
+        .ok_or(anyhow!("No frame {} in format {} on interface {}", ctrl.b_frame_index, ctrl.b_format_index, iface))?;
+    let dst = to.iter()
+        .filter(|entry| format.is_none() || format == Some(entry.format))
+        .find(|entry| entry.iface == iface && entry.kind == src.kind && entry.width == src.width && entry.height == src.height)
+        .ok_or(anyhow!("No {:?} {}x{} frame on interface {} to translate to", src.kind, src.width, src.height, iface))?;
+
+    let mut out = *ctrl;
+    out.b_format_index = dst.format;
+    out.b_frame_index = dst.frame;
+    out.dw_frame_interval = match ctrl.dw_frame_interval {
+        0 => dst.default_interval,
+        interval => dst.intervals.nearest(interval),
+    };
+    Ok(out)
+}
+
+fn collect_frames(node: &TreeNode, iface: u8, out: &mut Vec<FrameEntry>) {
+    let iface = match &node.parsed {
+        DescriptorTypes::Interface(desc) => desc.b_interface_number,
+        _ => iface,
+    };
+    let format = match &node.parsed {
+        DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) => Some((fmt.b_format_index, FrameKind::Mjpeg)),
+        DescriptorTypes::DescriptorUvcFormatUncompressed(fmt) => Some((fmt.b_format_index, FrameKind::Uncompressed(fmt.guid_format))),
+        DescriptorTypes::UvcFormatFrameBased(fmt) => Some((fmt.b_format_index, FrameKind::FrameBased(fmt.guid_format))),
+        _ => None,
+    };
+    if let Some((format, kind)) = format {
+        for child in node.children.iter() {
+            let entry = match (&kind, &child.parsed) {
+                (FrameKind::Mjpeg, DescriptorTypes::DescriptorUvcFrameMjpeg(frame)) =>
+                    (frame.b_frame_index, frame.w_width, frame.w_height, frame.dw_default_frame_interval, frame.dw_frame_interval.clone()),
+                (FrameKind::Uncompressed(_), DescriptorTypes::DescriptorUvcFrameUncompressed(frame)) =>
+                    (frame.b_frame_index, frame.w_width, frame.w_height, frame.dw_default_frame_interval, frame.dw_frame_interval.clone()),
+                (FrameKind::FrameBased(_), DescriptorTypes::UvcFrameFrameBased(frame)) =>
+                    (frame.b_frame_index, frame.w_width, frame.w_height, frame.dw_default_frame_interval, frame.dw_frame_interval.clone()),
+                _ => continue,
+            };
+            let (frame, width, height, default_interval, intervals) = entry;
+            // the parser leaves the min/max/step of continuous frames as extra bytes
+            let intervals = if intervals.is_empty() && child.extra.len() >= 12 {
+                let read = |at: usize| u32::from_le_bytes(child.extra[at..at + 4].try_into().unwrap());
+                Intervals::Continuous { min: read(0), max: read(4), step: read(8) }
+            } else {
+                Intervals::Discrete(intervals)
+            };
+            out.push(FrameEntry { iface, format, frame, kind, width, height, default_interval, intervals });
+        }
+        return;
+    }
+    node.children.iter().for_each(|child| collect_frames(child, iface, out));
+}
@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::Error;
+use uuid::Uuid;
+
+use crate::TreeNode;
+use crate::diff::debug_fields;
+use crate::dfu_proto::DescriptorDfuFunctional;
+use crate::uac_proto::{DescriptorUacFormatTypeUnknown, DescriptorUacInterfaceUnknown, Uac1AcHeaderDescriptor, Uac1AsHeaderDescriptor, Uac1OutputTerminalDescriptor, UacFeatureUnitDescriptor, UacFormatTypeIContinuousDescriptor, UacInputTerminalDescriptor, UacIsoEndpointDescriptor};
+use crate::usb_proto::{DescriptorConfig, DescriptorCsDevice, DescriptorCsEndpoint, DescriptorCsInterface, DescriptorDevice, DescriptorEndpoint, DescriptorInterface, DescriptorTypes, DescriptorUnknown, UacDescriptorEndpoint, UsbInterfaceAssocDescriptor, UsbSsEpCompDescriptor, UsbSspIsochEpCompDescriptor};
+use crate::uvc_proto::{DescriptorUvcFormatFrameBased, DescriptorUvcFormatMjpeg, DescriptorUvcFormatUncompressed, DescriptorUvcFrameFrameBased, DescriptorUvcFrameMjpeg, DescriptorUvcFrameUncompressed, DescriptorUvcInputHeader, DescriptorUvcVcInterfaceUnknown, DescriptorUvcVsInterfaceUnknown, UvcExtensionUnitDescriptor, UvcHeaderDescriptor, UvcInputTerminalDescriptor, UvcOutputTerminalDescriptor, UvcProcessingUnitDescriptor};
+
+impl FromStr for TreeNode {
+    type Err = Error;
+
+    /// Parses the tab-indented `Display` output of a tree, so `.txt` fixtures can be edited by hand
+    /// and serialized to binaries. Every field has to be present; custom descriptors can not be
+    /// parsed since only their decoder knows their layout.
+    fn from_str(text: &str) -> Result<TreeNode, Error> {
+        // the nodes from the root to the last one read; a node is added to its parent once a line
+        // at its depth or above shows that it has no more children
+        let mut stack: Vec<TreeNode> = vec![];
+        for (idx, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let depth = line.len() - line.trim_start_matches('\t').len();
+            if stack.is_empty() != (depth == 0) || depth > stack.len() {
+                return Err(anyhow!("Line {}: unexpected indentation", idx + 1));
+            }
+            let (line, extra) = split_extra(line.trim()).map_err(|e| anyhow!("Line {}: {}", idx + 1, e))?;
+            let parsed = parse_descriptor(line).map_err(|e| anyhow!("Line {}: {}", idx + 1, e))?;
+            close_nodes(&mut stack, depth);
+            stack.push(TreeNode { children: vec![], parsed, extra });
+        }
+        close_nodes(&mut stack, 1);
+        stack.pop().ok_or_else(|| anyhow!("No descriptors found"))
+    }
+}
+
+fn close_nodes(stack: &mut Vec<TreeNode>, depth: usize) {
+    while stack.len() > depth {
+        let node = stack.pop().unwrap();
+        stack.last_mut().unwrap().children.push(node);
+    }
+}
+
+/// Splits the ` extra: [..]` suffix `Display` writes after descriptors with trailing bytes.
+fn split_extra(line: &str) -> Result<(&str, Vec<u8>), Error> {
+    match line.rsplit_once(") extra: ") {
+        Some((descriptor, extra)) => {
+            let extra = Fields(HashMap::from([("extra", extra)])).list("extra")?;
+            Ok((&line[..descriptor.len() + 1], extra))
+        }
+        None => Ok((line, vec![])),
+    }
+}
+
+/// Parses one `Variant(Struct { field: value, ... })` line.
+fn parse_descriptor(line: &str) -> Result<DescriptorTypes, Error> {
+    if line == "Root" || line == "Root()" {
+        return Ok(DescriptorTypes::Root());
+    }
+    let (name, fields) = debug_fields(line).ok_or_else(|| anyhow!("Could not parse {:?}", line))?;
+    let variant = name.split('(').next().unwrap_or(name);
+    let mut f = Fields(fields.into_iter().collect());
+    let parsed = match variant {
+        "Unknown" => DescriptorTypes::Unknown(DescriptorUnknown { desc_type: f.num("desc_type")?, bytes: f.list("bytes")? }),
+        "Device" => DescriptorTypes::Device(DescriptorDevice {
+            bcd_usb: f.num("bcd_usb")?,
+            b_device_class: f.num("b_device_class")?,
+            b_device_sub_class: f.num("b_device_sub_class")?,
+            b_device_protocol: f.num("b_device_protocol")?,
+            b_max_packet_size0: f.num("b_max_packet_size0")?,
+            id_vendor: f.num("id_vendor")?,
+            id_product: f.num("id_product")?,
+            bcd_device: f.num("bcd_device")?,
+            i_manufacturer: f.num("i_manufacturer")?,
+            i_product: f.num("i_product")?,
+            i_serial_number: f.num("i_serial_number")?,
+            b_num_configurations: f.num("b_num_configurations")?,
+        }),
+        "CsDevice" => DescriptorTypes::CsDevice(DescriptorCsDevice { bytes: f.list("bytes")? }),
+        "DfuFunctional" => DescriptorTypes::DfuFunctional(DescriptorDfuFunctional {
+            bm_attributes: f.num("bm_attributes")?,
+            w_detach_time_out: f.num("w_detach_time_out")?,
+            w_transfer_size: f.num("w_transfer_size")?,
+            bcd_dfu_version: f.opt_num("bcd_dfu_version")?,
+        }),
+        "Config" => DescriptorTypes::Config(DescriptorConfig {
+            w_total_length: f.num("w_total_length")?,
+            b_num_interfaces: f.num("b_num_interfaces")?,
+            b_configuration_value: f.num("b_configuration_value")?,
+            i_configuration: f.num("i_configuration")?,
+            bm_attributes: f.num("bm_attributes")?,
+            b_max_power: f.num("b_max_power")?,
+        }),
+        "Interface" => DescriptorTypes::Interface(DescriptorInterface {
+            b_interface_number: f.num("b_interface_number")?,
+            b_alternate_setting: f.num("b_alternate_setting")?,
+            b_num_endpoints: f.num("b_num_endpoints")?,
+            b_interface_class: f.num("b_interface_class")?,
+            b_interface_sub_class: f.num("b_interface_sub_class")?,
+            b_interface_protocol: f.num("b_interface_protocol")?,
+            i_interface: f.num("i_interface")?,
+        }),
+        "CsInterface" => DescriptorTypes::CsInterface(DescriptorCsInterface { bytes: f.list("bytes")? }),
+        "InterfaceAssociation" => DescriptorTypes::InterfaceAssociation(UsbInterfaceAssocDescriptor {
+            b_first_interface: f.num("b_first_interface")?,
+            b_interface_count: f.num("b_interface_count")?,
+            b_function_class: f.num("b_function_class")?,
+            b_function_sub_class: f.num("b_function_sub_class")?,
+            b_function_protocol: f.num("b_function_protocol")?,
+            i_function: f.num("i_function")?,
+        }),
+        "Endpoint" => DescriptorTypes::Endpoint(endpoint(&mut f)?),
+        "HidEndpoint" => DescriptorTypes::HidEndpoint(endpoint(&mut f)?),
+        "UacEndpoint" => DescriptorTypes::UacEndpoint(UacDescriptorEndpoint {
+            b_endpoint_address: f.num("b_endpoint_address")?,
+            bm_attributes: f.num("bm_attributes")?,
+            w_max_packet_size: f.num("w_max_packet_size")?,
+            b_interval: f.num("b_interval")?,
+            b_refresh: f.num("b_refresh")?,
+            b_synch_address: f.num("b_synch_address")?,
+        }),
+        "SsEpComp" => DescriptorTypes::SsEpComp(UsbSsEpCompDescriptor {
+            b_max_burst: f.num("b_max_burst")?,
+            bm_attributes: f.num("bm_attributes")?,
+            w_bytes_per_interval: f.num("w_bytes_per_interval")?,
+        }),
+        "SspIsochEpComp" => DescriptorTypes::SspIsochEpComp(UsbSspIsochEpCompDescriptor {
+            w_reserved: f.num("w_reserved")?,
+            dw_bytes_per_interval: f.num("dw_bytes_per_interval")?,
+        }),
+        "CsEndpoint" => DescriptorTypes::CsEndpoint(DescriptorCsEndpoint { bytes: f.list("bytes")? }),
+        "UvcInputHeader" => DescriptorTypes::UvcInputHeader(DescriptorUvcInputHeader {
+            w_total_length: f.num("w_total_length")?,
+            b_endpoint_address: f.num("b_endpoint_address")?,
+            bm_info: f.num("bm_info")?,
+            b_terminal_link: f.num("b_terminal_link")?,
+            b_still_capture_method: f.num("b_still_capture_method")?,
+            b_trigger_support: f.num("b_trigger_support")?,
+            b_trigger_usage: f.num("b_trigger_usage")?,
+            b_control_size: f.num("b_control_size")?,
+            bma_controls: f.list("bma_controls")?,
+        }),
+        "UacAcHeader" => DescriptorTypes::UacAcHeader(Uac1AcHeaderDescriptor {
+            bcd_adc: f.num("bcd_adc")?,
+            w_total_length: f.num("w_total_length")?,
+            b_in_collection: f.num("b_in_collection")?,
+            ba_interface_nr: f.list("ba_interface_nr")?,
+        }),
+        "UacInputTerminal" => DescriptorTypes::UacInputTerminal(UacInputTerminalDescriptor {
+            b_terminal_id: f.num("b_terminal_id")?,
+            w_terminal_type: f.num("w_terminal_type")?,
+            b_assoc_terminal: f.num("b_assoc_terminal")?,
+            b_nr_channels: f.num("b_nr_channels")?,
+            w_channel_config: f.num("w_channel_config")?,
+            i_channel_names: f.num("i_channel_names")?,
+            i_terminal: f.num("i_terminal")?,
+        }),
+        "UacOutputTerminal" => DescriptorTypes::UacOutputTerminal(Uac1OutputTerminalDescriptor {
+            b_terminal_id: f.num("b_terminal_id")?,
+            w_terminal_type: f.num("w_terminal_type")?,
+            b_assoc_terminal: f.num("b_assoc_terminal")?,
+            b_source_id: f.num("b_source_id")?,
+            i_terminal: f.num("i_terminal")?,
+        }),
+        "UacFeatureUnit" => DescriptorTypes::UacFeatureUnit(UacFeatureUnitDescriptor {
+            b_unit_id: f.num("b_unit_id")?,
+            b_source_id: f.num("b_source_id")?,
+            b_control_size: f.num("b_control_size")?,
+            bma_controls: f.list("bma_controls")?,
+        }),
+        "UacAsGeneral" => DescriptorTypes::UacAsGeneral(Uac1AsHeaderDescriptor {
+            b_terminal_link: f.num("b_terminal_link")?,
+            b_delay: f.num("b_delay")?,
+            w_format_tag: f.num("w_format_tag")?,
+        }),
+        "UacFormatTypeI" => DescriptorTypes::UacFormatTypeI(UacFormatTypeIContinuousDescriptor {
+            b_nr_channels: f.num("b_nr_channels")?,
+            b_subframe_size: f.num("b_subframe_size")?,
+            b_bit_resolution: f.num("b_bit_resolution")?,
+            b_sam_freq_type: f.num("b_sam_freq_type")?,
+            t_sam_freq: f.list("t_sam_freq")?,
+        }),
+        "UacFormatTypeUnknown" => DescriptorTypes::UacFormatTypeUnknown(DescriptorUacFormatTypeUnknown { format_type: f.num("format_type")?, bytes: f.list("bytes")? }),
+        "UacIsoEndpointDescriptor" => DescriptorTypes::UacIsoEndpointDescriptor(UacIsoEndpointDescriptor {
+            b_descriptor_subtype: f.num("b_descriptor_subtype")?,
+            bm_attributes: f.num("bm_attributes")?,
+            b_lock_delay_units: f.num("b_lock_delay_units")?,
+            w_lock_delay: f.num("w_lock_delay")?,
+        }),
+        "DescriptorUvcFormatUncompressed" => DescriptorTypes::DescriptorUvcFormatUncompressed(DescriptorUvcFormatUncompressed {
+            b_format_index: f.num("b_format_index")?,
+            b_num_frame_descriptors: f.num("b_num_frame_descriptors")?,
+            guid_format: f.uuid("guid_format")?,
+            b_bits_per_pixel: f.num("b_bits_per_pixel")?,
+            b_default_frame_index: f.num("b_default_frame_index")?,
+            b_aspect_ratio_x: f.num("b_aspect_ratio_x")?,
+            b_aspect_ratio_y: f.num("b_aspect_ratio_y")?,
+            bm_interface_flags: f.num("bm_interface_flags")?,
+            b_copy_protect: f.num("b_copy_protect")?,
+        }),
+        "DescriptorUvcFormatMjpeg" => DescriptorTypes::DescriptorUvcFormatMjpeg(DescriptorUvcFormatMjpeg {
+            b_format_index: f.num("b_format_index")?,
+            b_num_frame_descriptors: f.num("b_num_frame_descriptors")?,
+            bm_flags: f.num("bm_flags")?,
+            b_default_frame_index: f.num("b_default_frame_index")?,
+            b_aspect_ratio_x: f.num("b_aspect_ratio_x")?,
+            b_aspect_ratio_y: f.num("b_aspect_ratio_y")?,
+            bm_interface_flags: f.num("bm_interface_flags")?,
+            b_copy_protect: f.num("b_copy_protect")?,
+        }),
+        "DescriptorUvcFrameUncompressed" => DescriptorTypes::DescriptorUvcFrameUncompressed(DescriptorUvcFrameUncompressed {
+            b_frame_index: f.num("b_frame_index")?,
+            bm_capabilities: f.num("bm_capabilities")?,
+            w_width: f.num("w_width")?,
+            w_height: f.num("w_height")?,
+            dw_min_bit_rate: f.num("dw_min_bit_rate")?,
+            dw_max_bit_rate: f.num("dw_max_bit_rate")?,
+            dw_max_video_frame_buffer_size: f.num("dw_max_video_frame_buffer_size")?,
+            dw_default_frame_interval: f.num("dw_default_frame_interval")?,
+            dw_frame_interval: f.list("dw_frame_interval")?,
+        }),
+        "DescriptorUvcFrameMjpeg" => DescriptorTypes::DescriptorUvcFrameMjpeg(DescriptorUvcFrameMjpeg {
+            b_frame_index: f.num("b_frame_index")?,
+            bm_capabilities: f.num("bm_capabilities")?,
+            w_width: f.num("w_width")?,
+            w_height: f.num("w_height")?,
+            dw_min_bit_rate: f.num("dw_min_bit_rate")?,
+            dw_max_bit_rate: f.num("dw_max_bit_rate")?,
+            dw_max_video_frame_buffer_size: f.num("dw_max_video_frame_buffer_size")?,
+            dw_default_frame_interval: f.num("dw_default_frame_interval")?,
+            dw_frame_interval: f.list("dw_frame_interval")?,
+        }),
+        "UvcFormatFrameBased" => DescriptorTypes::UvcFormatFrameBased(DescriptorUvcFormatFrameBased {
+            b_format_index: f.num("b_format_index")?,
+            b_num_frame_descriptors: f.num("b_num_frame_descriptors")?,
+            guid_format: f.uuid("guid_format")?,
+            b_bits_per_pixel: f.num("b_bits_per_pixel")?,
+            b_default_frame_index: f.num("b_default_frame_index")?,
+            b_aspect_ratio_x: f.num("b_aspect_ratio_x")?,
+            b_aspect_ratio_y: f.num("b_aspect_ratio_y")?,
+            bm_interface_flags: f.num("bm_interface_flags")?,
+            b_copy_protect: f.num("b_copy_protect")?,
+            b_variable_size: f.num("b_variable_size")?,
+        }),
+        "UvcFrameFrameBased" => DescriptorTypes::UvcFrameFrameBased(DescriptorUvcFrameFrameBased {
+            b_frame_index: f.num("b_frame_index")?,
+            bm_capabilities: f.num("bm_capabilities")?,
+            w_width: f.num("w_width")?,
+            w_height: f.num("w_height")?,
+            dw_min_bit_rate: f.num("dw_min_bit_rate")?,
+            dw_max_bit_rate: f.num("dw_max_bit_rate")?,
+            dw_default_frame_interval: f.num("dw_default_frame_interval")?,
+            dw_bytes_per_line: f.num("dw_bytes_per_line")?,
+            dw_frame_interval: f.list("dw_frame_interval")?,
+        }),
+        "DescriptorUvcVsInterfaceUnknown" => DescriptorTypes::DescriptorUvcVsInterfaceUnknown(DescriptorUvcVsInterfaceUnknown { iface_subclass: f.num("iface_subclass")?, bytes: f.list("bytes")? }),
+        "DescriptorUvcVcInterfaceUnknown" => DescriptorTypes::DescriptorUvcVcInterfaceUnknown(DescriptorUvcVcInterfaceUnknown { iface_subclass: f.num("iface_subclass")?, bytes: f.list("bytes")? }),
+        "DescriptorUacInterfaceUnknown" => DescriptorTypes::DescriptorUacInterfaceUnknown(DescriptorUacInterfaceUnknown { iface_subclass: f.num("iface_subclass")?, bytes: f.list("bytes")? }),
+        "UvcHeaderDescriptor" => DescriptorTypes::UvcHeaderDescriptor(UvcHeaderDescriptor {
+            bcd_uvc: f.num("bcd_uvc")?,
+            w_total_length: f.num("w_total_length")?,
+            dw_clock_frequency: f.num("dw_clock_frequency")?,
+            b_in_collection: f.num("b_in_collection")?,
+            ba_interface_nr: f.list("ba_interface_nr")?,
+        }),
+        "UvcVcInputTerminal" => DescriptorTypes::UvcVcInputTerminal(UvcInputTerminalDescriptor {
+            b_terminal_id: f.num("b_terminal_id")?,
+            w_terminal_type: f.num("w_terminal_type")?,
+            b_assoc_terminal: f.num("b_assoc_terminal")?,
+            i_terminal: f.num("i_terminal")?,
+            xtra: f.list("xtra")?,
+        }),
+        "UvcVcProcessingUnit" => DescriptorTypes::UvcVcProcessingUnit(UvcProcessingUnitDescriptor {
+            b_unit_id: f.num("b_unit_id")?,
+            b_source_id: f.num("b_source_id")?,
+            w_max_multiplier: f.num("w_max_multiplier")?,
+            b_control_size: f.num("b_control_size")?,
+            bm_controls: f.num("bm_controls")?,
+            i_processing: f.num("i_processing")?,
+            xtra: f.list("xtra")?,
+        }),
+        "UvcVcExtensionUnit" => DescriptorTypes::UvcVcExtensionUnit(UvcExtensionUnitDescriptor {
+            b_unit_id: f.num("b_unit_id")?,
+            guid_extension_code: f.uuid("guid_extension_code")?,
+            b_num_controls: f.num("b_num_controls")?,
+            b_nr_in_pins: f.num("b_nr_in_pins")?,
+            ba_source_id: f.list("ba_source_id")?,
+            b_control_size: f.num("b_control_size")?,
+            bm_controls: f.list("bm_controls")?,
+            i_extension: f.num("i_extension")?,
+        }),
+        "UvcVcOutputTerminal" => DescriptorTypes::UvcVcOutputTerminal(UvcOutputTerminalDescriptor {
+            b_terminal_id: f.num("b_terminal_id")?,
+            w_terminal_type: f.num("w_terminal_type")?,
+            b_assoc_terminal: f.num("b_assoc_terminal")?,
+            b_source_id: f.num("b_source_id")?,
+            i_terminal: f.num("i_terminal")?,
+        }),
+        "Custom" => return Err(anyhow!("Custom descriptors can not be parsed, use their binary form")),
+        other => return Err(anyhow!("Unknown descriptor {}", other)),
+    };
+    f.finish()?;
+    Ok(parsed)
+}
+
+fn endpoint(f: &mut Fields) -> Result<DescriptorEndpoint, Error> {
+    Ok(DescriptorEndpoint {
+        b_endpoint_address: f.num("b_endpoint_address")?,
+        bm_attributes: f.num("bm_attributes")?,
+        w_max_packet_size: f.num("w_max_packet_size")?,
+        b_interval: f.num("b_interval")?,
+    })
+}
+
+/// The fields of one descriptor, removed as they are read so leftovers can be reported.
+struct Fields<'a>(HashMap<&'a str, &'a str>);
+
+impl<'a> Fields<'a> {
+    fn value(&mut self, name: &str) -> Result<&'a str, Error> {
+        self.0.remove(name).ok_or_else(|| anyhow!("Missing field {}", name))
+    }
+
+    fn num<T: FromStr>(&mut self, name: &str) -> Result<T, Error> {
+        let value = self.value(name)?;
+        value.parse().map_err(|_| anyhow!("Invalid value {} for {}", value, name))
+    }
+
+    /// An `Option` as Debug prints it, `None` or `Some(value)`.
+    fn opt_num<T: FromStr>(&mut self, name: &str) -> Result<Option<T>, Error> {
+        let value = self.value(name)?;
+        if value == "None" {
+            return Ok(None);
+        }
+        let inner = value.strip_prefix("Some(").and_then(|value| value.strip_suffix(')'))
+            .ok_or_else(|| anyhow!("Invalid option {} for {}", value, name))?;
+        inner.parse().map(Some).map_err(|_| anyhow!("Invalid value {} for {}", inner, name))
+    }
+
+    fn list<T: FromStr>(&mut self, name: &str) -> Result<Vec<T>, Error> {
+        let value = self.value(name)?;
+        let items = value.strip_prefix('[').and_then(|value| value.strip_suffix(']'))
+            .ok_or_else(|| anyhow!("Invalid list {} for {}", value, name))?;
+        items.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| item.parse().map_err(|_| anyhow!("Invalid value {} in {}", item, name)))
+            .collect()
+    }
+
+    fn uuid(&mut self, name: &str) -> Result<Uuid, Error> {
+        let value = self.value(name)?;
+        Uuid::parse_str(value).map_err(|_| anyhow!("Invalid GUID {} for {}", value, name))
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        match self.0.keys().next() {
+            Some(name) => Err(anyhow!("Unknown field {}", name)),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::io::Write;
+
+use anyhow::Error;
+use libusb1_sys::constants::LIBUSB_CLASS_AUDIO;
+use num_traits::FromPrimitive;
+
+use crate::TreeNode;
+use crate::uac_proto::{FeatureUnitControlSelectors, Uac2RequestCodes, UacEndpointControlSelectors, UacInterfaceSubclass, UacRequestCodes, UacVolume};
+use crate::usb_proto::{DescriptorTypes, Recip, SetupPacket, XferDir, XferType};
+
+// 5.2.2.4.3.7 a graphic equalizer has at most 31 bands
+const GRAPHIC_EQUALIZER_BANDS: u16 = 31;
+// 5.2.3.2.3.1 the sampling frequency is a 3 byte parameter block
+const SAMPLING_FREQ_LEN: u16 = 3;
+// bmAttributes D0 of the class-specific isochronous endpoint descriptor
+const SAMPLING_FREQ_SUPPORTED: u8 = 1 << 0;
+
+/// One control of one channel of a Feature Unit; channel 0 is the master channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureUnitControl {
+    /// The AudioControl interface the unit belongs to
+    pub iface: u8,
+    pub unit_id: u8,
+    pub selector: FeatureUnitControlSelectors,
+    pub channel: u8,
+}
+
+impl FeatureUnitControl {
+    /// Every control the feature units of `tree` advertise in bmaControls, by unit, channel and
+    /// selector.
+    pub fn find_all(tree: &TreeNode) -> Vec<FeatureUnitControl> {
+        let mut controls = vec![];
+        collect_feature_unit_controls(tree, &mut controls);
+        controls
+    }
+
+    /// The control `selector` of `channel` if the unit `unit_id` advertises it.
+    pub fn find(tree: &TreeNode, unit_id: u8, selector: FeatureUnitControlSelectors, channel: u8) -> Option<FeatureUnitControl> {
+        FeatureUnitControl::find_all(tree).into_iter()
+            .find(|control| control.unit_id == unit_id && control.selector == selector && control.channel == channel)
+    }
+
+    /// wLength of the control's parameter block. A graphic equalizer's block depends on the bands
+    /// present, so the largest one is given.
+    pub fn param_len(&self) -> u16 {
+        match self.selector {
+            FeatureUnitControlSelectors::UacFuVolume | FeatureUnitControlSelectors::UacFuDelay => 2,
+            FeatureUnitControlSelectors::UacFuGraphicEqualizer => 4 + GRAPHIC_EQUALIZER_BANDS,
+            _ => 1,
+        }
+    }
+
+    /// A UAC1 request for the control, e.g. GET_MIN of the volume; wValue is the selector and
+    /// channel, wIndex the unit and interface.
+    pub fn request(&self, code: UacRequestCodes) -> SetupPacket {
+        SetupPacket::new(uac1_dir(code), XferType::Class, Recip::Iface, code as u8, self.w_value(), self.w_index(), self.param_len())
+    }
+
+    /// SET_CUR with the parameter block of `value`, which has to be a value of this control.
+    pub fn set_cur(&self, value: &FeatureUnitValue) -> Result<(SetupPacket, Vec<u8>), Error> {
+        if value.selector() != self.selector {
+            return Err(anyhow!("{:?} is not a value of {:?}", value, self.selector));
+        }
+        let mut data = vec![];
+        value.serialize(&mut data);
+        let mut setup = self.request(UacRequestCodes::SetCur);
+        setup.w_length = data.len() as u16;
+        Ok((setup, data))
+    }
+
+    /// Decodes the response to a GET request of the control.
+    pub fn decode(&self, data: &[u8]) -> Result<FeatureUnitValue, Error> {
+        FeatureUnitValue::deserialize(self.selector, data)
+    }
+
+    /// A UAC2 CUR or RANGE request for the control; see `Uac2Range` for RANGE parameter blocks.
+    pub fn uac2_request(&self, code: Uac2RequestCodes, dir: XferDir, w_length: u16) -> SetupPacket {
+        SetupPacket::new(dir, XferType::Class, Recip::Iface, code as u8, self.w_value(), self.w_index(), w_length)
+    }
+
+    fn w_value(&self) -> u16 {
+        (self.selector as u16) << 8 | self.channel as u16
+    }
+
+    fn w_index(&self) -> u16 {
+        (self.unit_id as u16) << 8 | self.iface as u16
+    }
+}
+
+fn collect_feature_unit_controls(node: &TreeNode, controls: &mut Vec<FeatureUnitControl>) {
+    if let DescriptorTypes::Interface(iface) = &node.parsed {
+        if iface.b_interface_class == LIBUSB_CLASS_AUDIO && iface.b_interface_sub_class == UacInterfaceSubclass::AudioControl as u8 {
+            for child in node.children.iter() {
+                let DescriptorTypes::UacFeatureUnit(fu) = &child.parsed else { continue };
+                let size = fu.b_control_size as usize;
+                if size == 0 || fu.bma_controls.is_empty() {
+                    continue;
+                }
+                // bma_controls ends with iFeature
+                let channels = fu.bma_controls[..fu.bma_controls.len() - 1].chunks_exact(size);
+                for (channel, bitmap) in channels.enumerate() {
+                    let bits = bitmap.iter().rev().fold(0u32, |bits, byte| bits << 8 | *byte as u32);
+                    for bit in 0..(size * 8).min(32) {
+                        // bit n stands for selector n + 1
+                        let Some(selector) = FeatureUnitControlSelectors::from_usize(bit + 1) else { continue };
+                        if bits & (1 << bit) != 0 {
+                            controls.push(FeatureUnitControl { iface: iface.b_interface_number, unit_id: fu.b_unit_id, selector, channel: channel as u8 });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    node.children.iter().for_each(|child| collect_feature_unit_controls(child, controls));
+}
+
+/// The parameter block of a Feature Unit control, see 5.2.2.4.3 of the UAC1 specification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeatureUnitValue {
+    Mute(bool),
+    /// In 1/256 dB, see `UacVolume`
+    Volume(i16),
+    /// In 1/4 dB
+    Bass(i8),
+    Mid(i8),
+    Treble(i8),
+    /// bmBandsPresent and the gain of each band present, in 1/4 dB
+    GraphicEqualizer { bands: u32, gains: Vec<i8> },
+    AutomaticGain(bool),
+    /// In 1/64 ms
+    Delay(u16),
+    BassBoost(bool),
+    Loudness(bool),
+}
+
+impl FeatureUnitValue {
+    pub fn selector(&self) -> FeatureUnitControlSelectors {
+        match self {
+            FeatureUnitValue::Mute(_) => FeatureUnitControlSelectors::UacFuMute,
+            FeatureUnitValue::Volume(_) => FeatureUnitControlSelectors::UacFuVolume,
+            FeatureUnitValue::Bass(_) => FeatureUnitControlSelectors::UacFuBass,
+            FeatureUnitValue::Mid(_) => FeatureUnitControlSelectors::UacFuMid,
+            FeatureUnitValue::Treble(_) => FeatureUnitControlSelectors::UacFuTreble,
+            FeatureUnitValue::GraphicEqualizer { .. } => FeatureUnitControlSelectors::UacFuGraphicEqualizer,
+            FeatureUnitValue::AutomaticGain(_) => FeatureUnitControlSelectors::UacFuAutomaticGain,
+            FeatureUnitValue::Delay(_) => FeatureUnitControlSelectors::UacFuDelay,
+            FeatureUnitValue::BassBoost(_) => FeatureUnitControlSelectors::UacFuBassBoost,
+            FeatureUnitValue::Loudness(_) => FeatureUnitControlSelectors::UacFuLoudness,
+        }
+    }
+
+    pub fn serialize(&self, mut buffer: impl Write) {
+        let bytes = match self {
+            FeatureUnitValue::Mute(on) | FeatureUnitValue::AutomaticGain(on) |
+            FeatureUnitValue::BassBoost(on) | FeatureUnitValue::Loudness(on) => vec![*on as u8],
+            FeatureUnitValue::Volume(volume) => volume.to_le_bytes().to_vec(),
+            FeatureUnitValue::Bass(gain) | FeatureUnitValue::Mid(gain) | FeatureUnitValue::Treble(gain) => vec![*gain as u8],
+            FeatureUnitValue::GraphicEqualizer { bands, gains } => {
+                bands.to_le_bytes().iter().copied().chain(gains.iter().map(|gain| *gain as u8)).collect()
+            }
+            FeatureUnitValue::Delay(delay) => delay.to_le_bytes().to_vec(),
+        };
+        buffer.write_all(&bytes).unwrap();
+    }
+
+    pub fn deserialize(selector: FeatureUnitControlSelectors, buffer: &[u8]) -> Result<FeatureUnitValue, Error> {
+        let byte = || buffer.first().copied().ok_or_else(|| anyhow!("Empty parameter block for {:?}", selector));
+        let word = || match buffer {
+            [lo, hi, ..] => Ok(u16::from_le_bytes([*lo, *hi])),
+            _ => Err(anyhow!("Parameter block of {} bytes too short for {:?}", buffer.len(), selector)),
+        };
+        Ok(match selector {
+            FeatureUnitControlSelectors::UacFuMute => FeatureUnitValue::Mute(byte()? != 0),
+            FeatureUnitControlSelectors::UacFuVolume => FeatureUnitValue::Volume(word()? as i16),
+            FeatureUnitControlSelectors::UacFuBass => FeatureUnitValue::Bass(byte()? as i8),
+            FeatureUnitControlSelectors::UacFuMid => FeatureUnitValue::Mid(byte()? as i8),
+            FeatureUnitControlSelectors::UacFuTreble => FeatureUnitValue::Treble(byte()? as i8),
+            FeatureUnitControlSelectors::UacFuGraphicEqualizer => {
+                let bands = buffer.get(..4).ok_or_else(|| anyhow!("Graphic equalizer block of {} bytes has no bmBandsPresent", buffer.len()))?;
+                let bands = u32::from_le_bytes(bands.try_into().unwrap());
+                let gains: Vec<i8> = buffer[4..].iter().take(bands.count_ones() as usize).map(|gain| *gain as i8).collect();
+                if gains.len() != bands.count_ones() as usize {
+                    return Err(anyhow!("Graphic equalizer block has {} of {} band gains", gains.len(), bands.count_ones()));
+                }
+                FeatureUnitValue::GraphicEqualizer { bands, gains }
+            }
+            FeatureUnitControlSelectors::UacFuAutomaticGain => FeatureUnitValue::AutomaticGain(byte()? != 0),
+            FeatureUnitControlSelectors::UacFuDelay => FeatureUnitValue::Delay(word()?),
+            FeatureUnitControlSelectors::UacFuBassBoost => FeatureUnitValue::BassBoost(byte()? != 0),
+            FeatureUnitControlSelectors::UacFuLoudness => FeatureUnitValue::Loudness(byte()? != 0),
+        })
+    }
+}
+
+impl UacVolume {
+    /// Builds the volume from the responses to GET_MIN, GET_MAX and GET_CUR of a volume control.
+    pub fn from_responses(min: &[u8], max: &[u8], cur: &[u8]) -> Result<UacVolume, Error> {
+        let volume = |data: &[u8]| match data {
+            [lo, hi, ..] => Ok(i16::from_le_bytes([*lo, *hi])),
+            _ => Err(anyhow!("Volume of {} bytes, 2 expected", data.len())),
+        };
+        UacVolume::new(volume(min)?, volume(max)?, volume(cur)?)
+    }
+
+    /// Builds the volume from the first subrange of a UAC2 RANGE response and the CUR value.
+    pub fn from_uac2_range(ranges: &[Uac2Range<i16>], cur: i16) -> Result<UacVolume, Error> {
+        let range = ranges.first().ok_or_else(|| anyhow!("Volume RANGE response without subranges"))?;
+        UacVolume::new(range.min, range.max, cur)
+    }
+}
+
+/// The UAC1 SAMPLING_FREQ_CONTROL of an isochronous audio endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplingFreqControl {
+    pub ep: u8,
+}
+
+impl SamplingFreqControl {
+    /// The endpoints of `tree` whose class-specific descriptor advertises the control.
+    pub fn find_all(tree: &TreeNode) -> Vec<SamplingFreqControl> {
+        let mut controls = vec![];
+        collect_sampling_freq_controls(tree, &mut controls);
+        controls
+    }
+
+    pub fn request(&self, code: UacRequestCodes) -> SetupPacket {
+        let w_value = (UacEndpointControlSelectors::SamplingFreq as u16) << 8;
+        SetupPacket::new(uac1_dir(code), XferType::Class, Recip::Ep, code as u8, w_value, self.ep as u16, SAMPLING_FREQ_LEN)
+    }
+
+    /// SET_CUR of `freq` Hz.
+    pub fn set_cur(&self, freq: u32) -> (SetupPacket, Vec<u8>) {
+        (self.request(UacRequestCodes::SetCur), freq.to_le_bytes()[..SAMPLING_FREQ_LEN as usize].to_vec())
+    }
+
+    /// The frequency in Hz of a GET response.
+    pub fn decode(data: &[u8]) -> Result<u32, Error> {
+        match data {
+            [b0, b1, b2, ..] => Ok(u32::from_le_bytes([*b0, *b1, *b2, 0])),
+            _ => Err(anyhow!("Sampling frequency of {} bytes, 3 expected", data.len())),
+        }
+    }
+}
+
+fn collect_sampling_freq_controls(node: &TreeNode, controls: &mut Vec<SamplingFreqControl>) {
+    // the class-specific endpoint descriptor follows its endpoint
+    for pair in node.children.windows(2) {
+        if let (DescriptorTypes::UacEndpoint(ep), DescriptorTypes::UacIsoEndpointDescriptor(cs)) = (&pair[0].parsed, &pair[1].parsed) {
+            let control = SamplingFreqControl { ep: ep.b_endpoint_address };
+            if cs.bm_attributes & SAMPLING_FREQ_SUPPORTED != 0 && !controls.contains(&control) {
+                controls.push(control);
+            }
+        }
+    }
+    node.children.iter().for_each(|child| collect_sampling_freq_controls(child, controls));
+}
+
+fn uac1_dir(code: UacRequestCodes) -> XferDir {
+    // GET requests have D7 set
+    if code as u8 & 0x80 != 0 { XferDir::ToHost } else { XferDir::ToDev }
+}
+
+/// A value of a UAC2 parameter block: layout 1 uses 1 byte values, layout 2 uses 2 and layout 3
+/// uses 4, signed or not depending on the control.
+pub trait RangeValue: Copy {
+    const LEN: usize;
+    fn read(bytes: &[u8]) -> Self;
+    fn write(self, buffer: &mut Vec<u8>);
+}
+
+macro_rules! range_value {
+    ($($t:ty),*) => {$(
+        impl RangeValue for $t {
+            const LEN: usize = std::mem::size_of::<$t>();
+            fn read(bytes: &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes.try_into().unwrap())
+            }
+            fn write(self, buffer: &mut Vec<u8>) {
+                buffer.extend_from_slice(&self.to_le_bytes());
+            }
+        }
+    )*};
+}
+
+range_value!(u8, i8, u16, i16, u32, i32);
+
+/// One subrange of a UAC2 RANGE parameter block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Uac2Range<T> {
+    pub min: T,
+    pub max: T,
+    pub res: T,
+}
+
+impl<T: RangeValue> Uac2Range<T> {
+    /// Parses a RANGE parameter block: wNumSubRanges followed by as many MIN, MAX and RES triplets.
+    pub fn deserialize_block(buffer: &[u8]) -> Result<Vec<Uac2Range<T>>, Error> {
+        let [lo, hi, ranges @ ..] = buffer else { return Err(anyhow!("RANGE block without wNumSubRanges")) };
+        let count = u16::from_le_bytes([*lo, *hi]) as usize;
+        if ranges.len() < count * 3 * T::LEN {
+            return Err(anyhow!("RANGE block of {} bytes is too short for {} subranges", buffer.len(), count));
+        }
+        Ok(ranges.chunks_exact(3 * T::LEN).take(count).map(|range| Uac2Range {
+            min: T::read(&range[..T::LEN]),
+            max: T::read(&range[T::LEN..2 * T::LEN]),
+            res: T::read(&range[2 * T::LEN..]),
+        }).collect())
+    }
+
+    pub fn serialize_block(ranges: &[Uac2Range<T>], mut buffer: impl Write) {
+        let mut bytes = (ranges.len() as u16).to_le_bytes().to_vec();
+        for range in ranges {
+            range.min.write(&mut bytes);
+            range.max.write(&mut bytes);
+            range.res.write(&mut bytes);
+        }
+        buffer.write_all(&bytes).unwrap();
+    }
+
+    /// wLength of a RANGE request returning `count` subranges.
+    pub fn block_len(count: usize) -> u16 {
+        (2 + count * 3 * T::LEN) as u16
+    }
+}
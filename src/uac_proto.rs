@@ -5,7 +5,7 @@ use crate::usb_proto::UsbDescriptorTypes;
 use anyhow::Error;
 use structure::byteorder::{WriteBytesExt, ReadBytesExt};
 
-#[derive(FromPrimitive, Debug)]
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u16)]
 pub enum FeatureUnitControlSelectors {
     UacFuMute = 0x01,
@@ -20,7 +20,15 @@ pub enum FeatureUnitControlSelectors {
     UacFuLoudness = 0x0a,
 }
 
+// A.10.5 Endpoint Control Selectors
 #[derive(FromPrimitive, Debug)]
+#[repr(u16)]
+pub enum UacEndpointControlSelectors {
+    SamplingFreq = 0x01,
+    Pitch = 0x02,
+}
+
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum UacRequestCodes {
     Undefined = 0x00,
@@ -34,6 +42,15 @@ pub enum UacRequestCodes {
     GetDef = 0x87,
 }
 
+// UAC2 A.14 Audio Class-Specific Request Codes, the direction tells get from set
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Uac2RequestCodes {
+    Cur = 0x01,
+    Range = 0x02,
+    Mem = 0x03,
+}
+
 // UAC
 // https://www.usb.org/sites/default/files/audio10.pdf
 // https://github.com/torvalds/linux/blob/master/include/uapi/linux/usb/audio.h
@@ -48,6 +65,7 @@ pub enum UacInterfaceSubclass {
 
 #[derive(FromPrimitive)]
 #[repr(u8)]
+#[non_exhaustive]
 pub enum UacDescriptorSubtypes {
     Header = 0x01,
     InputTerminal = 0x02,
@@ -61,6 +79,7 @@ pub enum UacDescriptorSubtypes {
 
 #[derive(FromPrimitive)]
 #[repr(u8)]
+#[non_exhaustive]
 pub enum UacInterfaceSubtypes {
     General = 0x01,
     FormatType = 0x02,
@@ -69,6 +88,7 @@ pub enum UacInterfaceSubtypes {
 
 #[derive(FromPrimitive)]
 #[repr(u8)]
+#[non_exhaustive]
 pub enum UacFormatTypeI {
     Undefined = 0x0,
     Pcm = 0x1,
@@ -78,7 +98,8 @@ pub enum UacFormatTypeI {
     Mulaw = 0x5,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Uac1AcHeaderDescriptor {
     pub bcd_adc: u16,
     pub w_total_length: u16,
@@ -106,7 +127,8 @@ impl Uac1AcHeaderDescriptor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Uac1OutputTerminalDescriptor {
     pub b_terminal_id: u8,
     pub w_terminal_type: u16,
@@ -132,7 +154,8 @@ impl Uac1OutputTerminalDescriptor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UacInputTerminalDescriptor {
     pub b_terminal_id: u8,
     pub w_terminal_type: u16,
@@ -160,7 +183,8 @@ impl UacInputTerminalDescriptor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UacFeatureUnitDescriptor {
     pub b_unit_id: u8,
     pub b_source_id: u8,
@@ -191,7 +215,8 @@ impl UacFeatureUnitDescriptor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Uac1AsHeaderDescriptor {
     pub b_terminal_link: u8,
     pub b_delay: u8,
@@ -211,7 +236,8 @@ impl Uac1AsHeaderDescriptor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UacIsoEndpointDescriptor {
     pub b_descriptor_subtype: u8,
     pub bm_attributes: u8,
@@ -232,7 +258,8 @@ impl UacIsoEndpointDescriptor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UacFormatTypeIContinuousDescriptor {
     pub b_nr_channels: u8,
     pub b_subframe_size: u8,
@@ -269,7 +296,8 @@ impl UacFormatTypeIContinuousDescriptor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorUacInterfaceUnknown {
     pub iface_subclass: u8,
     pub bytes: Vec<u8>,
@@ -285,7 +313,8 @@ impl DescriptorUacInterfaceUnknown {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorUacFormatTypeUnknown {
     pub format_type: u8,
     pub bytes: Vec<u8>,
@@ -324,6 +353,14 @@ impl UacVolume {
         }
     }
 
+    pub fn min(&self) -> i16 {
+        self.min
+    }
+
+    pub fn max(&self) -> i16 {
+        self.max
+    }
+
     /// Converts the current volume to a normalized in range of 0.0 - 1.0.
     pub fn cur_normalized(&self) -> f32 {
         let range = self.max as i32 - self.min as i32;
@@ -461,6 +498,13 @@ mod test {
         assert!(UacVolume::new(min, max, too_high).is_err());
     }
 
+    #[test]
+    fn uac_vol_min_max_accessors() {
+        let uac_vol = UacVolume::new(-0x2000, 0x1000, 0).unwrap();
+        assert_eq!(uac_vol.min(), -0x2000);
+        assert_eq!(uac_vol.max(), 0x1000);
+    }
+
     #[test]
     fn uac_vol_good_values_are_ok() {
         if let Err(e) = UacVolume::new(UacVolume::DB_MIN, UacVolume::DB_MAX, 0) {
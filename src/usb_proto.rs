@@ -4,10 +4,13 @@
 use std::io::Write;
 
 use anyhow::Error;
+use num_traits::FromPrimitive;
 use libusb1_sys::constants::*;
 use structure::byteorder::WriteBytesExt;
 use std::hash::Hash;
 use std::fmt::{Display, Debug, Formatter};
+use crate::decoder::CustomDescriptor;
+use crate::dfu_proto::DescriptorDfuFunctional;
 use crate::uac_proto::{UacInterfaceSubclass, UacInputTerminalDescriptor, Uac1OutputTerminalDescriptor, UacFeatureUnitDescriptor, Uac1AsHeaderDescriptor, UacFormatTypeIContinuousDescriptor, DescriptorUacFormatTypeUnknown, UacIsoEndpointDescriptor, Uac1AcHeaderDescriptor, DescriptorUacInterfaceUnknown};
 use crate::uvc_proto::{UvcInterfaceSubClass, DescriptorUvcInputHeader, DescriptorUvcFormatUncompressed, DescriptorUvcFormatMjpeg, DescriptorUvcFrameUncompressed, DescriptorUvcFrameMjpeg, DescriptorUvcFormatFrameBased, DescriptorUvcFrameFrameBased, DescriptorUvcVsInterfaceUnknown, DescriptorUvcVcInterfaceUnknown, UvcHeaderDescriptor, UvcInputTerminalDescriptor, UvcProcessingUnitDescriptor, UvcExtensionUnitDescriptor, UvcOutputTerminalDescriptor};
 
@@ -70,6 +73,75 @@ pub enum Recip {
 }
 pub const USB_RECIP_MASK: u8 = 0x1f;
 
+// 9.4 Standard Device Requests
+#[derive(Debug, Clone, Copy, FromPrimitive, PartialEq)]
+#[repr(u8)]
+pub enum StandardRequest {
+    GetStatus = 0x00,
+    ClearFeature = 0x01,
+    SetFeature = 0x03,
+    SetAddress = 0x05,
+    GetDescriptor = 0x06,
+    SetDescriptor = 0x07,
+    GetConfiguration = 0x08,
+    SetConfiguration = 0x09,
+    GetInterface = 0x0a,
+    SetInterface = 0x0b,
+    SynchFrame = 0x0c,
+}
+
+// 9.4 Table 9-6 Standard Feature Selectors
+pub const USB_ENDPOINT_HALT: u16 = 0;
+pub const USB_DEVICE_REMOTE_WAKEUP: u16 = 1;
+pub const USB_TEST_MODE: u16 = 2;
+
+/// The SETUP stage starting every control transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SetupPacket {
+    pub bm_request_type: u8,
+    pub b_request: u8,
+    pub w_value: u16,
+    pub w_index: u16,
+    pub w_length: u16,
+}
+
+impl SetupPacket {
+    pub fn new(dir: XferDir, xfer_type: XferType, recip: Recip, b_request: u8, w_value: u16, w_index: u16, w_length: u16) -> SetupPacket {
+        SetupPacket { bm_request_type: dir as u8 | xfer_type as u8 | recip as u8, b_request, w_value, w_index, w_length }
+    }
+
+    pub fn serialize(&self, mut buffer: impl Write) {
+        let format = structure!("<BBHHH");
+        format.pack_into(&mut buffer, self.bm_request_type, self.b_request, self.w_value, self.w_index, self.w_length).unwrap();
+    }
+
+    pub fn deserialize(buffer: &mut &[u8]) -> Result<SetupPacket, Error> {
+        let format = structure!("<BBHHH");
+        let (bm_request_type, b_request, w_value, w_index, w_length) = format.unpack_from(buffer)?;
+        Ok(SetupPacket { bm_request_type, b_request, w_value, w_index, w_length })
+    }
+
+    pub fn dir(&self) -> XferDir {
+        XferDir::from_u8(self.bm_request_type & USB_DIR_MASK).unwrap()
+    }
+
+    pub fn xfer_type(&self) -> XferType {
+        XferType::from_u8(self.bm_request_type & USB_XFER_TYPE_MASK).unwrap()
+    }
+
+    pub fn recip(&self) -> Recip {
+        Recip::from_u8(self.bm_request_type & USB_RECIP_MASK).unwrap()
+    }
+
+    /// Absent for class and vendor requests and for request codes the specification does not define.
+    pub fn standard_request(&self) -> Option<StandardRequest> {
+        match self.xfer_type() {
+            XferType::Std => StandardRequest::from_u8(self.b_request),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, FromPrimitive)]
 #[repr(u8)]
 pub enum UsbDescriptorTypes {
@@ -198,7 +270,8 @@ impl Display for IfaceAltSetting {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UsbDescriptorHeader {
     pub b_length: u8,
     pub b_descriptor_type: u8,
@@ -213,13 +286,15 @@ impl UsbDescriptorHeader {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorUnknown {
     pub desc_type: u8,
     pub bytes: Vec<u8>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorCsDevice {
     pub bytes: Vec<u8>,
 }
@@ -232,7 +307,8 @@ impl DescriptorCsDevice {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorCsInterface {
     pub bytes: Vec<u8>,
 }
@@ -245,7 +321,8 @@ impl DescriptorCsInterface {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorCsEndpoint {
     pub bytes: Vec<u8>,
 }
@@ -258,7 +335,8 @@ impl DescriptorCsEndpoint {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorDevice {
     pub bcd_usb: u16,
     pub b_device_class: u8,
@@ -289,7 +367,8 @@ impl DescriptorDevice {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorDevQualifier {
     pub bcd_usb: u16,
     pub b_device_class: u8,
@@ -320,7 +399,8 @@ impl DescriptorDevQualifier {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorConfig {
     pub w_total_length: u16,
     pub b_num_interfaces: u8,
@@ -345,7 +425,8 @@ impl DescriptorConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorInterface {
     pub b_interface_number: u8,
     pub b_alternate_setting: u8,
@@ -383,7 +464,8 @@ impl DescriptorInterface {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UsbInterfaceAssocDescriptor {
     pub b_first_interface: u8,
     pub b_interface_count: u8,
@@ -411,7 +493,8 @@ impl UsbInterfaceAssocDescriptor {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DescriptorTransferType {
     Control,
     Isochronous,
@@ -421,7 +504,8 @@ pub enum DescriptorTransferType {
     Invalid,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SynchType {
     None,
     Asynchronous,
@@ -430,7 +514,8 @@ pub enum SynchType {
     Invalid,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UsageType {
     Data,
     Feedback,
@@ -438,11 +523,12 @@ pub enum UsageType {
     Invalid,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UsbSsEpCompDescriptor {
-    b_max_burst: u8,
-    bm_attributes: u8,
-    w_bytes_per_interval: u16,
+    pub b_max_burst: u8,
+    pub bm_attributes: u8,
+    pub w_bytes_per_interval: u16,
 }
 impl UsbSsEpCompDescriptor {
     pub fn serialize(&self, mut buffer: impl Write) {
@@ -466,10 +552,11 @@ impl UsbSsEpCompDescriptor {
     pub fn mult(&self) -> u8 { (self.bm_attributes & 0x03) + 1 }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UsbSspIsochEpCompDescriptor {
-    w_reserved: u16,
-    dw_bytes_per_interval: u32,
+    pub w_reserved: u16,
+    pub dw_bytes_per_interval: u32,
 }
 impl UsbSspIsochEpCompDescriptor {
     pub fn serialize(&self, mut buffer: impl Write) {
@@ -489,7 +576,8 @@ impl UsbSspIsochEpCompDescriptor {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EndpointAttrTransferType {
     Control     = 0x00,
     Isochronous = 0x01,
@@ -497,7 +585,8 @@ pub enum EndpointAttrTransferType {
     Interrupt   = 0x03,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EndpointAttrSyncType {
     NoSync  = 0x00,
     Async   = 0x01,
@@ -505,7 +594,8 @@ pub enum EndpointAttrSyncType {
     Sync    = 0x03,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EndpointAttrUsageType {
     Data        = 0x00,
     Feedback    = 0x01,
@@ -521,7 +611,8 @@ pub fn ep_attr_to_u8(transfer: EndpointAttrTransferType, sync: EndpointAttrSyncT
     res
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UacDescriptorEndpoint {
     pub b_endpoint_address: u8,
     pub bm_attributes: u8,
@@ -556,7 +647,8 @@ impl UacDescriptorEndpoint {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorEndpoint {
     pub b_endpoint_address: u8,
     pub bm_attributes: u8,
@@ -656,12 +748,16 @@ impl DescriptorEndpoint {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "descriptor"))]
+#[non_exhaustive]
 pub enum DescriptorTypes {
     Root(),
     Unknown(DescriptorUnknown),
     Device(DescriptorDevice),
     CsDevice(DescriptorCsDevice),
+    DfuFunctional(DescriptorDfuFunctional),
     Config(DescriptorConfig),
     Interface(DescriptorInterface),
     CsInterface(DescriptorCsInterface),
@@ -695,6 +791,8 @@ pub enum DescriptorTypes {
     UvcVcProcessingUnit(UvcProcessingUnitDescriptor),
     UvcVcExtensionUnit(UvcExtensionUnitDescriptor),
     UvcVcOutputTerminal(UvcOutputTerminalDescriptor),
+    #[cfg_attr(feature = "serde", serde(with = "crate::decoder::custom_serde"))]
+    Custom(Box<dyn CustomDescriptor>),
 }
 
 impl DescriptorTypes {
@@ -0,0 +1,318 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Error;
+use libusb1_sys::constants::LIBUSB_REQUEST_GET_DESCRIPTOR;
+
+use crate::TreeNode;
+use crate::usb_proto::{DescriptorDevice, DeviceAddress, Recip, SetupPacket, UsbDescriptorTypes, XferDir, XferType};
+
+// https://www.tcpdump.org/linktypes.html
+const LINKTYPE_USB_LINUX: u32 = 189;
+const LINKTYPE_USB_LINUX_MMAPPED: u32 = 220;
+
+// pcapng block types
+const SECTION_HEADER: u32 = 0x0a0d0d0a;
+const INTERFACE_DESCRIPTION: u32 = 1;
+const SIMPLE_PACKET: u32 = 3;
+const ENHANCED_PACKET: u32 = 6;
+
+/// usbmon's transfer type for control transfers; 0 is isochronous, 1 interrupt and 3 bulk.
+pub const USBMON_CONTROL: u8 = 2;
+
+const DEVICE_DESCRIPTOR_LEN: usize = 18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsbmonEvent {
+    Submission,
+    Completion,
+    /// The URB could not be submitted
+    Error,
+}
+
+/// One usbmon event, see Documentation/usb/usbmon.rst in the kernel.
+#[derive(Debug, Clone)]
+pub struct UsbmonPacket {
+    /// The URB's tag, shared by its submission and completion
+    pub id: u64,
+    pub event: UsbmonEvent,
+    pub xfer_type: u8,
+    /// Endpoint number with the direction bit set for IN
+    pub ep: u8,
+    pub address: DeviceAddress,
+    /// Only present on the submission of a control transfer
+    pub setup: Option<SetupPacket>,
+    pub ts_sec: i64,
+    pub ts_usec: i32,
+    /// 0 or a negative errno; -115 (EINPROGRESS) on submissions
+    pub status: i32,
+    /// Requested length on submission, transferred length on completion
+    pub length: u32,
+    /// The captured data, which may be shorter than `length` if the capture was truncated
+    pub data: Vec<u8>,
+}
+
+/// A control transfer put back together from its submission and completion.
+#[derive(Debug, Clone)]
+pub struct ControlTransfer {
+    pub address: DeviceAddress,
+    pub setup: SetupPacket,
+    pub ts_sec: i64,
+    pub ts_usec: i32,
+    /// 0 on success, a negative errno otherwise, e.g. -32 (EPIPE) for a stall or -71 (EPROTO).
+    /// Absent when the capture ends before the transfer completes.
+    pub status: Option<i32>,
+    /// The data stage: from the submission for OUT transfers, from the completion for IN
+    pub data: Vec<u8>,
+}
+
+/// What a device returned to GET_DESCRIPTOR requests, see `captured_descriptors`.
+#[derive(Debug, Clone)]
+pub struct CapturedDevice {
+    pub device: Option<DescriptorDevice>,
+    /// The configurations returned in full, as `TreeNode::deserialize` returns them
+    pub root: TreeNode,
+    /// Configurations by index which were requested in full but came back shorter than their
+    /// wTotalLength or with a bLength that runs past the end; `hexdump::annotate` shows how far
+    /// they got
+    pub partial: BTreeMap<u8, Vec<u8>>,
+    /// GET_DESCRIPTOR requests that failed or never completed
+    pub failed: Vec<ControlTransfer>,
+}
+
+/// Reads the usbmon packets of a pcap or pcapng file, e.g. one saved by Wireshark. Packets of
+/// other link types are skipped.
+pub fn read_capture(bytes: &[u8]) -> Result<Vec<UsbmonPacket>, Error> {
+    if bytes.len() >= 4 && u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) == SECTION_HEADER {
+        read_pcapng(bytes)
+    } else {
+        read_pcap(bytes)
+    }
+}
+
+fn read_pcap(bytes: &[u8]) -> Result<Vec<UsbmonPacket>, Error> {
+    let mut reader = Reader { bytes, pos: 0, big_endian: false };
+    reader.big_endian = match reader.u32()? {
+        // microsecond and nanosecond timestamps
+        0xa1b2c3d4 | 0xa1b23c4d => false,
+        0xd4c3b2a1 | 0x4d3cb2a1 => true,
+        magic => return Err(anyhow!("Not a pcap file, magic {:#010x}", magic)),
+    };
+    reader.skip(16)?;
+    let link_type = reader.u32()? & 0xffff;
+    let mut packets = vec![];
+    while reader.pos < bytes.len() {
+        reader.skip(8)?;
+        let captured = reader.u32()? as usize;
+        reader.skip(4)?;
+        let data = reader.take(captured)?;
+        if let Some(packet) = parse_packet(data, link_type, reader.big_endian)? {
+            packets.push(packet);
+        }
+    }
+    Ok(packets)
+}
+
+#[allow(clippy::manual_is_multiple_of)] // usize::is_multiple_of needs Rust 1.87
+fn read_pcapng(bytes: &[u8]) -> Result<Vec<UsbmonPacket>, Error> {
+    let mut reader = Reader { bytes, pos: 0, big_endian: false };
+    let mut link_types: Vec<u32> = vec![];
+    let mut packets = vec![];
+    while reader.pos < bytes.len() {
+        let start = reader.pos;
+        let block_type = reader.u32()?;
+        if block_type == SECTION_HEADER {
+            // the byte order magic after the length tells how the section is written
+            let magic = bytes.get(start + 8..start + 12).ok_or_else(|| anyhow!("Truncated section header"))?;
+            reader.big_endian = magic == [0x1a, 0x2b, 0x3c, 0x4d];
+            link_types.clear();
+        }
+        let length = reader.u32()? as usize;
+        if length < 12 || length % 4 != 0 {
+            return Err(anyhow!("Invalid pcapng block length {} at {}", length, start));
+        }
+        let mut body = Reader { bytes: reader.take(length - 12)?, pos: 0, big_endian: reader.big_endian };
+        reader.skip(4)?;
+        let (interface, data) = match block_type {
+            INTERFACE_DESCRIPTION => {
+                link_types.push(body.u16()? as u32);
+                continue;
+            }
+            ENHANCED_PACKET => {
+                let interface = body.u32()? as usize;
+                body.skip(8)?;
+                let captured = body.u32()? as usize;
+                body.skip(4)?;
+                (interface, body.take(captured)?)
+            }
+            SIMPLE_PACKET => {
+                body.skip(4)?;
+                (0, &body.bytes[body.pos..])
+            }
+            _ => continue,
+        };
+        let link_type = *link_types.get(interface).ok_or_else(|| anyhow!("Packet for unknown interface {}", interface))?;
+        if let Some(packet) = parse_packet(data, link_type, reader.big_endian)? {
+            packets.push(packet);
+        }
+    }
+    Ok(packets)
+}
+
+/// Parses the usbmon header of `data`, which is in the byte order of the capturing host.
+fn parse_packet(data: &[u8], link_type: u32, big_endian: bool) -> Result<Option<UsbmonPacket>, Error> {
+    let header_len = match link_type {
+        LINKTYPE_USB_LINUX => 48,
+        LINKTYPE_USB_LINUX_MMAPPED => 64,
+        _ => return Ok(None),
+    };
+    if data.len() < header_len {
+        return Err(anyhow!("usbmon packet of {} bytes is shorter than its header", data.len()));
+    }
+    let mut reader = Reader { bytes: data, pos: 0, big_endian };
+    let id = reader.u64()?;
+    let event = match reader.u8()? {
+        b'S' => UsbmonEvent::Submission,
+        b'C' => UsbmonEvent::Completion,
+        b'E' => UsbmonEvent::Error,
+        event => return Err(anyhow!("Unknown usbmon event {:#04x}", event)),
+    };
+    let xfer_type = reader.u8()?;
+    let ep = reader.u8()?;
+    let num = reader.u8()?;
+    let bus = reader.u16()?;
+    // flag_setup and flag_data are 0 when the setup packet or data is present
+    let has_setup = reader.u8()? == 0;
+    let has_data = reader.u8()? == 0;
+    let ts_sec = reader.u64()? as i64;
+    let ts_usec = reader.u32()? as i32;
+    let status = reader.u32()? as i32;
+    let length = reader.u32()?;
+    let captured = reader.u32()? as usize;
+    let setup = if has_setup { Some(SetupPacket::deserialize(&mut reader.take(8)?)?) } else { None };
+    let payload = &data[header_len..];
+    let data = if has_data { payload[..captured.min(payload.len())].to_vec() } else { vec![] };
+    Ok(Some(UsbmonPacket {
+        id, event, xfer_type, ep, address: DeviceAddress::new(bus as u8, num), setup, ts_sec, ts_usec, status, length, data,
+    }))
+}
+
+/// Pairs the submissions of control transfers with their completions, in submission order.
+pub fn control_transfers(packets: &[UsbmonPacket]) -> Vec<ControlTransfer> {
+    let mut transfers: Vec<ControlTransfer> = vec![];
+    let mut pending: HashMap<u64, usize> = HashMap::new();
+    for packet in packets.iter().filter(|packet| packet.xfer_type == USBMON_CONTROL) {
+        match (packet.event, packet.setup) {
+            (UsbmonEvent::Submission, Some(setup)) => {
+                pending.insert(packet.id, transfers.len());
+                transfers.push(ControlTransfer {
+                    address: packet.address,
+                    setup,
+                    ts_sec: packet.ts_sec,
+                    ts_usec: packet.ts_usec,
+                    status: None,
+                    data: if setup.dir() == XferDir::ToDev { packet.data.clone() } else { vec![] },
+                });
+            }
+            (UsbmonEvent::Completion | UsbmonEvent::Error, _) => {
+                let Some(idx) = pending.remove(&packet.id) else { continue };
+                let transfer = &mut transfers[idx];
+                transfer.status = Some(packet.status);
+                if transfer.setup.dir() == XferDir::ToHost {
+                    transfer.data = packet.data.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+    transfers
+}
+
+/// Collects the device and configuration descriptors each device returned. A configuration is
+/// taken from its latest complete response, so a device that was reset and enumerated again shows
+/// what it returned last. String descriptors are not collected.
+pub fn captured_descriptors(transfers: &[ControlTransfer]) -> Result<HashMap<DeviceAddress, CapturedDevice>, Error> {
+    let mut found: HashMap<DeviceAddress, (CapturedDevice, BTreeMap<u8, Vec<u8>>)> = HashMap::new();
+    for transfer in transfers {
+        let setup = transfer.setup;
+        if setup.dir() != XferDir::ToHost || setup.xfer_type() != XferType::Std || setup.recip() != Recip::Dev ||
+            setup.b_request != LIBUSB_REQUEST_GET_DESCRIPTOR {
+            continue;
+        }
+        let (device, configs) = found.entry(transfer.address).or_insert_with(|| {
+            (CapturedDevice { device: None, root: TreeNode::new(), partial: BTreeMap::new(), failed: vec![] }, BTreeMap::new())
+        });
+        if transfer.status != Some(0) {
+            // what arrived before an error is still worth keeping
+            device.failed.push(transfer.clone());
+        }
+        let data = &transfer.data;
+        let index = (setup.w_value & 0xff) as u8;
+        match (setup.w_value >> 8) as u8 {
+            t if t == UsbDescriptorTypes::Device as u8 && data.len() >= DEVICE_DESCRIPTOR_LEN => {
+                device.device = Some(DescriptorDevice::deserialize(&mut &data[2..DEVICE_DESCRIPTOR_LEN])?);
+            }
+            t if t == UsbDescriptorTypes::Config as u8 && data.len() >= 4 => {
+                let total_length = u16::from_le_bytes([data[2], data[3]]) as usize;
+                if data.len() >= total_length {
+                    let config = data[..total_length].to_vec();
+                    if let Err(e) = TreeNode::deserialize(&mut &config[..]) {
+                        warn!("Configuration {} of {:?} can not be parsed: {}", index, transfer.address, e);
+                        configs.remove(&index);
+                        device.partial.insert(index, config);
+                    } else {
+                        configs.insert(index, config);
+                        device.partial.remove(&index);
+                    }
+                } else if setup.w_length as usize >= total_length {
+                    // asking for the first 9 bytes to learn wTotalLength is not a failure
+                    device.partial.insert(index, data.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+    found.into_iter().map(|(address, (mut device, configs))| {
+        let buffer: Vec<u8> = configs.into_values().flatten().collect();
+        device.root = TreeNode::deserialize(&mut &buffer[..])?;
+        Ok((address, device))
+    }).collect()
+}
+
+/// Reads fields in the byte order of a capture.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self.bytes.get(self.pos..self.pos + len)
+            .ok_or_else(|| anyhow!("Capture truncated at {}, {} more bytes expected", self.pos, len))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), Error> {
+        self.take(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let bytes = self.take(2)?.try_into().unwrap();
+        Ok(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.take(4)?.try_into().unwrap();
+        Ok(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        let bytes = self.take(8)?.try_into().unwrap();
+        Ok(if self.big_endian { u64::from_be_bytes(bytes) } else { u64::from_le_bytes(bytes) })
+    }
+}
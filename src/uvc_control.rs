@@ -0,0 +1,344 @@
+use anyhow::Error;
+use libusb1_sys::constants::LIBUSB_CLASS_VIDEO;
+use num_traits::FromPrimitive;
+
+use crate::TreeNode;
+use crate::usb_proto::{DescriptorTypes, Recip, SetupPacket, XferDir, XferType};
+use crate::uvc_proto::{UvcCtControlSelectors, UvcInterfaceSubClass, UvcPuControlSelectors, UvcRequestCodes};
+use UvcFieldType::{Bool, I16, I32, I8, U16, U32, U8};
+
+// B.1 Terminal Types
+const UVC_ITT_CAMERA: u16 = 0x0201;
+// 3.7.2.3 the camera terminal's bControlSize follows the three focal length fields
+const CT_CONTROL_SIZE_OFFSET: usize = 6;
+
+/// The kinds of entities whose standard controls are in `UVC_CONTROLS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UvcEntityKind {
+    CameraTerminal,
+    ProcessingUnit,
+}
+
+impl UvcEntityKind {
+    /// The kind of the terminal or unit `id` among the class-specific descriptors of a
+    /// VideoControl interface; other terminals and units give `None`.
+    pub fn of(iface: &TreeNode, id: u8) -> Option<UvcEntityKind> {
+        iface.children.iter().find_map(|child| match &child.parsed {
+            DescriptorTypes::UvcVcInputTerminal(it) if it.b_terminal_id == id && it.w_terminal_type == UVC_ITT_CAMERA => Some(UvcEntityKind::CameraTerminal),
+            DescriptorTypes::UvcVcProcessingUnit(pu) if pu.b_unit_id == id => Some(UvcEntityKind::ProcessingUnit),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UvcControlSelector {
+    Camera(UvcCtControlSelectors),
+    Processing(UvcPuControlSelectors),
+}
+
+impl UvcControlSelector {
+    /// The selector of wValue's high byte for a request to an entity of `kind`.
+    pub fn from_entity(kind: UvcEntityKind, selector: u8) -> Option<UvcControlSelector> {
+        match kind {
+            UvcEntityKind::CameraTerminal => UvcCtControlSelectors::from_u8(selector).map(UvcControlSelector::Camera),
+            UvcEntityKind::ProcessingUnit => UvcPuControlSelectors::from_u8(selector).map(UvcControlSelector::Processing),
+        }
+    }
+
+    pub fn kind(&self) -> UvcEntityKind {
+        match self {
+            UvcControlSelector::Camera(_) => UvcEntityKind::CameraTerminal,
+            UvcControlSelector::Processing(_) => UvcEntityKind::ProcessingUnit,
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match self {
+            UvcControlSelector::Camera(selector) => *selector as u8,
+            UvcControlSelector::Processing(selector) => *selector as u8,
+        }
+    }
+}
+
+/// How one field of a parameter block is encoded; all fields are little endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UvcFieldType {
+    Bool,
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+}
+
+impl UvcFieldType {
+    pub fn size(&self) -> usize {
+        match self {
+            UvcFieldType::Bool | UvcFieldType::U8 | UvcFieldType::I8 => 1,
+            UvcFieldType::U16 | UvcFieldType::I16 => 2,
+            UvcFieldType::U32 | UvcFieldType::I32 => 4,
+        }
+    }
+
+    fn range(&self) -> (i64, i64) {
+        match self {
+            UvcFieldType::Bool => (0, 1),
+            UvcFieldType::U8 => (0, u8::MAX as i64),
+            UvcFieldType::I8 => (i8::MIN as i64, i8::MAX as i64),
+            UvcFieldType::U16 => (0, u16::MAX as i64),
+            UvcFieldType::I16 => (i16::MIN as i64, i16::MAX as i64),
+            UvcFieldType::U32 => (0, u32::MAX as i64),
+            UvcFieldType::I32 => (i32::MIN as i64, i32::MAX as i64),
+        }
+    }
+
+    fn read(&self, bytes: &[u8]) -> i64 {
+        match self {
+            UvcFieldType::Bool => (bytes[0] != 0) as i64,
+            UvcFieldType::U8 => bytes[0] as i64,
+            UvcFieldType::I8 => bytes[0] as i8 as i64,
+            UvcFieldType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as i64,
+            UvcFieldType::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as i64,
+            UvcFieldType::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64,
+            UvcFieldType::I32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64,
+        }
+    }
+}
+
+/// One field of a control's parameter block, e.g. `wPanAbsolute` of the pan/tilt control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UvcControlField {
+    pub name: &'static str,
+    pub field_type: UvcFieldType,
+    /// What one step of the value stands for, e.g. `0.0001 s`; empty for plain numbers,
+    /// switches and bitmaps
+    pub unit: &'static str,
+}
+
+/// A standard camera terminal or processing unit control, see 4.2.2.1 and 4.2.2.3 of the UVC 1.5
+/// specification.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UvcControlSpec {
+    pub selector: UvcControlSelector,
+    /// The selector's name in the specification, e.g. `CT_ZOOM_ABSOLUTE_CONTROL`
+    pub name: &'static str,
+    /// The bit of the entity's bmControls advertising the control
+    pub bit: u8,
+    /// The fields of the parameter block in order
+    pub fields: &'static [UvcControlField],
+}
+
+const fn field(name: &'static str, field_type: UvcFieldType, unit: &'static str) -> UvcControlField {
+    UvcControlField { name, field_type, unit }
+}
+
+const fn ct(selector: UvcCtControlSelectors, name: &'static str, bit: u8, fields: &'static [UvcControlField]) -> UvcControlSpec {
+    UvcControlSpec { selector: UvcControlSelector::Camera(selector), name, bit, fields }
+}
+
+const fn pu(selector: UvcPuControlSelectors, name: &'static str, bit: u8, fields: &'static [UvcControlField]) -> UvcControlSpec {
+    UvcControlSpec { selector: UvcControlSelector::Processing(selector), name, bit, fields }
+}
+
+/// Every standard camera terminal and processing unit control, in bmControls order.
+pub static UVC_CONTROLS: &[UvcControlSpec] = &[
+    ct(UvcCtControlSelectors::ScanningModeControl, "CT_SCANNING_MODE_CONTROL", 0, &[field("bScanningMode", Bool, "")]),
+    ct(UvcCtControlSelectors::AeModeControl, "CT_AE_MODE_CONTROL", 1, &[field("bAutoExposureMode", U8, "")]),
+    ct(UvcCtControlSelectors::AePriorityControl, "CT_AE_PRIORITY_CONTROL", 2, &[field("bAutoExposurePriority", U8, "")]),
+    ct(UvcCtControlSelectors::ExposureTimeAbsoluteControl, "CT_EXPOSURE_TIME_ABSOLUTE_CONTROL", 3, &[field("dwExposureTimeAbsolute", U32, "0.0001 s")]),
+    ct(UvcCtControlSelectors::ExposureTimeRelativeControl, "CT_EXPOSURE_TIME_RELATIVE_CONTROL", 4, &[field("bExposureTimeRelative", I8, "step")]),
+    ct(UvcCtControlSelectors::FocusAbsoluteControl, "CT_FOCUS_ABSOLUTE_CONTROL", 5, &[field("wFocusAbsolute", U16, "mm")]),
+    ct(UvcCtControlSelectors::FocusRelativeControl, "CT_FOCUS_RELATIVE_CONTROL", 6, &[field("bFocusRelative", I8, "step"), field("bSpeed", U8, "")]),
+    ct(UvcCtControlSelectors::IrisAbsoluteControl, "CT_IRIS_ABSOLUTE_CONTROL", 7, &[field("wIrisAbsolute", U16, "0.01 f-stop")]),
+    ct(UvcCtControlSelectors::IrisRelativeControl, "CT_IRIS_RELATIVE_CONTROL", 8, &[field("bIrisRelative", I8, "step")]),
+    ct(UvcCtControlSelectors::ZoomAbsoluteControl, "CT_ZOOM_ABSOLUTE_CONTROL", 9, &[field("wObjectiveFocalLength", U16, "")]),
+    ct(UvcCtControlSelectors::ZoomRelativeControl, "CT_ZOOM_RELATIVE_CONTROL", 10, &[field("bZoom", I8, "step"), field("bDigitalZoom", Bool, ""), field("bSpeed", U8, "")]),
+    ct(UvcCtControlSelectors::PantiltAbsoluteControl, "CT_PANTILT_ABSOLUTE_CONTROL", 11, &[field("dwPanAbsolute", I32, "arc second"), field("dwTiltAbsolute", I32, "arc second")]),
+    ct(UvcCtControlSelectors::PantiltRelativeControl, "CT_PANTILT_RELATIVE_CONTROL", 12, &[
+        field("bPanRelative", I8, "step"), field("bPanSpeed", U8, ""), field("bTiltRelative", I8, "step"), field("bTiltSpeed", U8, ""),
+    ]),
+    ct(UvcCtControlSelectors::RollAbsoluteControl, "CT_ROLL_ABSOLUTE_CONTROL", 13, &[field("wAbsoluteRoll", I16, "degree")]),
+    ct(UvcCtControlSelectors::RollRelativeControl, "CT_ROLL_RELATIVE_CONTROL", 14, &[field("bRollRelative", I8, "step"), field("bSpeed", U8, "")]),
+    // bits 15 and 16 are reserved
+    ct(UvcCtControlSelectors::FocusAutoControl, "CT_FOCUS_AUTO_CONTROL", 17, &[field("bFocusAuto", Bool, "")]),
+    ct(UvcCtControlSelectors::PrivacyControl, "CT_PRIVACY_CONTROL", 18, &[field("bPrivacy", Bool, "")]),
+    ct(UvcCtControlSelectors::FocusSimpleControl, "CT_FOCUS_SIMPLE_CONTROL", 19, &[field("bFocus", U8, "")]),
+    ct(UvcCtControlSelectors::WindowControl, "CT_WINDOW_CONTROL", 20, &[
+        field("wWindow_Top", U16, "pixel"), field("wWindow_Left", U16, "pixel"), field("wWindow_Bottom", U16, "pixel"),
+        field("wWindow_Right", U16, "pixel"), field("wNumSteps", U16, ""), field("bmNumStepsUnits", U16, ""),
+    ]),
+    ct(UvcCtControlSelectors::RegionOfInterestControl, "CT_REGION_OF_INTEREST_CONTROL", 21, &[
+        field("wROI_Top", U16, "pixel"), field("wROI_Left", U16, "pixel"), field("wROI_Bottom", U16, "pixel"),
+        field("wROI_Right", U16, "pixel"), field("bmAutoControls", U16, ""),
+    ]),
+    pu(UvcPuControlSelectors::BrightnessControl, "PU_BRIGHTNESS_CONTROL", 0, &[field("wBrightness", I16, "")]),
+    pu(UvcPuControlSelectors::ContrastControl, "PU_CONTRAST_CONTROL", 1, &[field("wContrast", U16, "")]),
+    pu(UvcPuControlSelectors::HueControl, "PU_HUE_CONTROL", 2, &[field("wHue", I16, "0.01 degree")]),
+    pu(UvcPuControlSelectors::SaturationControl, "PU_SATURATION_CONTROL", 3, &[field("wSaturation", U16, "")]),
+    pu(UvcPuControlSelectors::SharpnessControl, "PU_SHARPNESS_CONTROL", 4, &[field("wSharpness", U16, "")]),
+    pu(UvcPuControlSelectors::GammaControl, "PU_GAMMA_CONTROL", 5, &[field("wGamma", U16, "0.01")]),
+    pu(UvcPuControlSelectors::WhiteBalanceTemperatureControl, "PU_WHITE_BALANCE_TEMPERATURE_CONTROL", 6, &[field("wWhiteBalanceTemperature", U16, "K")]),
+    pu(UvcPuControlSelectors::WhiteBalanceComponentControl, "PU_WHITE_BALANCE_COMPONENT_CONTROL", 7, &[
+        field("wWhiteBalanceBlue", U16, ""), field("wWhiteBalanceRed", U16, ""),
+    ]),
+    pu(UvcPuControlSelectors::BacklightCompensationControl, "PU_BACKLIGHT_COMPENSATION_CONTROL", 8, &[field("wBacklightCompensation", U16, "")]),
+    pu(UvcPuControlSelectors::GainControl, "PU_GAIN_CONTROL", 9, &[field("wGain", U16, "")]),
+    pu(UvcPuControlSelectors::PowerLineFrequencyControl, "PU_POWER_LINE_FREQUENCY_CONTROL", 10, &[field("bPowerLineFrequency", U8, "")]),
+    pu(UvcPuControlSelectors::HueAutoControl, "PU_HUE_AUTO_CONTROL", 11, &[field("bHueAuto", Bool, "")]),
+    pu(UvcPuControlSelectors::WhiteBalanceTemperatureAutoControl, "PU_WHITE_BALANCE_TEMPERATURE_AUTO_CONTROL", 12, &[field("bWhiteBalanceTemperatureAuto", Bool, "")]),
+    pu(UvcPuControlSelectors::WhiteBalanceComponentAutoControl, "PU_WHITE_BALANCE_COMPONENT_AUTO_CONTROL", 13, &[field("bWhiteBalanceComponentAuto", Bool, "")]),
+    pu(UvcPuControlSelectors::DigitalMultiplierControl, "PU_DIGITAL_MULTIPLIER_CONTROL", 14, &[field("wMultiplierStep", U16, "")]),
+    pu(UvcPuControlSelectors::DigitalMultiplierLimitControl, "PU_DIGITAL_MULTIPLIER_LIMIT_CONTROL", 15, &[field("wMultiplierLimit", U16, "")]),
+    pu(UvcPuControlSelectors::AnalogVideoStandardControl, "PU_ANALOG_VIDEO_STANDARD_CONTROL", 16, &[field("bVideoStandard", U8, "")]),
+    pu(UvcPuControlSelectors::AnalogLockStatusControl, "PU_ANALOG_LOCK_STATUS_CONTROL", 17, &[field("bStatus", U8, "")]),
+    pu(UvcPuControlSelectors::ContrastAutoControl, "PU_CONTRAST_AUTO_CONTROL", 18, &[field("bContrastAuto", Bool, "")]),
+];
+
+impl UvcControlSpec {
+    pub fn lookup(selector: UvcControlSelector) -> Option<&'static UvcControlSpec> {
+        UVC_CONTROLS.iter().find(|spec| spec.selector == selector)
+    }
+
+    /// wLength of every request but GET_INFO and GET_LEN.
+    pub fn w_length(&self) -> u16 {
+        self.fields.iter().map(|field| field.field_type.size() as u16).sum()
+    }
+
+    /// The parameter block holding `values`, one per field.
+    pub fn encode(&self, values: &[i64]) -> Result<Vec<u8>, Error> {
+        if values.len() != self.fields.len() {
+            return Err(anyhow!("{} takes {} values, {} given", self.name, self.fields.len(), values.len()));
+        }
+        let mut data = vec![];
+        for (field, value) in self.fields.iter().zip(values) {
+            let (min, max) = field.field_type.range();
+            if *value < min || *value > max {
+                return Err(anyhow!("{} {} is out of range {}..={}", field.name, value, min, max));
+            }
+            data.extend_from_slice(&value.to_le_bytes()[..field.field_type.size()]);
+        }
+        Ok(data)
+    }
+
+    /// The values of the fields of a GET_CUR, GET_MIN, GET_MAX, GET_RES or GET_DEF response.
+    pub fn decode(&self, data: &[u8]) -> Result<Vec<i64>, Error> {
+        if data.len() < self.w_length() as usize {
+            return Err(anyhow!("{} response of {} bytes, {} expected", self.name, data.len(), self.w_length()));
+        }
+        let mut offset = 0;
+        Ok(self.fields.iter().map(|field| {
+            let value = field.field_type.read(&data[offset..]);
+            offset += field.field_type.size();
+            value
+        }).collect())
+    }
+}
+
+/// The capabilities a GET_INFO response reports, see 4.1.2 of the UVC 1.5 specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UvcControlInfo {
+    pub supports_get: bool,
+    pub supports_set: bool,
+    pub disabled_by_auto: bool,
+    pub autoupdate: bool,
+    pub asynchronous: bool,
+    pub disabled_by_incompatibility: bool,
+}
+
+impl UvcControlInfo {
+    pub fn decode(data: &[u8]) -> Result<UvcControlInfo, Error> {
+        let info = *data.first().ok_or_else(|| anyhow!("Empty GET_INFO response"))?;
+        let bit = |n: u8| info & (1 << n) != 0;
+        Ok(UvcControlInfo {
+            supports_get: bit(0),
+            supports_set: bit(1),
+            disabled_by_auto: bit(2),
+            autoupdate: bit(3),
+            asynchronous: bit(4),
+            disabled_by_incompatibility: bit(5),
+        })
+    }
+}
+
+/// A standard control advertised by a camera terminal or processing unit of a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UvcControl {
+    /// The VideoControl interface the entity belongs to
+    pub iface: u8,
+    /// The terminal or unit ID
+    pub unit_id: u8,
+    pub spec: &'static UvcControlSpec,
+}
+
+impl UvcControl {
+    /// Every control the camera terminals and processing units of `tree` advertise in their
+    /// bmControls, by entity and bit.
+    pub fn find_all(tree: &TreeNode) -> Vec<UvcControl> {
+        let mut controls = vec![];
+        collect_uvc_controls(tree, &mut controls);
+        controls
+    }
+
+    /// The first entity advertising `selector`; cameras rarely have more than one of each.
+    pub fn find(tree: &TreeNode, selector: UvcControlSelector) -> Option<UvcControl> {
+        UvcControl::find_all(tree).into_iter().find(|control| control.spec.selector == selector)
+    }
+
+    /// A request for the control; wValue is the selector, wIndex the entity and interface.
+    /// GET_INFO and GET_LEN have their own fixed lengths.
+    pub fn request(&self, code: UvcRequestCodes) -> SetupPacket {
+        let w_length = match code {
+            UvcRequestCodes::GetInfo => 1,
+            UvcRequestCodes::GetLen => 2,
+            _ => self.spec.w_length(),
+        };
+        // GET requests have D7 set
+        let dir = if code as u8 & 0x80 != 0 { XferDir::ToHost } else { XferDir::ToDev };
+        SetupPacket::new(dir, XferType::Class, Recip::Iface, code as u8, (self.spec.selector.value() as u16) << 8,
+                         (self.unit_id as u16) << 8 | self.iface as u16, w_length)
+    }
+
+    /// SET_CUR with the parameter block holding `values`.
+    pub fn set_cur(&self, values: &[i64]) -> Result<(SetupPacket, Vec<u8>), Error> {
+        Ok((self.request(UvcRequestCodes::SetCur), self.spec.encode(values)?))
+    }
+
+    /// Decodes the response to a GET request other than GET_INFO and GET_LEN.
+    pub fn decode(&self, data: &[u8]) -> Result<Vec<i64>, Error> {
+        self.spec.decode(data)
+    }
+}
+
+fn collect_uvc_controls(node: &TreeNode, controls: &mut Vec<UvcControl>) {
+    if let DescriptorTypes::Interface(iface) = &node.parsed {
+        if iface.b_interface_class == LIBUSB_CLASS_VIDEO && iface.b_interface_sub_class == UvcInterfaceSubClass::VideoControl as u8 {
+            for child in node.children.iter() {
+                let (kind, unit_id, bm_controls) = match &child.parsed {
+                    DescriptorTypes::UvcVcInputTerminal(it) if it.w_terminal_type == UVC_ITT_CAMERA => {
+                        let Some(size) = it.xtra.get(CT_CONTROL_SIZE_OFFSET) else { continue };
+                        let start = CT_CONTROL_SIZE_OFFSET + 1;
+                        let bytes = &it.xtra[start.min(it.xtra.len())..(start + *size as usize).min(it.xtra.len())];
+                        (UvcEntityKind::CameraTerminal, it.b_terminal_id, le_bits(bytes))
+                    }
+                    DescriptorTypes::UvcVcProcessingUnit(pu) => {
+                        // UVC 1.5 widened bmControls to 3 bytes, so iProcessing holds the last one
+                        let high = if pu.b_control_size >= 3 { (pu.i_processing as u32) << 16 } else { 0 };
+                        (UvcEntityKind::ProcessingUnit, pu.b_unit_id, pu.bm_controls as u32 | high)
+                    }
+                    _ => continue,
+                };
+                let advertised = UVC_CONTROLS.iter()
+                    .filter(|spec| spec.selector.kind() == kind && bm_controls & (1 << spec.bit) != 0)
+                    .map(|spec| UvcControl { iface: iface.b_interface_number, unit_id, spec });
+                controls.extend(advertised);
+            }
+        }
+    }
+    node.children.iter().for_each(|child| collect_uvc_controls(child, controls));
+}
+
+fn le_bits(bytes: &[u8]) -> u32 {
+    bytes.iter().take(4).rev().fold(0, |bits, byte| bits << 8 | *byte as u32)
+}
@@ -22,6 +22,7 @@ pub enum UvcInterfaceSubClass {
 
 #[derive(FromPrimitive)]
 #[repr(u8)]
+#[non_exhaustive]
 pub enum UvcVsDescriptorSubtypes {
     Undefined = 0x00,
     InputHeader = 0x01,
@@ -41,6 +42,7 @@ pub enum UvcVsDescriptorSubtypes {
 
 #[derive(FromPrimitive)]
 #[repr(u8)]
+#[non_exhaustive]
 pub enum UvcVcDescriptorSubtypes {
     UvcVcDescriptorUndefined = 0x00,
     UvcVcHeader = 0x01,
@@ -51,7 +53,7 @@ pub enum UvcVcDescriptorSubtypes {
     UvcVcExtensionUnit = 0x06,
 }
 
-#[derive(FromPrimitive)]
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum UvcRequestCodes {
     Undefined = 0x00,
@@ -80,6 +82,68 @@ pub enum UvcRequestTypes {
     SyncDelayControl = 0x09,
 }
 
+// A.9.1 VideoControl Interface Control Selectors
+#[derive(FromPrimitive)]
+#[repr(u8)]
+pub enum UvcVcControlSelectors {
+    ControlUndefined = 0x00,
+    VideoPowerModeControl = 0x01,
+    RequestErrorCodeControl = 0x02,
+}
+
+// A.9.4 Camera Terminal Control Selectors
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum UvcCtControlSelectors {
+    ControlUndefined = 0x00,
+    ScanningModeControl = 0x01,
+    AeModeControl = 0x02,
+    AePriorityControl = 0x03,
+    ExposureTimeAbsoluteControl = 0x04,
+    ExposureTimeRelativeControl = 0x05,
+    FocusAbsoluteControl = 0x06,
+    FocusRelativeControl = 0x07,
+    FocusAutoControl = 0x08,
+    IrisAbsoluteControl = 0x09,
+    IrisRelativeControl = 0x0a,
+    ZoomAbsoluteControl = 0x0b,
+    ZoomRelativeControl = 0x0c,
+    PantiltAbsoluteControl = 0x0d,
+    PantiltRelativeControl = 0x0e,
+    RollAbsoluteControl = 0x0f,
+    RollRelativeControl = 0x10,
+    PrivacyControl = 0x11,
+    FocusSimpleControl = 0x12,
+    WindowControl = 0x13,
+    RegionOfInterestControl = 0x14,
+}
+
+// A.9.5 Processing Unit Control Selectors
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum UvcPuControlSelectors {
+    ControlUndefined = 0x00,
+    BacklightCompensationControl = 0x01,
+    BrightnessControl = 0x02,
+    ContrastControl = 0x03,
+    GainControl = 0x04,
+    PowerLineFrequencyControl = 0x05,
+    HueControl = 0x06,
+    SaturationControl = 0x07,
+    SharpnessControl = 0x08,
+    GammaControl = 0x09,
+    WhiteBalanceTemperatureControl = 0x0a,
+    WhiteBalanceTemperatureAutoControl = 0x0b,
+    WhiteBalanceComponentControl = 0x0c,
+    WhiteBalanceComponentAutoControl = 0x0d,
+    DigitalMultiplierControl = 0x0e,
+    DigitalMultiplierLimitControl = 0x0f,
+    HueAutoControl = 0x10,
+    AnalogVideoStandardControl = 0x11,
+    AnalogLockStatusControl = 0x12,
+    ContrastAutoControl = 0x13,
+}
+
 #[derive(FromPrimitive, Debug)]
 #[repr(u8)]
 pub enum UvcStreamErrorCodes {
@@ -135,13 +199,14 @@ impl UvcFrameHeader {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UvcOutputTerminalDescriptor {
-    b_terminal_id: u8,
-    w_terminal_type: u16,
-    b_assoc_terminal: u8,
-    b_source_id: u8,
-    i_terminal: u8,
+    pub b_terminal_id: u8,
+    pub w_terminal_type: u16,
+    pub b_assoc_terminal: u8,
+    pub b_source_id: u8,
+    pub i_terminal: u8,
 }
 impl UvcOutputTerminalDescriptor {
     pub fn serialize(&self, mut buffer: impl Write) {
@@ -168,16 +233,17 @@ impl UvcOutputTerminalDescriptor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UvcExtensionUnitDescriptor {
-    b_unit_id: u8,
-    guid_extension_code: Uuid,
-    b_num_controls: u8,
-    b_nr_in_pins: u8,
-    ba_source_id: Vec<u8>,
-    b_control_size: u8,
-    bm_controls: Vec<u8>,
-    i_extension: u8,
+    pub b_unit_id: u8,
+    pub guid_extension_code: Uuid,
+    pub b_num_controls: u8,
+    pub b_nr_in_pins: u8,
+    pub ba_source_id: Vec<u8>,
+    pub b_control_size: u8,
+    pub bm_controls: Vec<u8>,
+    pub i_extension: u8,
 }
 
 impl UvcExtensionUnitDescriptor {
@@ -214,15 +280,16 @@ impl UvcExtensionUnitDescriptor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UvcProcessingUnitDescriptor {
-    b_unit_id: u8,
-    b_source_id: u8,
-    w_max_multiplier: u16,
-    b_control_size: u8,
-    bm_controls: u16,
-    i_processing: u8,
-    xtra: Vec<u8>,
+    pub b_unit_id: u8,
+    pub b_source_id: u8,
+    pub w_max_multiplier: u16,
+    pub b_control_size: u8,
+    pub bm_controls: u16,
+    pub i_processing: u8,
+    pub xtra: Vec<u8>,
 }
 
 impl UvcProcessingUnitDescriptor {
@@ -251,13 +318,14 @@ impl UvcProcessingUnitDescriptor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UvcInputTerminalDescriptor {
-    b_terminal_id: u8,
-    w_terminal_type: u16,
-    b_assoc_terminal: u8,
-    i_terminal: u8,
-    xtra: Vec<u8>,
+    pub b_terminal_id: u8,
+    pub w_terminal_type: u16,
+    pub b_assoc_terminal: u8,
+    pub i_terminal: u8,
+    pub xtra: Vec<u8>,
 }
 
 impl UvcInputTerminalDescriptor {
@@ -286,7 +354,8 @@ impl UvcInputTerminalDescriptor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UvcHeaderDescriptor {
     pub bcd_uvc: u16,
     pub w_total_length: u16,
@@ -321,7 +390,8 @@ impl UvcHeaderDescriptor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorUvcInputHeader {
     pub w_total_length: u16,
     pub b_endpoint_address: u8,
@@ -371,8 +441,43 @@ impl DescriptorUvcInputHeader {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Which revision of the VS_PROBE/VS_COMMIT payload a `UvcStreamingControl` was read from or
+/// will be written as. The host picks one through wLength.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UvcStreamingControlVersion {
+    Uvc10,
+    Uvc11,
+    Uvc15,
+}
+
+impl UvcStreamingControlVersion {
+    pub fn size(&self) -> usize {
+        match self {
+            UvcStreamingControlVersion::Uvc10 => 26,
+            UvcStreamingControlVersion::Uvc11 => 34,
+            UvcStreamingControlVersion::Uvc15 => 48,
+        }
+    }
+
+    /// Largest revision that fits in `len` bytes
+    pub fn from_len(len: usize) -> UvcStreamingControlVersion {
+        if len >= UvcStreamingControlVersion::Uvc15.size() {
+            UvcStreamingControlVersion::Uvc15
+        } else if len >= UvcStreamingControlVersion::Uvc11.size() {
+            UvcStreamingControlVersion::Uvc11
+        } else {
+            UvcStreamingControlVersion::Uvc10
+        }
+    }
+}
+
+/// Video probe and commit controls, UVC 1.5 §4.3.1.1. Fields added by later revisions are zero
+/// when read from a shorter payload and are only written if `version` includes them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UvcStreamingControl {
+    pub version: UvcStreamingControlVersion,
     pub bm_hint: u16,
     pub b_format_index: u8,
     pub b_frame_index: u8,
@@ -384,15 +489,50 @@ pub struct UvcStreamingControl {
     pub w_delay: u16,
     pub dw_max_video_frame_size: u32,
     pub dw_max_payload_transfer_size: u32,
+    // UVC 1.1
+    pub dw_clock_frequency: u32,
+    pub bm_framing_info: u8,
+    pub b_prefered_version: u8,
+    pub b_min_version: u8,
+    pub b_max_version: u8,
+    // UVC 1.5
+    pub b_usage: u8,
+    pub b_bit_depth_luma: u8,
+    pub bm_settings: u8,
+    pub b_max_number_of_ref_frames_plus1: u8,
+    pub bm_rate_control_modes: u16,
+    pub bm_layout_per_stream: u64,
 }
 
 impl UvcStreamingControl {
+    pub fn serialize(&self, mut buffer: impl Write) {
+        let format = structure!("<HBBIHHHHHII");
+        format.pack_into(&mut buffer, self.bm_hint, self.b_format_index, self.b_frame_index, self.dw_frame_interval, self.w_key_frame_rate, self.w_pframe_rate,
+                         self.w_comp_quality, self.w_comp_window_size, self.w_delay, self.dw_max_video_frame_size, self.dw_max_payload_transfer_size,
+        ).unwrap();
+        if self.version == UvcStreamingControlVersion::Uvc10 {
+            return;
+        }
+        let format = structure!("<IBBBB");
+        format.pack_into(&mut buffer, self.dw_clock_frequency, self.bm_framing_info, self.b_prefered_version, self.b_min_version, self.b_max_version).unwrap();
+        if self.version == UvcStreamingControlVersion::Uvc11 {
+            return;
+        }
+        let format = structure!("<BBBBHQ");
+        format.pack_into(&mut buffer, self.b_usage, self.b_bit_depth_luma, self.bm_settings, self.b_max_number_of_ref_frames_plus1, self.bm_rate_control_modes,
+                         self.bm_layout_per_stream,
+        ).unwrap();
+    }
+
+    /// Reads the largest revision that fits in `buffer`.
     pub fn deserialize(mut buffer: &mut &[u8]) -> UvcStreamingControl {
+        let version = UvcStreamingControlVersion::from_len(buffer.len());
         let format = structure!("<HBBIHHHHHII");
         let (bm_hint, b_format_index, b_frame_index, dw_frame_interval, w_key_frame_rate, w_pframe_rate, w_comp_quality, w_comp_window_size, w_delay, dw_max_video_frame_size,
             dw_max_payload_transfer_size) = format.unpack_from(&mut buffer
         ).unwrap();
-        let msg = UvcStreamingControl {
+        let mut msg = UvcStreamingControl {
+            version,
             bm_hint,
             b_format_index,
             b_frame_index,
@@ -404,10 +544,34 @@ impl UvcStreamingControl {
             w_delay,
             dw_max_video_frame_size,
             dw_max_payload_transfer_size,
+            dw_clock_frequency: 0,
+            bm_framing_info: 0,
+            b_prefered_version: 0,
+            b_min_version: 0,
+            b_max_version: 0,
+            b_usage: 0,
+            b_bit_depth_luma: 0,
+            bm_settings: 0,
+            b_max_number_of_ref_frames_plus1: 0,
+            bm_rate_control_modes: 0,
+            bm_layout_per_stream: 0,
         };
+        if version != UvcStreamingControlVersion::Uvc10 {
+            let format = structure!("<IBBBB");
+            (msg.dw_clock_frequency, msg.bm_framing_info, msg.b_prefered_version, msg.b_min_version, msg.b_max_version) = format.unpack_from(&mut buffer).unwrap();
+        }
+        if version == UvcStreamingControlVersion::Uvc15 {
+            let format = structure!("<BBBBHQ");
+            (msg.b_usage, msg.b_bit_depth_luma, msg.bm_settings, msg.b_max_number_of_ref_frames_plus1, msg.bm_rate_control_modes,
+             msg.bm_layout_per_stream) = format.unpack_from(&mut buffer).unwrap();
+        }
         return msg;
     }
 
+    pub fn size(&self) -> usize {
+        self.version.size()
+    }
+
     pub fn fps(&self) -> i32 {
         (1.0f32 / (self.dw_frame_interval as f32 / 10000000.0)).round() as i32
     }
@@ -421,7 +585,8 @@ impl UncompressedFormats {
     pub const NV12: Uuid = Uuid::from_bytes([0x32, 0x31, 0x56, 0x4E, 0x00, 0x00, 0x00, 0x10, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71]);
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorUvcFormatUncompressed {
     pub b_format_index: u8,
     pub b_num_frame_descriptors: u8,
@@ -464,7 +629,8 @@ impl DescriptorUvcFormatUncompressed {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorUvcFormatFrameBased {
     pub b_format_index: u8,
     pub b_num_frame_descriptors: u8,
@@ -510,7 +676,8 @@ impl DescriptorUvcFormatFrameBased {
 }
 
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorUvcFormatMjpeg {
     pub b_format_index: u8,
     pub b_num_frame_descriptors: u8,
@@ -539,7 +706,8 @@ impl DescriptorUvcFormatMjpeg {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorUvcFrameUncompressed {
     pub b_frame_index: u8,
     pub bm_capabilities: u8,
@@ -585,7 +753,8 @@ impl DescriptorUvcFrameUncompressed {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorUvcFrameMjpeg {
     pub b_frame_index: u8,
     pub bm_capabilities: u8,
@@ -634,7 +803,8 @@ impl DescriptorUvcFrameMjpeg {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorUvcFrameFrameBased {
     pub b_frame_index: u8,
     pub bm_capabilities: u8,
@@ -680,7 +850,8 @@ impl DescriptorUvcFrameFrameBased {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorUvcVsInterfaceUnknown {
     pub iface_subclass: u8,
     pub bytes: Vec<u8>,
@@ -694,7 +865,8 @@ impl DescriptorUvcVsInterfaceUnknown {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DescriptorUvcVcInterfaceUnknown {
     pub iface_subclass: u8,
     pub bytes: Vec<u8>,
@@ -728,3 +900,36 @@ impl MockVideoConfig {
         MockVideoConfig { width, height, fps, format }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::uvc_proto::{UvcStreamingControl, UvcStreamingControlVersion};
+
+    #[test]
+    fn uvc_streaming_control_round_trip() {
+        let mut bytes: Vec<u8> = (1..=48).collect();
+        for (len, version) in [(48, UvcStreamingControlVersion::Uvc15), (34, UvcStreamingControlVersion::Uvc11), (26, UvcStreamingControlVersion::Uvc10)] {
+            bytes.truncate(len);
+            let ctrl = UvcStreamingControl::deserialize(&mut &bytes[..]);
+            assert_eq!(ctrl.version, version);
+            assert_eq!(ctrl.size(), len);
+            let mut actual = vec![];
+            ctrl.serialize(&mut actual);
+            assert_eq!(actual, bytes);
+        }
+    }
+
+    #[test]
+    fn uvc_streaming_control_uvc15_fields() {
+        let bytes: Vec<u8> = (1..=48).collect();
+        let ctrl = UvcStreamingControl::deserialize(&mut &bytes[..]);
+        assert_eq!(ctrl.b_format_index, 3);
+        assert_eq!(ctrl.dw_clock_frequency, u32::from_le_bytes([27, 28, 29, 30]));
+        assert_eq!(ctrl.bm_framing_info, 31);
+        assert_eq!(ctrl.b_max_version, 34);
+        assert_eq!(ctrl.b_usage, 35);
+        assert_eq!(ctrl.b_max_number_of_ref_frames_plus1, 38);
+        assert_eq!(ctrl.bm_rate_control_modes, u16::from_le_bytes([39, 40]));
+        assert_eq!(ctrl.bm_layout_per_stream, u64::from_le_bytes([41, 42, 43, 44, 45, 46, 47, 48]));
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::TreeNode;
+use crate::usb_proto::DescriptorTypes;
+
+const SPEC_USB_CONFIG: &str = "USB 2.0 §9.6.3";
+const SPEC_USB_INTERFACE: &str = "USB 2.0 §9.6.5";
+const SPEC_USB_ENDPOINT: &str = "USB 2.0 §9.6.6";
+const SPEC_USB_IAD: &str = "USB 3.2 §9.6.4 (Interface Association)";
+const SPEC_UVC_VC_HEADER: &str = "UVC 1.5 §3.7.2";
+const SPEC_UVC_VS_INPUT_HEADER: &str = "UVC 1.5 §3.9.2.1";
+const SPEC_UVC_FORMAT: &str = "UVC 1.5 payload specs §3.1.1 (Format Descriptor)";
+const SPEC_UVC_FRAME: &str = "UVC 1.5 payload specs §3.1.2 (Frame Descriptor)";
+const SPEC_UAC_AC_HEADER: &str = "UAC 1.0 §4.3.2";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A single spec violation found by `TreeNode::validate`.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Location in the tree, e.g. `Config 1 / IAD 0..1 / Interface 1.0 / UvcInputHeader / Format 2`
+    pub path: String,
+    pub spec: &'static str,
+    pub message: String,
+    pub fix: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}: {} ({}); fix: {}", severity, self.path, self.message, self.spec, self.fix)
+    }
+}
+
+impl TreeNode {
+    /// Checks the tree against the USB/UVC/UAC invariants that `fix_tree` maintains, plus
+    /// cross-references such as shared endpoint addresses that it cannot repair.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diags = vec![];
+        validate_node(self, &mut vec![], &mut diags);
+        diags
+    }
+}
+
+struct Reporter<'a> {
+    path: &'a [String],
+    diags: &'a mut Vec<Diagnostic>,
+}
+
+impl<'a> Reporter<'a> {
+    fn report(&mut self, severity: Severity, spec: &'static str, message: String, fix: String) {
+        self.diags.push(Diagnostic { severity, path: self.path.join(" / "), spec, message, fix });
+    }
+}
+
+fn validate_node(node: &TreeNode, path: &mut Vec<String>, diags: &mut Vec<Diagnostic>) {
+    path.push(node_label(&node.parsed));
+    let mut reporter = Reporter { path, diags };
+    match &node.parsed {
+        DescriptorTypes::Config(conf) => {
+            let tree_len = node.serialized_len();
+            if conf.w_total_length as usize != tree_len {
+                reporter.report(Severity::Error, SPEC_USB_CONFIG,
+                                format!("w_total_length is {} but the configuration is {} bytes", conf.w_total_length, tree_len),
+                                format!("set w_total_length to {}", tree_len));
+            }
+            let iface_cnt = node.find_ifaces().len();
+            if conf.b_num_interfaces as usize != iface_cnt {
+                reporter.report(Severity::Error, SPEC_USB_CONFIG,
+                                format!("b_num_interfaces is {} but {} interfaces are present", conf.b_num_interfaces, iface_cnt),
+                                format!("set b_num_interfaces to {}", iface_cnt));
+            }
+            validate_config_refs(node, &mut reporter);
+        }
+        DescriptorTypes::Interface(iface) => {
+            let ep_cnt = node.endpoint_count();
+            if iface.b_num_endpoints as usize != ep_cnt {
+                reporter.report(Severity::Error, SPEC_USB_INTERFACE,
+                                format!("b_num_endpoints is {} but {} endpoints follow", iface.b_num_endpoints, ep_cnt),
+                                format!("set b_num_endpoints to {}", ep_cnt));
+            }
+            let cs_len = node.class_specific_len();
+            for child in node.children.iter() {
+                let (w_total_length, spec) = match &child.parsed {
+                    DescriptorTypes::UvcHeaderDescriptor(hdr) => (hdr.w_total_length, SPEC_UVC_VC_HEADER),
+                    DescriptorTypes::UacAcHeader(hdr) => (hdr.w_total_length, SPEC_UAC_AC_HEADER),
+                    _ => continue,
+                };
+                if w_total_length as usize != cs_len {
+                    reporter.report(Severity::Error, spec,
+                                    format!("header w_total_length is {} but the class-specific descriptors are {} bytes", w_total_length, cs_len),
+                                    format!("set w_total_length to {}", cs_len));
+                }
+            }
+        }
+        DescriptorTypes::UvcInputHeader(hdr) => {
+            let tree_len = node.serialized_len();
+            if hdr.w_total_length as usize != tree_len {
+                reporter.report(Severity::Error, SPEC_UVC_VS_INPUT_HEADER,
+                                format!("w_total_length is {} but the header, formats and frames are {} bytes", hdr.w_total_length, tree_len),
+                                format!("set w_total_length to {}", tree_len));
+            }
+            let fmt_cnt = node.children.iter().filter(|child| child.parsed.is_uvc_format()).count();
+            if hdr.b_control_size == 0 || hdr.bma_controls.len() != fmt_cnt * hdr.b_control_size as usize {
+                reporter.report(Severity::Error, SPEC_UVC_VS_INPUT_HEADER,
+                                format!("bma_controls has {} bytes for {} formats with b_control_size {}", hdr.bma_controls.len(), fmt_cnt, hdr.b_control_size),
+                                format!("provide b_control_size bytes of bmaControls for each of the {} formats", fmt_cnt));
+            }
+        }
+        DescriptorTypes::DescriptorUvcFormatMjpeg(_) |
+        DescriptorTypes::DescriptorUvcFormatUncompressed(_) |
+        DescriptorTypes::UvcFormatFrameBased(_) => {
+            let (b_num_frame_descriptors, b_default_frame_index) = match &node.parsed {
+                DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) => (fmt.b_num_frame_descriptors, fmt.b_default_frame_index),
+                DescriptorTypes::DescriptorUvcFormatUncompressed(fmt) => (fmt.b_num_frame_descriptors, fmt.b_default_frame_index),
+                DescriptorTypes::UvcFormatFrameBased(fmt) => (fmt.b_num_frame_descriptors, fmt.b_default_frame_index),
+                _ => unreachable!(),
+            };
+            let frame_idxs = node.uvc_frame_indices();
+            if b_num_frame_descriptors as usize != frame_idxs.len() {
+                reporter.report(Severity::Error, SPEC_UVC_FORMAT,
+                                format!("b_num_frame_descriptors is {} but {} frames follow", b_num_frame_descriptors, frame_idxs.len()),
+                                format!("set b_num_frame_descriptors to {}", frame_idxs.len()));
+            }
+            if frame_idxs.is_empty() {
+                reporter.report(Severity::Error, SPEC_UVC_FORMAT,
+                                "format has no frame descriptors".to_string(),
+                                "remove the format or add a frame".to_string());
+            } else if !frame_idxs.contains(&b_default_frame_index) {
+                reporter.report(Severity::Error, SPEC_UVC_FORMAT,
+                                format!("b_default_frame_index {} does not match any of the frames {:?}", b_default_frame_index, frame_idxs),
+                                format!("set b_default_frame_index to {}", nearest(b_default_frame_index, &frame_idxs)));
+            }
+        }
+        DescriptorTypes::DescriptorUvcFrameMjpeg(_) |
+        DescriptorTypes::DescriptorUvcFrameUncompressed(_) |
+        DescriptorTypes::UvcFrameFrameBased(_) => {
+            let (default_interval, intervals) = match &node.parsed {
+                DescriptorTypes::DescriptorUvcFrameMjpeg(frame) => (frame.dw_default_frame_interval, &frame.dw_frame_interval),
+                DescriptorTypes::DescriptorUvcFrameUncompressed(frame) => (frame.dw_default_frame_interval, &frame.dw_frame_interval),
+                DescriptorTypes::UvcFrameFrameBased(frame) => (frame.dw_default_frame_interval, &frame.dw_frame_interval),
+                _ => unreachable!(),
+            };
+            if !intervals.is_empty() && !intervals.contains(&default_interval) {
+                reporter.report(Severity::Warning, SPEC_UVC_FRAME,
+                                format!("dw_default_frame_interval {} is not one of the frame intervals {:?}", default_interval, intervals),
+                                format!("set dw_default_frame_interval to {}", nearest(default_interval, intervals)));
+            }
+        }
+        _ => {}
+    }
+
+    for child in node.children.iter() {
+        validate_node(child, path, diags);
+    }
+    path.pop();
+}
+
+/// Checks references between descriptors of one configuration: IAD ranges, VC/AC header
+/// interface lists and endpoint addresses shared between interfaces.
+fn validate_config_refs(config: &TreeNode, reporter: &mut Reporter) {
+    let mut ifaces = vec![];
+    collect_ifaces(config, &mut ifaces);
+
+    let mut endpoints: HashMap<u8, u8> = HashMap::new();
+    collect_endpoints(config, None, &mut |ep, iface| {
+        match endpoints.get(&ep) {
+            Some(owner) if *owner != iface => {
+                reporter.report(Severity::Error, SPEC_USB_ENDPOINT,
+                                format!("endpoint {:#04x} is used by interface {} and interface {}", ep, owner, iface),
+                                "give each interface its own endpoint addresses".to_string());
+            }
+            _ => { endpoints.insert(ep, iface); }
+        }
+    });
+
+    visit(config, &mut |node| {
+        match &node.parsed {
+            DescriptorTypes::InterfaceAssociation(assoc) => {
+                if assoc.b_interface_count == 0 {
+                    reporter.report(Severity::Error, SPEC_USB_IAD,
+                                    format!("IAD starting at interface {} has b_interface_count 0", assoc.b_first_interface),
+                                    "remove the IAD or set b_interface_count".to_string());
+                }
+                let first = assoc.b_first_interface as usize;
+                for num in first..first + assoc.b_interface_count as usize {
+                    if !ifaces.iter().any(|iface| *iface as usize == num) {
+                        reporter.report(Severity::Error, SPEC_USB_IAD,
+                                        format!("IAD covers interface {} which does not exist", num),
+                                        "shrink b_interface_count or remove the IAD".to_string());
+                    }
+                }
+            }
+            DescriptorTypes::UvcHeaderDescriptor(hdr) => check_header_ifaces(reporter, SPEC_UVC_VC_HEADER, hdr.b_in_collection, &hdr.ba_interface_nr, &ifaces),
+            DescriptorTypes::UacAcHeader(hdr) => check_header_ifaces(reporter, SPEC_UAC_AC_HEADER, hdr.b_in_collection, &hdr.ba_interface_nr, &ifaces),
+            _ => {}
+        }
+    });
+}
+
+fn check_header_ifaces(reporter: &mut Reporter, spec: &'static str, b_in_collection: u8, ba_interface_nr: &[u8], ifaces: &[u8]) {
+    if b_in_collection as usize != ba_interface_nr.len() {
+        reporter.report(Severity::Error, spec,
+                        format!("b_in_collection is {} but {} interfaces are listed", b_in_collection, ba_interface_nr.len()),
+                        format!("set b_in_collection to {}", ba_interface_nr.len()));
+    }
+    for num in ba_interface_nr.iter() {
+        if !ifaces.contains(num) {
+            reporter.report(Severity::Error, spec,
+                            format!("header lists streaming interface {} which does not exist", num),
+                            format!("remove {} from ba_interface_nr", num));
+        }
+    }
+}
+
+fn visit(node: &TreeNode, cb: &mut dyn FnMut(&TreeNode)) {
+    cb(node);
+    node.children.iter().for_each(|child| visit(child, cb));
+}
+
+fn collect_ifaces(node: &TreeNode, out: &mut Vec<u8>) {
+    if let DescriptorTypes::Interface(iface) = &node.parsed {
+        if !out.contains(&iface.b_interface_number) {
+            out.push(iface.b_interface_number);
+        }
+    }
+    node.children.iter().for_each(|child| collect_ifaces(child, out));
+}
+
+fn collect_endpoints(node: &TreeNode, iface: Option<u8>, cb: &mut dyn FnMut(u8, u8)) {
+    let iface = match &node.parsed {
+        DescriptorTypes::Interface(desc) => Some(desc.b_interface_number),
+        DescriptorTypes::Endpoint(ep) | DescriptorTypes::HidEndpoint(ep) => {
+            cb(ep.b_endpoint_address, iface.unwrap_or(0));
+            iface
+        }
+        DescriptorTypes::UacEndpoint(ep) => {
+            cb(ep.b_endpoint_address, iface.unwrap_or(0));
+            iface
+        }
+        _ => iface,
+    };
+    node.children.iter().for_each(|child| collect_endpoints(child, iface, cb));
+}
+
+pub(crate) fn node_label(parsed: &DescriptorTypes) -> String {
+    match parsed {
+        DescriptorTypes::Config(conf) => format!("Config {}", conf.b_configuration_value),
+        DescriptorTypes::InterfaceAssociation(assoc) => format!("IAD {}..{}", assoc.b_first_interface,
+                                                                 (assoc.b_first_interface as usize + assoc.b_interface_count as usize).saturating_sub(1)),
+        DescriptorTypes::Interface(iface) => format!("Interface {}.{}", iface.b_interface_number, iface.b_alternate_setting),
+        DescriptorTypes::Endpoint(ep) | DescriptorTypes::HidEndpoint(ep) => format!("Endpoint {:#04x}", ep.b_endpoint_address),
+        DescriptorTypes::UacEndpoint(ep) => format!("Endpoint {:#04x}", ep.b_endpoint_address),
+        DescriptorTypes::DescriptorUvcFormatMjpeg(fmt) => format!("Format {}", fmt.b_format_index),
+        DescriptorTypes::DescriptorUvcFormatUncompressed(fmt) => format!("Format {}", fmt.b_format_index),
+        DescriptorTypes::UvcFormatFrameBased(fmt) => format!("Format {}", fmt.b_format_index),
+        DescriptorTypes::DescriptorUvcFrameMjpeg(frame) => format!("Frame {}", frame.b_frame_index),
+        DescriptorTypes::DescriptorUvcFrameUncompressed(frame) => format!("Frame {}", frame.b_frame_index),
+        DescriptorTypes::UvcFrameFrameBased(frame) => format!("Frame {}", frame.b_frame_index),
+        other => {
+            let name = format!("{:?}", other);
+            name.split('(').next().unwrap_or_default().to_string()
+        }
+    }
+}
+
+/// The value in `values` closest to `value` (the lower one on a tie), or `value` itself if it is
+/// present or `values` is empty.
+pub(crate) fn nearest<T: Copy + Into<i64>>(value: T, values: &[T]) -> T {
+    let target: i64 = value.into();
+    values.iter().copied()
+        .min_by_key(|v| ((*v).into() - target).abs() * 2 + ((*v).into() > target) as i64)
+        .unwrap_or(value)
+}